@@ -1,62 +1,892 @@
-use evaluator::object::{Object, ObjectType};
-use evaluator::evaluator::NULL;
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::rc::Rc;
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+use evaluator::object::{Object, ObjectType, ErrorKind, HashKey, StringBuilder};
+use evaluator::evaluator::{NULL, TRUE, FALSE, values_equal};
+use output;
 
 pub trait BuildInFunction {
-    fn call(&self, Vec<Object>) -> Object;
+    fn arity(&self) -> Arity;
+    fn apply(&self, xs: &[Object]) -> Object;
+
+    fn call(&self, xs: &[Object]) -> Object {
+        match self.arity().check(xs.len()) {
+            Some(error) => error,
+            None => self.apply(xs),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Arity {
+    Exactly(usize),
+    AtLeast(usize),
+    Range(usize, usize),
+}
+
+impl Arity {
+    pub fn check(&self, got: usize) -> Option<Object> {
+        let (ok, want) = match *self {
+            Arity::Exactly(n) => (got == n, format!("={}", n)),
+            Arity::AtLeast(n) => (got >= n, format!(">={}", n)),
+            Arity::Range(lo, hi) => (got >= lo && got <= hi, format!("={}..{}", lo, hi)),
+        };
+        match ok {
+            true => None,
+            false => {
+                Some(Object::new_error(ErrorKind::ArgumentError,
+                                       format!("wrong number of arguments. got {} want{}",
+                                               got,
+                                               want)))
+            }
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Len;
 
 impl BuildInFunction for Len {
-    fn call(&self, xs: Vec<Object>) -> Object {
-        if xs.len() != 1 {
-            return Object::new_error(format!("wrong number of arguments. got {} want=1", xs.len()));
-        }
-        match xs.first() {
-            Some(x) => {
-                match x.object_type {
-                    ObjectType::StringType(ref s) => Object::new_i32(s.len() as i32),
-                    ObjectType::Array(ref a) => Object::new_i32(a.elements.len() as i32),
+    fn arity(&self) -> Arity {
+        Arity::Exactly(1)
+    }
+
+    fn apply(&self, xs: &[Object]) -> Object {
+        match xs[0].object_type {
+            ObjectType::StringType(ref s) => Object::new_int(s.len() as i64),
+            ObjectType::Array(ref a) => Object::new_int(a.elements.len() as i64),
+            _ => {
+                Object::new_error(ErrorKind::TypeError,
+                                  format!("argument to \"len\" not supported. got {:?}",
+                                          xs[0].object_type))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct First;
+
+impl BuildInFunction for First {
+    fn arity(&self) -> Arity {
+        Arity::Exactly(1)
+    }
+
+    fn apply(&self, xs: &[Object]) -> Object {
+        match xs[0].object_type {
+            ObjectType::Array(ref a) => a.elements.first().cloned().unwrap_or(NULL),
+            ObjectType::StringType(ref s) => {
+                match s.chars().next() {
+                    Some(c) => Object::new_string(c.to_string()),
+                    None => NULL,
+                }
+            }
+            _ => {
+                Object::new_error(ErrorKind::TypeError,
+                                  format!("argument to \"first\" not supported. got {:?}",
+                                          xs[0].object_type))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rest;
+
+impl BuildInFunction for Rest {
+    fn arity(&self) -> Arity {
+        Arity::Exactly(1)
+    }
+
+    fn apply(&self, xs: &[Object]) -> Object {
+        match xs[0].object_type {
+            ObjectType::Array(ref a) => {
+                match a.elements.is_empty() {
+                    true => NULL,
+                    false => Object::new_array(a.elements[1..].to_vec()),
+                }
+            }
+            ObjectType::StringType(ref s) => Object::new_string(s.chars().skip(1).collect()),
+            _ => {
+                Object::new_error(ErrorKind::TypeError,
+                                  format!("argument to \"rest\" not supported. got {:?}",
+                                          xs[0].object_type))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Append;
+
+impl BuildInFunction for Append {
+    fn arity(&self) -> Arity {
+        Arity::Exactly(2)
+    }
+
+    fn apply(&self, xs: &[Object]) -> Object {
+        match (&xs[0].object_type, &xs[1].object_type) {
+            (ObjectType::StringType(s), ObjectType::StringType(t)) => {
+                Object::new_string(format!("{}{}", s, t))
+            }
+            (ObjectType::StringType(s), _) => {
+                Object::new_string(format!("{}{}", s, to_str(&xs[1])))
+            }
+            _ => {
+                Object::new_error(ErrorKind::TypeError,
+                                  format!("argument to \"append\" not supported. got {:?}, {:?}",
+                                          xs[0].object_type,
+                                          xs[1].object_type))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Push;
+
+impl BuildInFunction for Push {
+    fn arity(&self) -> Arity {
+        Arity::Exactly(2)
+    }
+
+    fn apply(&self, xs: &[Object]) -> Object {
+        match xs[0].object_type {
+            ObjectType::Array(ref a) => {
+                let mut elements = a.elements.to_vec();
+                elements.push(xs[1].clone());
+                Object::new_array(elements)
+            }
+            _ => {
+                Object::new_error(ErrorKind::TypeError,
+                                  format!("argument to \"push\" not supported. got {:?}",
+                                          xs[0].object_type))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ToArray;
+
+impl BuildInFunction for ToArray {
+    fn arity(&self) -> Arity {
+        Arity::Exactly(1)
+    }
+
+    fn apply(&self, xs: &[Object]) -> Object {
+        match xs[0].object_type {
+            ObjectType::Array(_) => xs[0].clone(),
+            ObjectType::StringType(ref s) => {
+                Object::new_array(s.chars().map(|c| Object::new_string(c.to_string())).collect())
+            }
+            ObjectType::HashType(ref h) => {
+                let mut keys = h.pairs.keys().collect::<Vec<_>>();
+                keys.sort();
+                let pairs = keys.iter()
+                    .map(|k| Object::new_array(vec![k.to_object(), h.pairs[*k].clone()]))
+                    .collect();
+                Object::new_array(pairs)
+            }
+            _ => {
+                Object::new_error(ErrorKind::TypeError,
+                                  format!("argument to \"to_array\" not supported. got {:?}",
+                                          xs[0].object_type))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Puts;
+
+impl BuildInFunction for Puts {
+    fn arity(&self) -> Arity {
+        Arity::AtLeast(0)
+    }
+
+    fn apply(&self, xs: &[Object]) -> Object {
+        for x in xs.iter() {
+            output::write_line(x.inspect());
+        }
+        NULL
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Trace;
+
+impl BuildInFunction for Trace {
+    fn arity(&self) -> Arity {
+        Arity::Exactly(1)
+    }
+
+    fn apply(&self, xs: &[Object]) -> Object {
+        output::write_line(format!("trace: {}", xs[0].inspect()));
+        xs[0].clone()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Repr;
+
+impl BuildInFunction for Repr {
+    fn arity(&self) -> Arity {
+        Arity::Exactly(1)
+    }
+
+    fn apply(&self, xs: &[Object]) -> Object {
+        match xs[0].to_source() {
+            Some(s) => Object::new_string(s),
+            None => {
+                Object::new_error(ErrorKind::TypeError,
+                                  format!("argument to \"repr\" not supported. got {:?}",
+                                          xs[0].object_type))
+            }
+        }
+    }
+}
+
+// Strings come back unchanged rather than quoted like `repr` does. Objects with no source
+// form fall back to `inspect`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Str;
+
+impl BuildInFunction for Str {
+    fn arity(&self) -> Arity {
+        Arity::Exactly(1)
+    }
+
+    fn apply(&self, xs: &[Object]) -> Object {
+        Object::new_string(to_str(&xs[0]))
+    }
+}
+
+// How `str` renders a value, which `append` reuses for its second argument.
+fn to_str(x: &Object) -> String {
+    match x.object_type {
+        ObjectType::StringType(ref s) => s.clone(),
+        ObjectType::Null(_) => "null".to_string(),
+        _ => x.to_source().unwrap_or_else(|| x.inspect()),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pretty;
+
+impl BuildInFunction for Pretty {
+    fn arity(&self) -> Arity {
+        Arity::Exactly(1)
+    }
+
+    fn apply(&self, xs: &[Object]) -> Object {
+        Object::new_string(xs[0].pretty(2))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SizeOf;
+
+impl BuildInFunction for SizeOf {
+    fn arity(&self) -> Arity {
+        Arity::Exactly(1)
+    }
+
+    fn apply(&self, xs: &[Object]) -> Object {
+        Object::new_int(xs[0].estimated_size() as i64)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeepEqual;
+
+impl BuildInFunction for DeepEqual {
+    fn arity(&self) -> Arity {
+        Arity::Exactly(2)
+    }
+
+    fn apply(&self, xs: &[Object]) -> Object {
+        match xs[0].deep_equal(&xs[1]) {
+            true => TRUE,
+            false => FALSE,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AssertEq;
+
+impl BuildInFunction for AssertEq {
+    fn arity(&self) -> Arity {
+        Arity::Exactly(2)
+    }
+
+    fn apply(&self, xs: &[Object]) -> Object {
+        if xs[0] == xs[1] {
+            return NULL;
+        }
+        let data = [("actual", &xs[0]), ("expected", &xs[1])]
+            .iter()
+            .map(|&(k, v)| (HashKey::StringType(k.to_string()), v.clone()))
+            .collect();
+        Object::new_error_with_data(ErrorKind::AssertionError,
+                                    format!("assertion failed: got {}, expected {}",
+                                            xs[0].inspect(),
+                                            xs[1].inspect()),
+                                    Object::new_hash(data))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Num;
+
+impl BuildInFunction for Num {
+    fn arity(&self) -> Arity {
+        Arity::Exactly(1)
+    }
+
+    // Strings that read as an integer become integers, and other finite numbers become
+    // floats. "inf" and "nan" are rejected even though Rust would parse them.
+    fn apply(&self, xs: &[Object]) -> Object {
+        match xs[0].object_type {
+            ObjectType::Integer(_) |
+            ObjectType::Float(_) => xs[0].clone(),
+            ObjectType::StringType(ref s) => {
+                let s = s.trim();
+                if let Ok(n) = i64::from_str(s) {
+                    return Object::new_int(n);
+                }
+                match f64::from_str(s) {
+                    Ok(x) if x.is_finite() => Object::new_f64(x),
                     _ => {
-                        Object::new_error(format!("argument to \"len\" not supported. got {:?}",
-                                                  x.object_type))
+                        Object::new_error(ErrorKind::TypeError,
+                                          format!("could not parse {:?} as a number", s))
+                    }
+                }
+            }
+            _ => {
+                Object::new_error(ErrorKind::TypeError,
+                                  format!("argument to \"num\" not supported. got {:?}",
+                                          xs[0].object_type))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Int;
+
+impl BuildInFunction for Int {
+    fn arity(&self) -> Arity {
+        Arity::Exactly(1)
+    }
+
+    // Floats truncate toward zero. Strings must spell an integer, so int("3.9") is an error
+    // while int(3.9) is 3.
+    fn apply(&self, xs: &[Object]) -> Object {
+        match xs[0].object_type {
+            ObjectType::Integer(_) => xs[0].clone(),
+            ObjectType::Float(x) => {
+                let t = x.trunc();
+                if t >= i64::MIN as f64 && t < -(i64::MIN as f64) {
+                    Object::new_int(t as i64)
+                } else {
+                    Object::new_error(ErrorKind::OverflowError,
+                                      format!("{} is out of integer range", x))
+                }
+            }
+            ObjectType::StringType(ref s) => {
+                match i64::from_str(s.trim()) {
+                    Ok(n) => Object::new_int(n),
+                    Err(_) => {
+                        Object::new_error(ErrorKind::TypeError,
+                                          format!("could not parse {:?} as an integer", s))
                     }
                 }
             }
+            _ => {
+                Object::new_error(ErrorKind::TypeError,
+                                  format!("argument to \"int\" not supported. got {:?}",
+                                          xs[0].object_type))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cmp;
+
+impl BuildInFunction for Cmp {
+    fn arity(&self) -> Arity {
+        Arity::Exactly(2)
+    }
+
+    fn apply(&self, xs: &[Object]) -> Object {
+        match xs[0].partial_cmp(&xs[1]) {
+            Some(o) => Object::new_int(o as i64),
+            None => {
+                Object::new_error(ErrorKind::TypeError,
+                                  format!("arguments to \"cmp\" not comparable. got {:?}, {:?}",
+                                          xs[0].object_type,
+                                          xs[1].object_type))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hash;
+
+impl BuildInFunction for Hash {
+    fn arity(&self) -> Arity {
+        Arity::Exactly(1)
+    }
+
+    fn apply(&self, xs: &[Object]) -> Object {
+        match HashKey::new(&xs[0]) {
+            Some(key) => Object::new_int(key.stable_hash() as i64),
             None => {
-                Object::new_error(format!("wrong number of arguments. got {} want=1", xs.len()))
+                Object::new_error(ErrorKind::TypeError,
+                                  format!("argument to \"hash\" not supported. got {:?}",
+                                          xs[0].object_type))
             }
         }
     }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
-pub struct PrintLn;
+pub struct ReadFile;
 
-impl BuildInFunction for PrintLn {
-    fn call(&self, xs: Vec<Object>) -> Object {
-        println!("{}",
-                 xs.iter()
-                     .map(|x| x.inspect())
-                     .collect::<Vec<String>>()
-                     .join(" "));
-        NULL
+impl BuildInFunction for ReadFile {
+    fn arity(&self) -> Arity {
+        Arity::Exactly(1)
+    }
+
+    fn apply(&self, xs: &[Object]) -> Object {
+        match xs[0].object_type {
+            ObjectType::StringType(ref path) => {
+                match fs::read_to_string(path) {
+                    Ok(contents) => Object::new_string(contents),
+                    Err(e) => Object::new_error(ErrorKind::IOError, format!("{}: {}", path, e)),
+                }
+            }
+            _ => {
+                Object::new_error(ErrorKind::TypeError,
+                                  format!("argument to \"read_file\" not supported. got {:?}",
+                                          xs[0].object_type))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WriteFile;
+
+impl BuildInFunction for WriteFile {
+    fn arity(&self) -> Arity {
+        Arity::Exactly(2)
+    }
+
+    fn apply(&self, xs: &[Object]) -> Object {
+        match (&xs[0].object_type, &xs[1].object_type) {
+            (ObjectType::StringType(path), ObjectType::StringType(contents)) => {
+                match fs::write(path, contents) {
+                    Ok(()) => NULL,
+                    Err(e) => Object::new_error(ErrorKind::IOError, format!("{}: {}", path, e)),
+                }
+            }
+            _ => {
+                Object::new_error(ErrorKind::TypeError,
+                                  format!("argument to \"write_file\" not supported. got {:?}, \
+                                           {:?}",
+                                          xs[0].object_type,
+                                          xs[1].object_type))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GetEnv;
+
+impl BuildInFunction for GetEnv {
+    fn arity(&self) -> Arity {
+        Arity::Exactly(1)
+    }
+
+    fn apply(&self, xs: &[Object]) -> Object {
+        match xs[0].object_type {
+            ObjectType::StringType(ref name) => {
+                match env::var(name) {
+                    Ok(value) => Object::new_string(value),
+                    Err(_) => NULL,
+                }
+            }
+            _ => {
+                Object::new_error(ErrorKind::TypeError,
+                                  format!("argument to \"getenv\" not supported. got {:?}",
+                                          xs[0].object_type))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ErrorKindOf;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ErrorData;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Now;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Once;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Apply;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TakeWhile;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DropWhile;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Bench;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Freeze;
+
+impl BuildInFunction for Freeze {
+    fn arity(&self) -> Arity {
+        Arity::Exactly(1)
+    }
+
+    fn apply(&self, xs: &[Object]) -> Object {
+        let mut x = xs[0].clone();
+        match x.object_type {
+            ObjectType::Array(_) |
+            ObjectType::HashType(_) => deep_freeze(&mut x),
+            _ => {
+                return Object::new_error(ErrorKind::TypeError,
+                                         format!("argument to \"freeze\" not supported. got {:?}",
+                                                 x.object_type))
+            }
+        }
+        x
+    }
+}
+
+// Freezes `x` and every container nested in it. A frozen container only ever holds frozen
+// ones, so its storage is copied only when some element still needs freezing.
+fn deep_freeze(x: &mut Object) {
+    match x.object_type {
+        ObjectType::Array(ref mut a) => {
+            a.frozen = true;
+            if a.elements.iter().any(is_mutable) {
+                Rc::make_mut(&mut a.elements).iter_mut().for_each(deep_freeze);
+            }
+        }
+        ObjectType::HashType(ref mut h) => {
+            h.frozen = true;
+            if h.pairs.values().any(is_mutable) {
+                Rc::make_mut(&mut h.pairs).values_mut().for_each(deep_freeze);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn is_mutable(x: &Object) -> bool {
+    match x.object_type {
+        ObjectType::Array(ref a) => !a.frozen,
+        ObjectType::HashType(ref h) => !h.frozen,
+        _ => false,
     }
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Builder;
+
+impl BuildInFunction for Builder {
+    fn arity(&self) -> Arity {
+        Arity::Exactly(0)
+    }
+
+    fn apply(&self, _: &[Object]) -> Object {
+        Object { object_type: ObjectType::Builder(StringBuilder::new()) }
+    }
+}
+
+// Returns the builder itself, so pushes can be chained.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BuildPush;
+
+impl BuildInFunction for BuildPush {
+    fn arity(&self) -> Arity {
+        Arity::Exactly(2)
+    }
+
+    fn apply(&self, xs: &[Object]) -> Object {
+        match (&xs[0].object_type, &xs[1].object_type) {
+            (ObjectType::Builder(b), ObjectType::StringType(x)) => {
+                b.push(x);
+                xs[0].clone()
+            }
+            (x, y) => {
+                Object::new_error(ErrorKind::TypeError,
+                                  format!("argument to \"build_push\" not supported. got {:?}, \
+                                           {:?}",
+                                          x,
+                                          y))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BuildString;
+
+impl BuildInFunction for BuildString {
+    fn arity(&self) -> Arity {
+        Arity::Exactly(1)
+    }
+
+    fn apply(&self, xs: &[Object]) -> Object {
+        match xs[0].object_type {
+            ObjectType::Builder(ref b) => Object::new_string(b.build()),
+            ref x => {
+                Object::new_error(ErrorKind::TypeError,
+                                  format!("argument to \"build_string\" not supported. got {:?}",
+                                          x))
+            }
+        }
+    }
+}
+
+// The call form of `in`: hashes are checked for the key, arrays for an equal element.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Contains;
+
+impl BuildInFunction for Contains {
+    fn arity(&self) -> Arity {
+        Arity::Exactly(2)
+    }
+
+    fn apply(&self, xs: &[Object]) -> Object {
+        let found = match xs[0].object_type {
+            ObjectType::Array(ref a) => a.elements.iter().any(|x| values_equal(x, &xs[1])),
+            ObjectType::HashType(ref h) => {
+                match HashKey::new(&xs[1]) {
+                    Some(key) => h.pairs.contains_key(&key),
+                    None => {
+                        return Object::new_error(ErrorKind::TypeError,
+                                                 format!("unusable as hash key: {:?}",
+                                                         xs[1].object_type))
+                    }
+                }
+            }
+            ref x => {
+                return Object::new_error(ErrorKind::TypeError,
+                                         format!("argument to \"contains\" not supported. got {:?}",
+                                                 x))
+            }
+        };
+        match found {
+            true => TRUE,
+            false => FALSE,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Watch;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Unwatch;
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum BuildIn {
     Len(Len),
-    PrintLn(PrintLn),
+    First(First),
+    Rest(Rest),
+    Append(Append),
+    Push(Push),
+    ToArray(ToArray),
+    Puts(Puts),
+    Trace(Trace),
+    Repr(Repr),
+    Str(Str),
+    Pretty(Pretty),
+    SizeOf(SizeOf),
+    DeepEqual(DeepEqual),
+    AssertEq(AssertEq),
+    Num(Num),
+    Int(Int),
+    Cmp(Cmp),
+    Hash(Hash),
+    ReadFile(ReadFile),
+    WriteFile(WriteFile),
+    GetEnv(GetEnv),
+    ErrorKindOf(ErrorKindOf),
+    ErrorData(ErrorData),
+    Now(Now),
+    Once(Once),
+    Apply(Apply),
+    TakeWhile(TakeWhile),
+    DropWhile(DropWhile),
+    Bench(Bench),
+    Freeze(Freeze),
+    Builder(Builder),
+    BuildPush(BuildPush),
+    BuildString(BuildString),
+    Contains(Contains),
+    Watch(Watch),
+    Unwatch(Unwatch),
 }
 
 impl BuildIn {
+    pub fn all() -> Vec<BuildIn> {
+        vec![BuildIn::Len(Len),
+             BuildIn::First(First),
+             BuildIn::Rest(Rest),
+             BuildIn::Append(Append),
+             BuildIn::Push(Push),
+             BuildIn::ToArray(ToArray),
+             BuildIn::Puts(Puts),
+             BuildIn::Trace(Trace),
+             BuildIn::Repr(Repr),
+             BuildIn::Str(Str),
+             BuildIn::Pretty(Pretty),
+             BuildIn::SizeOf(SizeOf),
+             BuildIn::DeepEqual(DeepEqual),
+             BuildIn::AssertEq(AssertEq),
+             BuildIn::Num(Num),
+             BuildIn::Int(Int),
+             BuildIn::Cmp(Cmp),
+             BuildIn::Hash(Hash),
+             BuildIn::ReadFile(ReadFile),
+             BuildIn::WriteFile(WriteFile),
+             BuildIn::GetEnv(GetEnv),
+             BuildIn::ErrorKindOf(ErrorKindOf),
+             BuildIn::ErrorData(ErrorData),
+             BuildIn::Now(Now),
+             BuildIn::Once(Once),
+             BuildIn::Apply(Apply),
+             BuildIn::TakeWhile(TakeWhile),
+             BuildIn::DropWhile(DropWhile),
+             BuildIn::Bench(Bench),
+             BuildIn::Freeze(Freeze),
+             BuildIn::Builder(Builder),
+             BuildIn::BuildPush(BuildPush),
+             BuildIn::BuildString(BuildString),
+             BuildIn::Contains(Contains),
+             BuildIn::Watch(Watch),
+             BuildIn::Unwatch(Unwatch)]
+    }
+
     pub fn set_from_string(function_name: &String) -> Option<Object> {
-        match function_name.as_str() {
-            "len" => Some(Object { object_type: ObjectType::BuildIn(BuildIn::Len(Len)) }),
-            "puts" => Some(Object { object_type: ObjectType::BuildIn(BuildIn::PrintLn(PrintLn)) }),
-            _ => None,
+        static TABLE: OnceLock<HashMap<&'static str, BuildIn>> = OnceLock::new();
+        let table = TABLE.get_or_init(|| {
+            BuildIn::all().into_iter().map(|b| (b.name(), b)).collect()
+        });
+        table
+            .get(function_name.as_str())
+            .map(|b| Object { object_type: ObjectType::BuildIn(b.clone()) })
+    }
+
+    pub fn arity(&self) -> Arity {
+        match *self {
+            BuildIn::Len(ref f) => f.arity(),
+            BuildIn::First(ref f) => f.arity(),
+            BuildIn::Rest(ref f) => f.arity(),
+            BuildIn::Append(ref f) => f.arity(),
+            BuildIn::Push(ref f) => f.arity(),
+            BuildIn::ToArray(ref f) => f.arity(),
+            BuildIn::Puts(ref f) => f.arity(),
+            BuildIn::Trace(ref f) => f.arity(),
+            BuildIn::Repr(ref f) => f.arity(),
+            BuildIn::Str(ref f) => f.arity(),
+            BuildIn::Pretty(ref f) => f.arity(),
+            BuildIn::SizeOf(ref f) => f.arity(),
+            BuildIn::DeepEqual(ref f) => f.arity(),
+            BuildIn::AssertEq(ref f) => f.arity(),
+            BuildIn::Num(ref f) => f.arity(),
+            BuildIn::Int(ref f) => f.arity(),
+            BuildIn::Cmp(ref f) => f.arity(),
+            BuildIn::Hash(ref f) => f.arity(),
+            BuildIn::ReadFile(ref f) => f.arity(),
+            BuildIn::WriteFile(ref f) => f.arity(),
+            BuildIn::GetEnv(ref f) => f.arity(),
+            BuildIn::Freeze(ref f) => f.arity(),
+            BuildIn::Builder(ref f) => f.arity(),
+            BuildIn::BuildPush(ref f) => f.arity(),
+            BuildIn::BuildString(ref f) => f.arity(),
+            BuildIn::Contains(ref f) => f.arity(),
+            BuildIn::Watch(_) |
+            BuildIn::Apply(_) |
+            BuildIn::TakeWhile(_) |
+            BuildIn::DropWhile(_) => Arity::Exactly(2),
+            BuildIn::Now(_) => Arity::Exactly(0),
+            BuildIn::Once(_) |
+            BuildIn::Bench(_) |
+            BuildIn::ErrorKindOf(_) |
+            BuildIn::ErrorData(_) |
+            BuildIn::Unwatch(_) => Arity::Exactly(1),
+        }
+    }
+
+    pub fn needs_file_access(&self) -> bool {
+        matches!(*self, BuildIn::ReadFile(_) | BuildIn::WriteFile(_))
+    }
+
+    pub fn needs_env_access(&self) -> bool {
+        matches!(*self, BuildIn::GetEnv(_))
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            BuildIn::Len(_) => "len",
+            BuildIn::First(_) => "first",
+            BuildIn::Rest(_) => "rest",
+            BuildIn::Append(_) => "append",
+            BuildIn::Push(_) => "push",
+            BuildIn::ToArray(_) => "to_array",
+            BuildIn::Puts(_) => "puts",
+            BuildIn::Trace(_) => "trace",
+            BuildIn::Repr(_) => "repr",
+            BuildIn::Str(_) => "str",
+            BuildIn::Pretty(_) => "pretty",
+            BuildIn::SizeOf(_) => "size_of",
+            BuildIn::DeepEqual(_) => "deep_equal",
+            BuildIn::AssertEq(_) => "assert_eq",
+            BuildIn::Num(_) => "num",
+            BuildIn::Int(_) => "int",
+            BuildIn::Cmp(_) => "cmp",
+            BuildIn::Hash(_) => "hash",
+            BuildIn::ReadFile(_) => "read_file",
+            BuildIn::WriteFile(_) => "write_file",
+            BuildIn::GetEnv(_) => "getenv",
+            BuildIn::ErrorKindOf(_) => "error_kind",
+            BuildIn::ErrorData(_) => "error_data",
+            BuildIn::Now(_) => "now",
+            BuildIn::Once(_) => "once",
+            BuildIn::Apply(_) => "apply",
+            BuildIn::TakeWhile(_) => "take_while",
+            BuildIn::DropWhile(_) => "drop_while",
+            BuildIn::Bench(_) => "bench",
+            BuildIn::Freeze(_) => "freeze",
+            BuildIn::Builder(_) => "builder",
+            BuildIn::BuildPush(_) => "build_push",
+            BuildIn::BuildString(_) => "build_string",
+            BuildIn::Contains(_) => "contains",
+            BuildIn::Watch(_) => "watch",
+            BuildIn::Unwatch(_) => "unwatch",
         }
     }
 }
@@ -69,16 +899,86 @@ mod tests {
     fn build_in_let_should_recieve_string() {
         let len = Len {};
         let expect = [Object::new_string("test".to_string())].to_vec();
-        assert_eq!(len.call(expect).to_i32().unwrap(), 4);
+        assert_eq!(len.call(&expect).to_int().unwrap(), 4);
     }
 
     #[test]
     fn build_in_let_should_recieve_array() {
         let len = Len {};
-        let array = Object::new_array([Object::new_i32(1), Object::new_i32(2), Object::new_i32(3)]
+        let array = Object::new_array([Object::new_int(1), Object::new_int(2), Object::new_int(3)]
                                           .to_vec());
         let expect = [array].to_vec();
-        assert_eq!(len.call(expect).to_i32().unwrap(), 3);
+        assert_eq!(len.call(&expect).to_int().unwrap(), 3);
+    }
+
+    #[test]
+    fn build_in_should_reject_wrong_argument_count() {
+        assert_eq!(Len.call(&[]).to_error_message().unwrap(),
+                   "wrong number of arguments. got 0 want=1");
+        assert_eq!(Append.call(&[Object::new_string("a".to_string())]).to_error_kind(),
+                   Some(ErrorKind::ArgumentError));
+    }
+
+    #[test]
+    fn build_in_should_borrow_arguments() {
+        let args = [Object::new_array((0..10000).map(Object::new_int).collect())];
+        for _ in 0..100 {
+            assert_eq!(Len.call(&args), Object::new_int(10000));
+        }
+        assert_eq!(First.call(&args), Object::new_int(0));
+    }
+
+    #[test]
+    fn build_in_repr_should_render_source() {
+        let repr = Repr {};
+        let array = Object::new_array([Object::new_int(1), Object::new_string("a".to_string())]
+                                          .to_vec());
+        assert_eq!(repr.call(&[array]).to_string().unwrap(), "[1, \"a\"]");
+        assert_eq!(repr.call(&[NULL]).to_error_message().unwrap(),
+                   "argument to \"repr\" not supported. got Null(Null)");
+    }
+
+    #[test]
+    fn build_in_pretty_should_indent_by_two_spaces() {
+        let pretty = Pretty {};
+        let array = Object::new_array([Object::new_int(1),
+                                       Object::new_array([Object::new_int(2)].to_vec())]
+                                          .to_vec());
+        assert_eq!(pretty.call(&[array]).to_string().unwrap(),
+                   "[\n  1,\n  [\n    2\n  ]\n]");
+    }
+
+    #[test]
+    fn it_should_resolve_every_build_in_by_name() {
+        for b in BuildIn::all() {
+            let resolved = BuildIn::set_from_string(&b.name().to_string());
+            assert_eq!(resolved, Some(Object { object_type: ObjectType::BuildIn(b.clone()) }));
+        }
+        assert_eq!(BuildIn::set_from_string(&"println".to_string()), None);
+        assert_eq!(BuildIn::set_from_string(&"".to_string()), None);
+    }
+
+    #[test]
+    fn it_should_register_unique_build_in_names() {
+        let mut names = BuildIn::all().iter().map(|b| b.name()).collect::<Vec<_>>();
+        let count = names.len();
+        names.sort();
+        names.dedup();
+        assert_eq!(names.len(), count);
     }
-}
 
+    #[test]
+    fn it_should_check_arity() {
+        let expects = [(Arity::Exactly(1), 1, None),
+                       (Arity::Exactly(1), 2, Some("wrong number of arguments. got 2 want=1")),
+                       (Arity::AtLeast(1), 3, None),
+                       (Arity::AtLeast(1), 0, Some("wrong number of arguments. got 0 want>=1")),
+                       (Arity::Range(1, 2), 2, None),
+                       (Arity::Range(1, 2), 3, Some("wrong number of arguments. got 3 want=1..2"))];
+        for expect in expects.iter() {
+            assert_eq!(expect.0.check(expect.1),
+                       expect.2.map(|m| Object::new_error(ErrorKind::ArgumentError,
+                                                          m.to_string())));
+        }
+    }
+}
@@ -1,16 +1,25 @@
+use std::cell::RefCell;
 use std::collections::HashMap;
+use std::mem;
+use std::rc::Rc;
+use std::str::FromStr;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
 
-use parser::ast::{Node, Statements, AST, Expressions, IfExpression, BlockStatement, Identifier,
-                  HashLiteral};
-use evaluator::object::{Object, ObjectType, Null, Enviroment, Function, HashKey, HashType};
-use buildin::{BuildIn, BuildInFunction};
+use parser::ast::{Node, Statements, AST, Expressions, IfExpression, WhileExpression,
+                  BlockStatement, Identifier, HashLiteral, AssignStatement, LetRecStatement,
+                  SliceExpression};
+use evaluator::object::{Object, ObjectType, Null, Enviroment, Function, HashKey, HashType,
+                        ErrorKind, NativeFunction, Array, float_eq};
+use evaluator::options::{EvalOptions, divide, modulo, float_modulo};
+use buildin::{BuildIn, BuildInFunction, Arity, ToArray};
+use utils::utc_date_time;
 
-const TRUE: Object = Object { object_type: ObjectType::Boolean(true) };
-const FALSE: Object = Object { object_type: ObjectType::Boolean(false) };
+pub const TRUE: Object = Object { object_type: ObjectType::Boolean(true) };
+pub const FALSE: Object = Object { object_type: ObjectType::Boolean(false) };
 pub const NULL: Object = Object { object_type: ObjectType::Null(Null) };
 
 fn is_error(x: &Object) -> bool {
-    x.object_type.to_type() == Object::new_error("".to_string()).object_type.to_type()
+    matches!(x.object_type, ObjectType::Error(_, _, _))
 }
 
 pub fn eval(node: AST, env: &mut Enviroment) -> Object {
@@ -19,7 +28,10 @@ pub fn eval(node: AST, env: &mut Enviroment) -> Object {
         Program(x) => eval_program(&x.statements, env),
         BlockStatement(x) => eval_block_statement(x, env),
         ReturnStatement(x) => {
-            let val = eval(x.return_value.to_ast(), env);
+            let val = match x.return_value {
+                Some(v) => eval(v.to_ast(), env),
+                None => NULL,
+            };
             if is_error(&val) {
                 return val;
             }
@@ -27,25 +39,36 @@ pub fn eval(node: AST, env: &mut Enviroment) -> Object {
         }
         LetStatement(x) => {
             let val = eval(x.value.to_ast(), env);
+            let val = check_heap_limit(val, env);
             if is_error(&val) {
                 return val;
             }
-            let result = env.set(x.name.value.clone(), val);
-            result
+            match bind(&x.name, val.clone(), env) {
+                Some(old) => notify_watcher(&x.name.value, old, val, env),
+                None => val,
+            }
         }
         Identifier(ref x) => eval_identifier(x, env),
         IfExpression(ref x) => eval_if_expression(x, env),
+        WhileExpression(ref x) => eval_while_expression(x, env),
         ExpressionStatement(x) => eval(x.expression.to_ast(), env),
-        IntegerLiteral(n) => Object::new_i32(n.value),
+        TestStatement(_) => NULL,
+        AssignStatement(x) => eval_assign_statement(x, env),
+        LetRecStatement(x) => eval_let_rec_statement(x, env),
+        IntegerLiteral(n) => Object::new_int(n.value),
+        FloatLiteral(n) => Object::new_f64(n.value),
         StringLiteral(n) => Object::new_string(n.value),
         ArrayLiteral(x) => {
             let elements = eval_expression(&x.elements, env);
             match elements {
-                Ok(x) => Object::new_array(x),
+                Ok(x) => check_heap_limit(Object::new_array(x), env),
                 Err(x) => x,
             }
         }
-        HashLiteral(h) => eval_hash_literal(h, env),
+        HashLiteral(h) => {
+            let hash = eval_hash_literal(h, env);
+            check_heap_limit(hash, env)
+        }
         Boolean(n) => native_bool_to_boolean_obj(n.value),
         PrefixExpression(x) => {
             let operator = x.operator.clone();
@@ -53,7 +76,7 @@ pub fn eval(node: AST, env: &mut Enviroment) -> Object {
             if is_error(&right) {
                 return right;
             }
-            eval_prefix_expression(operator, right)
+            eval_prefix_expression(operator, right).with_position(&x.token)
         }
         InfixExpression(x) => {
             let operator = x.operator.clone();
@@ -61,11 +84,21 @@ pub fn eval(node: AST, env: &mut Enviroment) -> Object {
             if is_error(&left) {
                 return left;
             }
+            match (operator.as_str(), left.is_truthy()) {
+                ("&&", false) => return FALSE,
+                ("||", true) => return TRUE,
+                _ => {}
+            }
             let right = eval(x.right.to_ast(), env);
             if is_error(&right) {
                 return right;
             }
-            eval_infix_expression(operator, left, right)
+            if operator == "&&" || operator == "||" {
+                return native_bool_to_boolean_obj(right.is_truthy());
+            }
+            let result = eval_infix_expression(operator, left, right, env.options())
+                .with_position(&x.token);
+            check_heap_limit(result, env)
         }
         FunctionLiteral(x) => Object::new_function(x.parameters.clone(), x.body.clone(), env),
         CallExpression(x) => {
@@ -73,9 +106,18 @@ pub fn eval(node: AST, env: &mut Enviroment) -> Object {
             if is_error(&func) {
                 return func;
             }
+            match func.object_type {
+                ObjectType::BuildIn(BuildIn::ErrorKindOf(_)) => {
+                    return eval_error_argument(&x.arguments, env, error_kind_of)
+                }
+                ObjectType::BuildIn(BuildIn::ErrorData(_)) => {
+                    return eval_error_argument(&x.arguments, env, error_data_of)
+                }
+                _ => {}
+            }
             let args = eval_expression(&x.arguments, env);
             match args {
-                Ok(a) => apply_function(func, a),
+                Ok(a) => apply_function(func, a, env),
                 Err(x) => x,
             }
         }
@@ -90,32 +132,389 @@ pub fn eval(node: AST, env: &mut Enviroment) -> Object {
             }
             eval_index_expression(left, index)
         }
+        SliceExpression(z) => eval_slice_expression(z, env),
     }
 }
 
-fn apply_function(func: Object, args: Vec<Object>) -> Object {
+fn apply_function(func: Object, args: Vec<Object>, env: &mut Enviroment) -> Object {
     match func.object_type {
         ObjectType::Function(f) => {
+            if let Some(error) = Arity::Exactly(f.parameters.len()).check(args.len()) {
+                return error;
+            }
             let mut env = extend_function_env(&f, args);
+            if env.depth() > env.options().max_scope_depth {
+                return Object::new_error(ErrorKind::LimitError,
+                                         "maximum scope depth exceeded".to_string());
+            }
             let evaluated = eval(f.body.to_enum().to_ast(), &mut env);
             unwrap_return_value(evaluated)
         }
         ObjectType::BuildIn(b) => {
+            if let Some(error) = b.arity().check(args.len()) {
+                return error;
+            }
+            if b.needs_file_access() && !env.options().allow_file_access {
+                return Object::new_error(ErrorKind::LimitError,
+                                         format!("{} is disabled: file access is not allowed",
+                                                 b.name()));
+            }
+            if b.needs_env_access() && !env.options().allow_env_access {
+                return Object::new_error(ErrorKind::LimitError,
+                                         format!("{} is disabled: environment access is not \
+                                                  allowed",
+                                                 b.name()));
+            }
             match b {
-                BuildIn::Len(l) => l.call(args),
-                BuildIn::PrintLn(l) => l.call(args),
+                BuildIn::Len(l) => l.call(&args),
+                BuildIn::First(l) => l.call(&args),
+                BuildIn::Rest(l) => l.call(&args),
+                BuildIn::Append(l) => l.call(&args),
+                BuildIn::Push(l) => l.call(&args),
+                BuildIn::ToArray(ref l) => eval_to_array(l, args, env),
+                BuildIn::Puts(l) => l.call(&args),
+                BuildIn::Trace(l) => l.call(&args),
+                BuildIn::Repr(l) => l.call(&args),
+                BuildIn::Str(l) => l.call(&args),
+                BuildIn::Pretty(l) => l.call(&args),
+                BuildIn::SizeOf(l) => l.call(&args),
+                BuildIn::DeepEqual(l) => l.call(&args),
+                BuildIn::AssertEq(l) => l.call(&args),
+                BuildIn::Num(l) => l.call(&args),
+                BuildIn::Int(l) => l.call(&args),
+                BuildIn::Cmp(l) => l.call(&args),
+                BuildIn::Hash(l) => l.call(&args),
+                BuildIn::ReadFile(l) => l.call(&args),
+                BuildIn::WriteFile(l) => l.call(&args),
+                BuildIn::GetEnv(l) => l.call(&args),
+                BuildIn::Freeze(l) => l.call(&args),
+                BuildIn::Builder(l) => l.call(&args),
+                BuildIn::BuildPush(l) => l.call(&args),
+                BuildIn::BuildString(l) => l.call(&args),
+                BuildIn::Contains(l) => l.call(&args),
+                BuildIn::ErrorKindOf(_) => error_kind_of(&args[0]),
+                BuildIn::ErrorData(_) => error_data_of(&args[0]),
+                BuildIn::Now(_) => eval_now(env),
+                BuildIn::Once(_) => eval_once(args, env),
+                BuildIn::Apply(_) => eval_apply(args, env),
+                BuildIn::TakeWhile(_) => eval_split_while("take_while", true, args, env),
+                BuildIn::DropWhile(_) => eval_split_while("drop_while", false, args, env),
+                BuildIn::Bench(_) => eval_bench(args, env),
+                BuildIn::Watch(_) => eval_watch(args, env),
+                BuildIn::Unwatch(_) => eval_unwatch(args, env),
+            }
+        }
+        ObjectType::Native(f) => f.call(args),
+        _ => Object::new_error(ErrorKind::TypeError, format!("not a function {:?}", func)),
+    }
+}
+
+// Watchers are kept on the enviroment `watch` is called in, so a watch registered inside
+// a function body only lives as long as that call. Watching an unbound name is allowed;
+// the callback fires once the name is bound again after its first binding.
+fn eval_watch(args: Vec<Object>, env: &mut Enviroment) -> Object {
+    match (&args[0].object_type, &args[1].object_type) {
+        (&ObjectType::StringType(ref name), &ObjectType::Function(_)) |
+        (&ObjectType::StringType(ref name), &ObjectType::BuildIn(_)) |
+        (&ObjectType::StringType(ref name), &ObjectType::Native(_)) => {
+            env.watch(name.clone(), args[1].clone());
+            NULL
+        }
+        _ => {
+            Object::new_error(ErrorKind::TypeError,
+                              format!("argument to \"watch\" not supported. got {:?}, {:?}",
+                                      args[0].object_type,
+                                      args[1].object_type))
+        }
+    }
+}
+
+fn eval_unwatch(args: Vec<Object>, env: &mut Enviroment) -> Object {
+    match args[0].object_type {
+        ObjectType::StringType(ref name) => native_bool_to_boolean_obj(env.unwatch(name)),
+        _ => {
+            Object::new_error(ErrorKind::TypeError,
+                              format!("argument to \"unwatch\" not supported. got {:?}",
+                                      args[0].object_type))
+        }
+    }
+}
+
+// error_kind and error_data are the calls that receive their argument even when evaluating
+// it fails, so scripts can tell what went wrong instead of just propagating the error.
+fn eval_error_argument(arguments: &[Box<Expressions>],
+                       env: &mut Enviroment,
+                       f: fn(&Object) -> Object)
+                       -> Object {
+    if let Some(error) = Arity::Exactly(1).check(arguments.len()) {
+        return error;
+    }
+    f(&eval(arguments[0].to_ast(), env))
+}
+
+fn eval_now(env: &Enviroment) -> Object {
+    let time = env.options().fixed_time.unwrap_or_else(SystemTime::now);
+    let seconds = match time.duration_since(UNIX_EPOCH) {
+        Ok(d) => d.as_secs() as i64,
+        Err(e) => -(e.duration().as_secs_f64().ceil() as i64),
+    };
+    let (year, month, day, hour, minute, second) = utc_date_time(seconds);
+    let fields = [("year", year),
+                  ("month", month),
+                  ("day", day),
+                  ("hour", hour),
+                  ("minute", minute),
+                  ("second", second)];
+    Object::new_hash(fields.iter()
+                         .map(|&(k, v)| (HashKey::StringType(k.to_string()), Object::new_int(v)))
+                         .collect())
+}
+
+fn check_thunk(name: &str, x: &Object) -> Option<Object> {
+    match x.object_type {
+        ObjectType::Function(ref f) if f.parameters.is_empty() => None,
+        ref x => {
+            Some(Object::new_error(ErrorKind::TypeError,
+                                   format!("argument to {:?} must be a function with no \
+                                            parameters. got {:?}",
+                                           name,
+                                           x)))
+        }
+    }
+}
+
+// Calls the thunk once and returns {"result": ..., "ms": ...}, where "ms" is the elapsed
+// wall-clock time as a float. An error from the thunk is returned as is.
+fn eval_bench(args: Vec<Object>, env: &mut Enviroment) -> Object {
+    if let Some(error) = check_thunk("bench", &args[0]) {
+        return error;
+    }
+    let start = Instant::now();
+    let result = apply_function(args[0].clone(), vec![], env);
+    let ms = start.elapsed().as_secs_f64() * 1000.0;
+    if is_error(&result) {
+        return result;
+    }
+    let fields = [("result", result), ("ms", Object::new_f64(ms))];
+    Object::new_hash(fields.iter()
+                         .map(|&(k, ref v)| (HashKey::StringType(k.to_string()), v.clone()))
+                         .collect())
+}
+
+// The thunk runs on the first call and its result is returned from then on. An error is
+// not cached, so a failed initialization is retried on the next call.
+fn eval_once(args: Vec<Object>, env: &Enviroment) -> Object {
+    if let Some(error) = check_thunk("once", &args[0]) {
+        return error;
+    }
+    let thunk = args[0].clone();
+    let options = env.options().clone();
+    let cache: Rc<RefCell<Option<Object>>> = Rc::new(RefCell::new(None));
+    let once = NativeFunction::new("once", move |args| {
+        if let Some(error) = Arity::Exactly(0).check(args.len()) {
+            return error;
+        }
+        if let Some(ref x) = *cache.borrow() {
+            return x.clone();
+        }
+        let mut env = Enviroment::with_options(options.clone());
+        let result = apply_function(thunk.clone(), vec![], &mut env);
+        if !is_error(&result) {
+            *cache.borrow_mut() = Some(result.clone());
+        }
+        result
+    });
+    Object { object_type: ObjectType::Native(once) }
+}
+
+fn check_function(name: &str, position: &str, x: &Object) -> Option<Object> {
+    match x.object_type {
+        ObjectType::Function(_) |
+        ObjectType::BuildIn(_) |
+        ObjectType::Native(_) => None,
+        ref x => {
+            Some(Object::new_error(ErrorKind::TypeError,
+                                   format!("{} argument to {:?} must be a function. got {:?}",
+                                           position,
+                                           name,
+                                           x)))
+        }
+    }
+}
+
+// The values `x` iterates over, pulled one at a time. Arrays give their elements. A hash
+// whose "__iter__" key holds a function is iterable too: the function is called with the step
+// number 0, 1, 2, ... and returns the next value, or null once it is done. Closures can't
+// keep state between calls, hence the step argument.
+struct Iteration {
+    elements: Option<Rc<Vec<Object>>>,
+    next: Object,
+    step: usize,
+}
+
+impl Iteration {
+    fn new(name: &str, x: &Object) -> Result<Self, Object> {
+        let next = match x.object_type {
+            ObjectType::Array(ref a) => {
+                return Ok(Iteration {
+                              elements: Some(a.elements.clone()),
+                              next: NULL,
+                              step: 0,
+                          })
+            }
+            ObjectType::HashType(ref h) => {
+                h.pairs.get(&HashKey::StringType("__iter__".to_string())).cloned()
+            }
+            _ => None,
+        };
+        match next {
+            Some(ref f) if check_function(name, "first", f).is_none() => {
+                Ok(Iteration {
+                       elements: None,
+                       next: f.clone(),
+                       step: 0,
+                   })
+            }
+            _ => {
+                Err(Object::new_error(ErrorKind::TypeError,
+                                      format!("first argument to {:?} must be an array or an \
+                                               iterable. got {:?}",
+                                              name,
+                                              x.object_type)))
             }
         }
-        _ => Object::new_error(format!("not a function {:?}", func)),
     }
+
+    // The next value, None once the iteration is done, or the error `__iter__` returned.
+    fn next(&mut self, env: &mut Enviroment) -> Option<Result<Object, Object>> {
+        let step = self.step;
+        self.step += 1;
+        if let Some(ref elements) = self.elements {
+            return elements.get(step).cloned().map(Ok);
+        }
+        let value = apply_function(self.next.clone(), vec![Object::new_int(step as i64)], env);
+        match value.object_type {
+            ObjectType::Null(_) => None,
+            ObjectType::Error(_, _, _) => Some(Err(value)),
+            _ => Some(Ok(value)),
+        }
+    }
+
+    fn collect(mut self, env: &mut Enviroment) -> Result<Vec<Object>, Object> {
+        let mut values = vec![];
+        while let Some(value) = self.next(env) {
+            values.push(value?);
+        }
+        Ok(values)
+    }
+}
+
+// Collects every value of `x`; see Iteration.
+fn iterate(name: &str, x: &Object, env: &mut Enviroment) -> Result<Vec<Object>, Object> {
+    Iteration::new(name, x)?.collect(env)
+}
+
+// Iterables are collected; anything else converts like the pure builtin.
+fn eval_to_array(f: &ToArray, args: Vec<Object>, env: &mut Enviroment) -> Object {
+    let iterable = match args[0].object_type {
+        ObjectType::HashType(ref h) => {
+            h.pairs.contains_key(&HashKey::StringType("__iter__".to_string()))
+        }
+        _ => false,
+    };
+    if !iterable {
+        return f.call(&args);
+    }
+    match iterate("to_array", &args[0], env) {
+        Ok(x) => Object::new_array(x),
+        Err(e) => e,
+    }
+}
+
+fn eval_apply(args: Vec<Object>, env: &mut Enviroment) -> Object {
+    if let Some(error) = check_function("apply", "first", &args[0]) {
+        return error;
+    }
+    match args[1].object_type {
+        ObjectType::Array(ref a) => apply_function(args[0].clone(), a.elements.to_vec(), env),
+        ref x => {
+            Object::new_error(ErrorKind::TypeError,
+                              format!("second argument to \"apply\" must be an array. got {:?}",
+                                      x))
+        }
+    }
+}
+
+// take_while keeps the elements before the first one the predicate rejects and drop_while
+// keeps the rest. Values are pulled only as far as needed, so take_while also ends on an
+// unbounded iterable. A predicate error is returned as is.
+fn eval_split_while(name: &str, take: bool, args: Vec<Object>, env: &mut Enviroment) -> Object {
+    if let Some(error) = check_function(name, "second", &args[1]) {
+        return error;
+    }
+    let mut iteration = match Iteration::new(name, &args[0]) {
+        Ok(x) => x,
+        Err(e) => return e,
+    };
+    let mut head = vec![];
+    let mut rejected = None;
+    while let Some(element) = iteration.next(env) {
+        let element = match element {
+            Ok(x) => x,
+            Err(e) => return e,
+        };
+        let result = apply_function(args[1].clone(), vec![element.clone()], env);
+        if is_error(&result) {
+            return result;
+        }
+        if !result.is_truthy() {
+            rejected = Some(element);
+            break;
+        }
+        head.push(element);
+    }
+    if take {
+        return Object::new_array(head);
+    }
+    let mut tail: Vec<Object> = rejected.into_iter().collect();
+    match iteration.collect(env) {
+        Ok(rest) => tail.extend(rest),
+        Err(e) => return e,
+    }
+    Object::new_array(tail)
+}
+
+fn error_kind_of(x: &Object) -> Object {
+    match x.to_error_kind() {
+        Some(kind) => Object::new_string(kind.name().to_string()),
+        None => NULL,
+    }
+}
+
+fn error_data_of(x: &Object) -> Object {
+    x.to_error_data().unwrap_or(NULL)
+}
+
+fn notify_watcher(name: &String, old: Object, new: Object, env: &mut Enviroment) -> Object {
+    let callback = match env.get_watcher(name) {
+        Some(w) => w.clone(),
+        None => return new,
+    };
+    let result = apply_function(callback, vec![old, new.clone()], env);
+    if is_error(&result) {
+        return result;
+    }
+    new
 }
 
 fn extend_function_env(func: &Function, args: Vec<Object>) -> Enviroment {
     let mut env = Enviroment::new_enclosed_enviroment(func.env.clone());
-    for i in 0..func.parameters.len() {
-        let p = &func.parameters[i];
-        let a = &args[i];
-        env.set(p.value.clone(), a.clone());
+    for (slot, f) in func.group.iter().enumerate() {
+        let member = Object::from_rec_function(f, &func.group, &func.env);
+        env.assign_at(f.name.clone(), slot, member);
+    }
+    for (p, a) in func.parameters.iter().zip(args) {
+        bind(p, a, &mut env);
     }
     env
 }
@@ -141,22 +540,272 @@ fn eval_expression(expressions: &Vec<Box<Expressions>>,
     Ok(result)
 }
 
+fn eval_assign_statement(x: AssignStatement, env: &mut Enviroment) -> Object {
+    let target = match x.target {
+        Expressions::IndexExpression(t) => t,
+        Expressions::Identifier(name) => return eval_reassignment(name, x.value, env),
+        t => {
+            return Object::new_error(ErrorKind::TypeError,
+                                     format!("invalid assignment target {}", t.string()))
+        }
+    };
+    let name = match *target.left {
+        Expressions::Identifier(i) => i,
+        t => {
+            return Object::new_error(ErrorKind::TypeError,
+                                     format!("invalid assignment target {}", t.string()))
+        }
+    };
+
+    let current = eval_identifier(&name, env);
+    if is_error(&current) {
+        return current;
+    }
+    let index = eval(target.index.to_ast(), env);
+    if is_error(&index) {
+        return index;
+    }
+    let value = eval(x.value.to_ast(), env);
+    if is_error(&value) {
+        return value;
+    }
+
+    // Unless a watcher wants the old value or the heap limit may reject the new one, the
+    // binding is moved out of the enviroment, so an unshared container is updated in place
+    // instead of copied.
+    let taken = match env.get_watcher(&name.value).is_some() ||
+                      env.options().max_heap_bytes.is_some() {
+        true => None,
+        false => env.take(&name.value),
+    };
+    let restore = taken.is_some();
+    let mut updated = match taken {
+        Some(x) => {
+            drop(current);
+            x
+        }
+        None => current,
+    };
+    if let Err(e) = eval_index_assignment(&mut updated, index, value) {
+        if restore {
+            env.reassign(name.value.clone(), updated);
+        }
+        return e;
+    }
+
+    let updated = check_heap_limit(updated, env);
+    if is_error(&updated) {
+        return updated;
+    }
+    match env.reassign(name.value.clone(), updated.clone()) {
+        Some(old) => notify_watcher(&name.value, old, updated, env),
+        None => updated,
+    }
+}
+
+fn eval_reassignment(name: Identifier, value: Expressions, env: &mut Enviroment) -> Object {
+    let value = check_heap_limit(eval(value.to_ast(), env), env);
+    if is_error(&value) {
+        return value;
+    }
+    let old = match name.resolved.and_then(|at| env.reassign_at(at, value.clone())) {
+        Some(old) => Some(old),
+        None => env.reassign(name.value.clone(), value.clone()),
+    };
+    match old {
+        Some(old) => notify_watcher(&name.value, old, value, env),
+        None => {
+            Object::new_error(ErrorKind::NameError,
+                              format!("identifier not found: {}", name.value))
+        }
+    }
+}
+
+fn eval_index_assignment(left: &mut Object, index: Object, value: Object) -> Result<(), Object> {
+    let frozen = match left.object_type {
+        ObjectType::Array(ref x) => x.frozen,
+        ObjectType::HashType(ref x) => x.frozen,
+        _ => false,
+    };
+    if frozen {
+        return Err(Object::new_error(ErrorKind::TypeError,
+                                     "cannot mutate frozen value".to_string()));
+    }
+
+    match (&mut left.object_type, index.object_type) {
+        (&mut ObjectType::StringType(ref mut s), ObjectType::Integer(i)) => {
+            let replacement = match value.object_type {
+                ObjectType::StringType(ref r) if r.chars().count() == 1 => r.clone(),
+                x => {
+                    return Err(Object::new_error(ErrorKind::TypeError,
+                                                 format!("string index assignment requires a \
+                                                          single character. got {:?}",
+                                                         x)))
+                }
+            };
+            let mut chars: Vec<String> = s.chars().map(|c| c.to_string()).collect();
+            if chars.is_empty() {
+                return Err(Object::new_error(ErrorKind::IndexError,
+                                             format!("index out of range: string is empty got={}",
+                                                     i)));
+            }
+            let max_index = (chars.len() - 1) as i64;
+            let position = from_end(i, chars.len());
+            if position < 0 || position > max_index {
+                return Err(Object::new_error(ErrorKind::IndexError,
+                                             format!("index out of range: max={} got={}",
+                                                     max_index,
+                                                     i)));
+            }
+            chars[position as usize] = replacement;
+            *s = chars.concat();
+            Ok(())
+        }
+        (&mut ObjectType::Array(ref mut xs), ObjectType::Integer(i)) => {
+            if xs.elements.is_empty() {
+                return Err(Object::new_error(ErrorKind::IndexError,
+                                             format!("index out of range: array is empty got={}",
+                                                     i)));
+            }
+            let max_index = (xs.elements.len() - 1) as i64;
+            let position = from_end(i, xs.elements.len());
+            if position < 0 || position > max_index {
+                return Err(Object::new_error(ErrorKind::IndexError,
+                                             format!("index out of range: max={} got={}",
+                                                     max_index,
+                                                     i)));
+            }
+            // Copies the elements only when another holder still shares them.
+            Rc::make_mut(&mut xs.elements)[position as usize] = value;
+            Ok(())
+        }
+        (&mut ObjectType::HashType(ref mut h), index_type) => {
+            let index = Object { object_type: index_type };
+            match HashKey::new(&index) {
+                Some(key) => {
+                    Rc::make_mut(&mut h.pairs).insert(key, value);
+                    Ok(())
+                }
+                None => Err(Object::new_error(ErrorKind::TypeError,
+                                              format!("unusable as hash key: {:?}",
+                                                      index.object_type))),
+            }
+        }
+        (x, _) => {
+            Err(Object::new_error(ErrorKind::TypeError,
+                                  format!("index assignment not supported {:?}", x)))
+        }
+    }
+}
+
+// A negative index counts back from the end, so -1 is the last element.
+fn from_end(i: i64, len: usize) -> i64 {
+    if i < 0 { i + len as i64 } else { i }
+}
+
 fn eval_index_expression(left: Object, index: Object) -> Object {
     match left.object_type {
         ObjectType::Array(xs) => {
             if let ObjectType::Integer(i) = index.object_type {
-                let max_index = xs.elements.len() - 1;
-                if max_index < i as usize || i < 0 {
-                    Object::new_error(format!("index out of range: max={} got={}", max_index, i))
+                if xs.elements.is_empty() {
+                    return Object::new_error(ErrorKind::IndexError,
+                                             format!("index out of range: array is empty got={}",
+                                                     i));
+                }
+                let max_index = (xs.elements.len() - 1) as i64;
+                let position = from_end(i, xs.elements.len());
+                if position < 0 || position > max_index {
+                    Object::new_error(ErrorKind::IndexError,
+                                      format!("index out of range: max={} got={}", max_index, i))
+                } else {
+                    (&xs.elements)[position as usize].clone()
+                }
+            } else {
+                Object::new_error(ErrorKind::TypeError,
+                                  format!("index operator not supported {:?}", index.object_type))
+            }
+        }
+        ObjectType::StringType(s) => {
+            if let ObjectType::Integer(i) = index.object_type {
+                // Index by character rather than byte so multi-byte text cannot split a char.
+                let chars: Vec<char> = s.chars().collect();
+                if chars.is_empty() {
+                    return Object::new_error(ErrorKind::IndexError,
+                                             format!("index out of range: string is empty got={}",
+                                                     i));
+                }
+                let max_index = (chars.len() - 1) as i64;
+                let position = from_end(i, chars.len());
+                if position < 0 || position > max_index {
+                    Object::new_error(ErrorKind::IndexError,
+                                      format!("index out of range: max={} got={}", max_index, i))
                 } else {
-                    (&xs.elements)[i as usize].clone()
+                    Object::new_string(chars[position as usize].to_string())
                 }
             } else {
-                Object::new_error(format!("index operator not supported {:?}", index.object_type))
+                Object::new_error(ErrorKind::TypeError,
+                                  format!("index operator not supported {:?}", index.object_type))
             }
         }
         ObjectType::HashType(xs) => eval_hash_index_expression(xs, index),
-        _ => Object::new_error(format!("index operator not supported {:?}", index.object_type)),
+        _ => Object::new_error(ErrorKind::TypeError,
+                               format!("index operator not supported {:?}", index.object_type)),
+    }
+}
+
+fn eval_slice_bound(bound: &Option<Box<Expressions>>,
+                    env: &mut Enviroment)
+                    -> Result<Option<i64>, Object> {
+    let bound = match *bound {
+        Some(ref x) => eval(x.to_ast(), env),
+        None => return Ok(None),
+    };
+    match bound.object_type {
+        ObjectType::Integer(i) => Ok(Some(i)),
+        ObjectType::Error(_, _, _) => Err(bound),
+        x => {
+            Err(Object::new_error(ErrorKind::TypeError,
+                                  format!("slice bound must be an integer. got {:?}", x)))
+        }
+    }
+}
+
+// Bounds count back from the end when negative and are then clamped to the length, so a
+// slice that is inverted or runs past either end is shorter or empty rather than an error.
+fn slice_range(start: Option<i64>, end: Option<i64>, len: usize) -> (usize, usize) {
+    let clamp = |i: i64| from_end(i, len).max(0).min(len as i64) as usize;
+    let start = start.map_or(0, clamp);
+    let end = end.map_or(len, clamp);
+    (start, end.max(start))
+}
+
+fn eval_slice_expression(x: SliceExpression, env: &mut Enviroment) -> Object {
+    let left = eval(x.left.to_ast(), env);
+    if is_error(&left) {
+        return left;
+    }
+    let start = match eval_slice_bound(&x.start, env) {
+        Ok(x) => x,
+        Err(e) => return e,
+    };
+    let end = match eval_slice_bound(&x.end, env) {
+        Ok(x) => x,
+        Err(e) => return e,
+    };
+    match left.object_type {
+        ObjectType::Array(xs) => {
+            let (start, end) = slice_range(start, end, xs.elements.len());
+            Object::new_array(xs.elements[start..end].to_vec())
+        }
+        ObjectType::StringType(s) => {
+            let chars: Vec<char> = s.chars().collect();
+            let (start, end) = slice_range(start, end, chars.len());
+            Object::new_string(chars[start..end].iter().collect())
+        }
+        x => {
+            Object::new_error(ErrorKind::TypeError,
+                              format!("slice operator not supported {:?}", x))
+        }
     }
 }
 
@@ -169,7 +818,8 @@ fn eval_hash_index_expression(left: HashType, index: Object) -> Object {
                 None => NULL,
             }
         }
-        None => Object::new_error(format!("unusable as hash key: {:?}", index.object_type)),
+        None => Object::new_error(ErrorKind::TypeError,
+                                  format!("unusable as hash key: {:?}", index.object_type)),
     }
 }
 
@@ -180,20 +830,35 @@ fn eval_program(statements: &Vec<Statements>, env: &mut Enviroment) -> Object {
         if let ObjectType::Return(x) = result.object_type {
             return *x;
         }
-        if let ObjectType::Error(_) = result.object_type {
+        if let ObjectType::Error(_, _, _) = result.object_type {
             return result;
         }
     }
     result
 }
 
+// Binds `name` in `env`, into its slot when the resolver gave it one.
+fn bind(name: &Identifier, value: Object, env: &mut Enviroment) -> Option<Object> {
+    match name.resolved {
+        Some(at) => env.assign_at(name.value.clone(), at.slot, value),
+        None => env.assign(name.value.clone(), value),
+    }
+}
+
 fn eval_identifier(statement: &Identifier, env: &mut Enviroment) -> Object {
+    if let Some(x) = statement.resolved.and_then(|at| env.get_at(at)) {
+        return x;
+    }
     match env.get(&statement.value) {
-        Some(x) => x.clone(),
+        Some(x) => x,
         None => {
+            if let Some(x) = env.get_prelude(&statement.value) {
+                return x;
+            }
             match BuildIn::set_from_string(&statement.value) {
                 Some(y) => y,
-                _ => Object::new_error(format!("identifier not found: {}", statement.value)),
+                _ => Object::new_error(ErrorKind::NameError,
+                                       format!("identifier not found: {}", statement.value)),
             }
         }
     }
@@ -206,7 +871,7 @@ fn eval_block_statement(x: BlockStatement, env: &mut Enviroment) -> Object {
         if let ObjectType::Return(_) = result.object_type {
             return result;
         }
-        if let ObjectType::Error(_) = result.object_type {
+        if let ObjectType::Error(_, _, _) = result.object_type {
             return result;
         }
     }
@@ -218,7 +883,7 @@ fn eval_if_expression(x: &IfExpression, env: &mut Enviroment) -> Object {
     if is_error(&condition) {
         return condition;
     }
-    match is_truthy(condition) {
+    match condition.is_truthy() {
         true => eval(x.consequence.to_enum().to_ast(), env),
         false => {
             if let &Some(ref y) = &x.alternative {
@@ -229,6 +894,61 @@ fn eval_if_expression(x: &IfExpression, env: &mut Enviroment) -> Object {
     }
 }
 
+fn eval_let_rec_statement(x: LetRecStatement, env: &mut Enviroment) -> Object {
+    let mut group = vec![];
+    for binding in x.bindings.iter() {
+        match binding.value {
+            Expressions::FunctionLiteral(ref f) => {
+                group.push(Object::new_rec_function(f.parameters.clone(),
+                                                    f.body.clone(),
+                                                    binding.name.value.clone()))
+            }
+            _ => {
+                return Object::new_error(ErrorKind::TypeError,
+                                         format!("let rec binding {} is not a function",
+                                                 binding.name.value))
+            }
+        }
+    }
+
+    let captured = env.clone();
+    let mut result = NULL;
+    for (f, binding) in group.iter().zip(x.bindings.iter()) {
+        let val = check_heap_limit(Object::from_rec_function(f, &group, &captured), env);
+        if is_error(&val) {
+            return val;
+        }
+        result = match bind(&binding.name, val.clone(), env) {
+            Some(old) => notify_watcher(&f.name, old, val, env),
+            None => val,
+        };
+        if is_error(&result) {
+            return result;
+        }
+    }
+    result
+}
+
+// The body runs in the enclosing enviroment, so `let` inside the loop rebinds the
+// surrounding name. The value is that of the last completed iteration.
+fn eval_while_expression(x: &WhileExpression, env: &mut Enviroment) -> Object {
+    let mut result = NULL;
+    loop {
+        let condition = eval(x.condition.to_ast(), env);
+        if is_error(&condition) {
+            return condition;
+        }
+        if !condition.is_truthy() {
+            return result;
+        }
+        result = eval_block_statement(x.body.clone(), env);
+        match result.object_type {
+            ObjectType::Return(_) | ObjectType::Error(_, _, _) => return result,
+            _ => {}
+        }
+    }
+}
+
 fn eval_hash_literal(x: HashLiteral, env: &mut Enviroment) -> Object {
     let mut pairs: HashMap<HashKey, Object> = HashMap::new();
 
@@ -246,19 +966,22 @@ fn eval_hash_literal(x: HashLiteral, env: &mut Enviroment) -> Object {
         if let Some(hash_key) = HashKey::new(&key) {
             pairs.insert(hash_key, value);
         } else {
-            return Object::new_error(format!("hash key not support for {:?}", key.object_type));
+            return Object::new_error(ErrorKind::TypeError,
+                                     format!("unusable as hash key: {:?}", key.object_type));
         }
     }
 
-    Object { object_type: ObjectType::HashType(HashType { pairs: pairs }) }
+    Object::new_hash(pairs)
 }
 
-fn is_truthy(x: Object) -> bool {
-    match x {
-        NULL => false,
-        TRUE => true,
-        FALSE => false,
-        _ => true,
+// The heap estimate only covers values bound in the enviroment chain, plus the value being
+// built; it is a guard against runaway allocation rather than exact accounting.
+fn check_heap_limit(x: Object, env: &Enviroment) -> Object {
+    match env.options().max_heap_bytes {
+        Some(limit) if !is_error(&x) && env.heap_bytes() + x.estimated_size() > limit => {
+            Object::new_error(ErrorKind::LimitError, "heap limit exceeded".to_string())
+        }
+        _ => x,
     }
 }
 
@@ -269,11 +992,32 @@ fn native_bool_to_boolean_obj(x: bool) -> Object {
     }
 }
 
-fn eval_infix_expression(operator: String, left: Object, right: Object) -> Object {
+fn eval_infix_expression(operator: String,
+                         left: Object,
+                         right: Object,
+                         options: &EvalOptions)
+                         -> Object {
+    if operator == "in" {
+        return eval_in_expression(left, right);
+    }
+
     if let ObjectType::Integer(l) = left.object_type {
         if let ObjectType::Integer(r) = right.object_type {
-            return eval_integer_infix_expression(operator, l, r);
+            return eval_integer_infix_expression(operator, l, r, options);
+        }
+    }
+
+    match (&left.object_type, &right.object_type) {
+        (ObjectType::Float(l), ObjectType::Float(r)) => {
+            return eval_float_infix_expression(operator, *l, *r, options)
         }
+        (ObjectType::Integer(l), ObjectType::Float(r)) => {
+            return eval_float_infix_expression(operator, *l as f64, *r, options)
+        }
+        (ObjectType::Float(l), ObjectType::Integer(r)) => {
+            return eval_float_infix_expression(operator, *l, *r as f64, options)
+        }
+        _ => {}
     }
 
     if let ObjectType::StringType(l) = left.object_type.clone() {
@@ -282,8 +1026,26 @@ fn eval_infix_expression(operator: String, left: Object, right: Object) -> Objec
         }
     }
 
+    match (operator.as_str(), &left.object_type, &right.object_type) {
+        ("==", ObjectType::Array(_), ObjectType::Array(_)) |
+        ("==", ObjectType::HashType(_), ObjectType::HashType(_)) => {
+            return native_bool_to_boolean_obj(values_equal(&left, &right))
+        }
+        ("!=", ObjectType::Array(_), ObjectType::Array(_)) |
+        ("!=", ObjectType::HashType(_), ObjectType::HashType(_)) => {
+            return native_bool_to_boolean_obj(!values_equal(&left, &right))
+        }
+        _ => {}
+    }
+
+    if options.coerce_numeric_strings {
+        if let Some(result) = eval_coerced_infix_expression(&operator, &left, &right, options) {
+            return result;
+        }
+    }
+
     if left.object_type.to_type() != right.object_type.to_type() {
-        return Object::new_error(format!("type mismatch: {:?} {} {:?}",
+        return Object::new_error(ErrorKind::TypeError, format!("type mismatch: {:?} {} {:?}",
                                          left.object_type,
                                          operator,
                                          right.object_type));
@@ -293,7 +1055,7 @@ fn eval_infix_expression(operator: String, left: Object, right: Object) -> Objec
         "==" => native_bool_to_boolean_obj(left == right),
         "!=" => native_bool_to_boolean_obj(left != right),
         _ => {
-            Object::new_error(format!("unknown operator: {:?} {} {:?}",
+            Object::new_error(ErrorKind::TypeError, format!("unknown operator: {:?} {} {:?}",
                                       left.object_type,
                                       operator,
                                       right.object_type))
@@ -301,24 +1063,174 @@ fn eval_infix_expression(operator: String, left: Object, right: Object) -> Objec
     }
 }
 
-fn eval_integer_infix_expression(operator: String, left: i32, right: i32) -> Object {
+fn arrays_equal(left: &Array, right: &Array) -> bool {
+    left.elements.len() == right.elements.len() &&
+    left.elements.iter().zip(right.elements.iter()).all(|(l, r)| values_equal(l, r))
+}
+
+// Key order never matters; only the key sets and the value under each key do.
+fn hashes_equal(left: &HashType, right: &HashType) -> bool {
+    left.pairs.len() == right.pairs.len() &&
+    left.pairs.iter().all(|(k, l)| right.pairs.get(k).is_some_and(|r| values_equal(l, r)))
+}
+
+// `==` on array elements and hash values, and the membership test of `in`. Numbers compare
+// like they do at the top level, so [1] == [1.0], and values of different types are unequal
+// rather than a type mismatch.
+pub fn values_equal(left: &Object, right: &Object) -> bool {
+    match (&left.object_type, &right.object_type) {
+        (ObjectType::Array(l), ObjectType::Array(r)) => arrays_equal(l, r),
+        (ObjectType::HashType(l), ObjectType::HashType(r)) => hashes_equal(l, r),
+        (ObjectType::Float(l), ObjectType::Float(r)) => float_eq(*l, *r),
+        (ObjectType::Integer(l), ObjectType::Float(r)) => float_eq(*l as f64, *r),
+        (ObjectType::Float(l), ObjectType::Integer(r)) => float_eq(*l, *r as f64),
+        _ => left == right,
+    }
+}
+
+fn eval_in_expression(left: Object, right: Object) -> Object {
+    match right.object_type {
+        ObjectType::Array(ref a) => {
+            native_bool_to_boolean_obj(a.elements.iter().any(|x| values_equal(x, &left)))
+        }
+        ObjectType::HashType(ref h) => {
+            match HashKey::new(&left) {
+                Some(key) => native_bool_to_boolean_obj(h.pairs.contains_key(&key)),
+                None => {
+                    Object::new_error(ErrorKind::TypeError,
+                                      format!("unusable as hash key: {:?}", left.object_type))
+                }
+            }
+        }
+        ObjectType::StringType(ref s) => {
+            match left.object_type {
+                ObjectType::StringType(ref l) => native_bool_to_boolean_obj(s.contains(l.as_str())),
+                _ => {
+                    Object::new_error(ErrorKind::TypeError,
+                                      format!("type mismatch: {:?} in {:?}",
+                                              left.object_type,
+                                              right.object_type))
+                }
+            }
+        }
+        _ => {
+            Object::new_error(ErrorKind::TypeError,
+                              format!("unknown operator: {:?} in {:?}",
+                                      left.object_type,
+                                      right.object_type))
+        }
+    }
+}
+
+// With coerce_numeric_strings, `+` joins a string and an integer as strings, while the
+// other operators read a numeric string as an integer.
+fn eval_coerced_infix_expression(operator: &String,
+                                 left: &Object,
+                                 right: &Object,
+                                 options: &EvalOptions)
+                                 -> Option<Object> {
+    let (l, r) = match (&left.object_type, &right.object_type) {
+        (ObjectType::StringType(s), ObjectType::Integer(n)) => {
+            if operator == "+" {
+                return Some(Object::new_string(format!("{}{}", s, n)));
+            }
+            (i64::from_str(s.trim()).ok()?, *n)
+        }
+        (ObjectType::Integer(n), ObjectType::StringType(s)) => {
+            if operator == "+" {
+                return Some(Object::new_string(format!("{}{}", n, s)));
+            }
+            (*n, i64::from_str(s.trim()).ok()?)
+        }
+        _ => return None,
+    };
+    Some(eval_integer_infix_expression(operator.clone(), l, r, options))
+}
+
+fn eval_integer_infix_expression(operator: String,
+                                 left: i64,
+                                 right: i64,
+                                 options: &EvalOptions)
+                                 -> Object {
     match operator.as_str() {
-        "+" => Object::new_i32(left + right),
-        "-" => Object::new_i32(left - right),
-        "*" => Object::new_i32(left * right),
-        "/" => Object::new_i32(left / right),
+        "+" => checked_int(left.checked_add(right)),
+        "-" => checked_int(left.checked_sub(right)),
+        "*" => checked_int(left.checked_mul(right)),
+        "/" | "%" if right == 0 => {
+            Object::new_error(ErrorKind::DivByZero, "division by zero".to_string())
+        }
+        "/" => checked_int(divide(left, right, options.division_mode)),
+        "%" => checked_int(modulo(left, right, options.modulo_mode)),
+        ".." => eval_range(left, right as i128, options),
+        "..=" => eval_range(left, right as i128 + 1, options),
         "<" => native_bool_to_boolean_obj(left < right),
         ">" => native_bool_to_boolean_obj(left > right),
         "==" => native_bool_to_boolean_obj(left == right),
         "!=" => native_bool_to_boolean_obj(left != right),
-        _ => Object::new_error(format!("unknown operator: Integer {} Integer", operator)),
+        _ => Object::new_error(ErrorKind::TypeError,
+                               format!("unknown operator: Integer {} Integer", operator)),
+    }
+}
+
+// Division by zero is an error as it is for integers, but overflow gives an infinity and
+// infinities can combine into NaN. `==` uses float_eq, so NaN equals itself.
+fn eval_float_infix_expression(operator: String,
+                               left: f64,
+                               right: f64,
+                               options: &EvalOptions)
+                               -> Object {
+    match operator.as_str() {
+        "+" => Object::new_f64(left + right),
+        "-" => Object::new_f64(left - right),
+        "*" => Object::new_f64(left * right),
+        "/" | "%" if right == 0.0 => {
+            Object::new_error(ErrorKind::DivByZero, "division by zero".to_string())
+        }
+        "/" => Object::new_f64(left / right),
+        "%" => Object::new_f64(float_modulo(left, right, options.modulo_mode)),
+        "<" => native_bool_to_boolean_obj(left < right),
+        ">" => native_bool_to_boolean_obj(left > right),
+        "==" => native_bool_to_boolean_obj(float_eq(left, right)),
+        "!=" => native_bool_to_boolean_obj(!float_eq(left, right)),
+        _ => Object::new_error(ErrorKind::TypeError,
+                               format!("unknown operator: Float {} Float", operator)),
+    }
+}
+
+// Ranges are end-exclusive and empty when end <= start. The size is checked against the
+// heap limit before the array is built.
+// Ranges are built eagerly, so even without a heap limit their length is capped well below
+// what would exhaust the host's memory.
+const MAX_RANGE_LEN: usize = 1 << 20;
+
+fn eval_range(start: i64, end: i128, options: &EvalOptions) -> Object {
+    let len = (end - start as i128).clamp(0, usize::MAX as i128) as usize;
+    if len > MAX_RANGE_LEN {
+        return Object::new_error(ErrorKind::LimitError,
+                                 format!("range too large: {} elements, max={}",
+                                         len,
+                                         MAX_RANGE_LEN));
+    }
+    if let Some(limit) = options.max_heap_bytes {
+        if len.saturating_mul(mem::size_of::<Object>()) > limit {
+            return Object::new_error(ErrorKind::LimitError, "heap limit exceeded".to_string());
+        }
+    }
+    Object::new_array((start as i128..end).map(|x| Object::new_int(x as i64)).collect())
+}
+
+fn checked_int(x: Option<i64>) -> Object {
+    match x {
+        Some(n) => Object::new_int(n),
+        None => Object::new_error(ErrorKind::OverflowError, "integer overflow".to_string()),
     }
 }
 
 fn eval_string_infix_expression(operator: String, left: String, right: String) -> Object {
     match operator.as_str() {
         "+" => Object::new_string(format!("{}{}", left, right)),
-        _ => Object::new_error(format!("unknown operator: String {} String", operator)),
+        _ => Object::new_error(ErrorKind::TypeError,
+                               format!("unknown operator: String {} String", operator)),
     }
 }
 
@@ -326,14 +1238,22 @@ fn eval_prefix_expression(operator: String, right: Object) -> Object {
     match operator.as_str() {
         "!" => eval_bang_operator_expression(right),
         "-" => eval_minus_operator_expression(right),
-        _ => Object::new_error(format!("unknown operator: {}{:?}", operator, right.object_type)),
+        _ => Object::new_error(ErrorKind::TypeError,
+                               format!("unknown operator: {}{:?}", operator, right.object_type)),
     }
 }
 
 fn eval_minus_operator_expression(right: Object) -> Object {
     match right.object_type {
-        ObjectType::Integer(x) => Object::new_i32(-x),
-        _ => Object::new_error(format!("unknown operator: -{:?}", right.object_type)),
+        ObjectType::Float(x) => Object::new_f64(-x),
+        ObjectType::Integer(x) => {
+            match x.checked_neg() {
+                Some(n) => Object::new_int(n),
+                None => Object::new_error(ErrorKind::OverflowError, "integer overflow".to_string()),
+            }
+        }
+        _ => Object::new_error(ErrorKind::TypeError,
+                               format!("unknown operator: -{:?}", right.object_type)),
     }
 }
 
@@ -352,13 +1272,22 @@ mod tests {
     use lexer::lexer;
     use parser::parser;
     use parser::ast::Node;
+    use output;
+    use evaluator::options::{DivisionMode, ModuloMode};
 
     fn test_eval(input: String) -> Object {
-        let l = lexer::Lexer::new(input);
-        let mut parser = parser::Parser::new(l);
+        test_eval_with_options(input, EvalOptions::new())
+    }
+
+    fn test_eval_with_options(input: String, options: EvalOptions) -> Object {
+        test_eval_in(input, &mut Enviroment::with_options(options))
+    }
+
+    fn test_eval_in(input: String, env: &mut Enviroment) -> Object {
+        let l = lexer::Lexer::new(input);
+        let mut parser = parser::Parser::new(l);
         let program = parser.parse_program();
-        let mut env = Enviroment::new();
-        eval(program.to_enum().to_ast(), &mut env)
+        eval(program.to_enum().to_ast(), env)
     }
 
     #[test]
@@ -380,7 +1309,210 @@ mod tests {
                        ("(5 + 10 * 2 + 15 / 3) * 2 + -10", 50)];
         for expect in expects.iter() {
             let result = test_eval(expect.0.to_string());
-            assert_eq!(result.to_i32().unwrap(), expect.1);
+            assert_eq!(result.to_int().unwrap(), expect.1);
+        }
+    }
+
+    #[test]
+    fn it_should_divide_by_division_mode() {
+        let floor = EvalOptions { division_mode: DivisionMode::Floor, ..EvalOptions::new() };
+        let expects = [("-7 / 2", -3, -4),
+                       ("7 / 2", 3, 3),
+                       ("7 / -2", -3, -4),
+                       ("-8 / 2", -4, -4),
+                       ("-7 / -2", 3, 3),
+                       ("let half = fn(x) { x / 2 }; half(-7)", -3, -4)];
+        for expect in expects.iter() {
+            assert_eq!(test_eval(expect.0.to_string()).to_int(), Some(expect.1));
+            assert_eq!(test_eval_with_options(expect.0.to_string(), floor.clone()).to_int(),
+                       Some(expect.2));
+        }
+    }
+
+    #[test]
+    fn it_should_evaluate_float_expression() {
+        let expects = [("2.75", 2.75),
+                       ("-2.5", -2.5),
+                       ("1.5 + 2.25", 3.75),
+                       ("5.0 - 7.5", -2.5),
+                       ("1.5 * 4.0", 6.0),
+                       ("7.5 / 2.5", 3.0),
+                       ("7.5 % 2.0", 1.5),
+                       ("1 + 0.5", 1.5),
+                       ("0.5 * 4", 2.0),
+                       ("7 / 2.0", 3.5),
+                       ("let half = fn(x) { x / 2.0 }; half(3)", 1.5)];
+        for expect in expects.iter() {
+            assert_eq!(test_eval(expect.0.to_string()).to_f64(), Some(expect.1), "{}", expect.0);
+        }
+
+        let third = test_eval("1.0 / 3.0".to_string());
+        assert_eq!(third.to_f64(), Some(1.0 / 3.0));
+        assert_eq!(third.inspect(), "0.3333333333333333");
+        assert_eq!(test_eval("2.0 * 3".to_string()).inspect(), "6.0");
+        assert_eq!(test_eval("[1.5, -0.25]".to_string()).to_source(),
+                   Some("[1.5, -0.25]".to_string()));
+
+        let floored = EvalOptions { modulo_mode: ModuloMode::Floored, ..EvalOptions::new() };
+        assert_eq!(test_eval_with_options("-7.5 % 2.0".to_string(), floored).to_f64(), Some(0.5));
+        assert_eq!(test_eval("-7.5 % 2.0".to_string()).to_f64(), Some(-1.5));
+
+        let errors = [("1.5 / 0.0", "division by zero, at line: 1, column: 5"),
+                      ("1.5 / 0", "division by zero, at line: 1, column: 5"),
+                      ("1.5 .. 3.0", "unknown operator: Float .. Float, at line: 1, column: 5"),
+                      ("1.5 + true", "type mismatch: Float(1.5) + Boolean(true), at line: 1, column: 5")];
+        for expect in errors.iter() {
+            assert_eq!(test_eval(expect.0.to_string()).to_error_message(),
+                       Some(expect.1.to_string()),
+                       "{}",
+                       expect.0);
+        }
+    }
+
+    #[test]
+    fn it_should_compare_floats() {
+        let expects = [("3.0 == 3.0", true),
+                       ("3.0 != 3.0", false),
+                       ("3 == 3.0", true),
+                       ("1.5 < 2", true),
+                       ("2 > 1.5", true),
+                       ("-0.0 == 0.0", true),
+                       // Floats are binary, so 0.1 + 0.2 is 0.30000000000000004.
+                       ("0.1 + 0.2 == 0.3", false),
+                       ("0.1 + 0.2 > 0.3", true),
+                       ("0.5 + 0.25 == 0.75", true),
+                       ("[1.5] == [1.5]", true),
+                       ("deep_equal([1.5], [1.5])", true)];
+        for expect in expects.iter() {
+            assert_eq!(test_eval(expect.0.to_string()).to_bool(), Some(expect.1), "{}", expect.0);
+        }
+        assert_eq!(test_eval("cmp(1, 1.5)".to_string()).to_int(), Some(-1));
+    }
+
+    #[test]
+    fn it_should_evaluate_modulo_operator() {
+        let expects = [("10 % 3", 1), ("9 % 3", 0), ("2 % 5", 2), ("-10 % 3", -1), ("10 % -3", 1),
+                       ("17 % 5 % 3", 2)];
+        for expect in expects.iter() {
+            assert_eq!(test_eval(expect.0.to_string()).to_int(), Some(expect.1));
+        }
+        assert_eq!(test_eval("10 % 0".to_string()),
+                   Object::new_error(ErrorKind::DivByZero,
+                                     "division by zero, at line: 1, column: 4".to_string()));
+    }
+
+    #[test]
+    fn it_should_evaluate_division_by_zero_to_error() {
+        let expects = [("5 / 0", 3),
+                       ("let x = 0; 5 / x", 14),
+                       ("fn(a) { a / 0 }(1) + 1", 11),
+                       ("[1, 10 / 0, 3]", 8)];
+        for expect in expects.iter() {
+            assert_eq!(test_eval(expect.0.to_string()).to_error_message(),
+                       Some(format!("division by zero, at line: 1, column: {}", expect.1)),
+                       "{}",
+                       expect.0);
+        }
+    }
+
+    #[test]
+    fn it_should_evaluate_zero_and_negative_zero_alike() {
+        let expects = ["0", "-0", "--0", "-(0)", "0 * -1", "-0 + 0", "0 - 0"];
+        for expect in expects.iter() {
+            let result = test_eval(expect.to_string());
+            assert_eq!(result, Object::new_int(0), "{}", expect);
+            assert_eq!(result.to_source(), Some("0".to_string()), "{}", expect);
+        }
+        assert_eq!(test_eval("-0 == 0".to_string()), TRUE);
+        assert_eq!(test_eval("[-0] == [0]".to_string()), TRUE);
+        assert_eq!(test_eval("{0: 1}[-0]".to_string()), Object::new_int(1));
+    }
+
+    #[test]
+    fn it_should_evaluate_integer_overflow_to_error() {
+        let expects = [("9223372036854775807 + 1", 21),
+                       ("-9223372036854775807 - 2", 22),
+                       ("4294967296 * 4294967296", 12),
+                       ("let max = 9223372036854775807; max * -2", 36)];
+        for expect in expects.iter() {
+            assert_eq!(test_eval(expect.0.to_string()),
+                       Object::new_error(ErrorKind::OverflowError,
+                                         format!("integer overflow, at line: 1, column: {}",
+                                                 expect.1)),
+                       "{}",
+                       expect.0);
+        }
+        assert_eq!(test_eval("9223372036854775806 + 1".to_string()).to_int(), Some(i64::MAX));
+        assert_eq!(test_eval("-9223372036854775807 - 1".to_string()).to_int(), Some(i64::MIN));
+        assert_eq!(test_eval("65536 * 65536".to_string()).to_int(), Some(4294967296));
+        assert_eq!(test_eval("len(9223372036854775806..=9223372036854775807)".to_string()),
+                   Object::new_int(2));
+    }
+
+    #[test]
+    fn it_should_evaluate_min_divided_by_minus_one_to_overflow_error() {
+        let floor = EvalOptions {
+            division_mode: DivisionMode::Floor,
+            modulo_mode: ModuloMode::Floored,
+            ..EvalOptions::new()
+        };
+        let inputs = ["(-9223372036854775807 - 1) / -1", "(-9223372036854775807 - 1) % -1"];
+        for input in inputs.iter() {
+            for options in [EvalOptions::new(), floor.clone()].iter() {
+                assert_eq!(test_eval_with_options(input.to_string(), options.clone()),
+                           Object::new_error(ErrorKind::OverflowError,
+                                             "integer overflow, at line: 1, column: 28"
+                                                 .to_string()),
+                           "{}",
+                           input);
+            }
+        }
+    }
+
+    #[test]
+    fn it_should_modulo_by_modulo_mode() {
+        let floored = EvalOptions { modulo_mode: ModuloMode::Floored, ..EvalOptions::new() };
+        let expects = [("-7 % 2", -1, 1),
+                       ("7 % 2", 1, 1),
+                       ("7 % -2", 1, -1),
+                       ("-7 % -2", -1, -1),
+                       ("-8 % 2", 0, 0),
+                       ("1 + 10 % 4 * 2", 5, 5)];
+        for expect in expects.iter() {
+            assert_eq!(test_eval(expect.0.to_string()).to_int(), Some(expect.1));
+            assert_eq!(test_eval_with_options(expect.0.to_string(), floored.clone()).to_int(),
+                       Some(expect.2));
+        }
+    }
+
+    #[test]
+    fn it_should_coerce_numeric_strings_when_enabled() {
+        let lenient = EvalOptions { coerce_numeric_strings: true, ..EvalOptions::new() };
+        let expects = [("\"5\" + 5", Object::new_string("55".to_string())),
+                       ("5 + \"5\"", Object::new_string("55".to_string())),
+                       ("\"10\" - 4", Object::new_int(6)),
+                       ("3 * \"4\"", Object::new_int(12)),
+                       ("\"2\" < 3", TRUE),
+                       ("\"5\" + \"5\"", Object::new_string("55".to_string()))];
+        for expect in expects.iter() {
+            assert_eq!(test_eval_with_options(expect.0.to_string(), lenient.clone()), expect.1);
+        }
+
+        assert_eq!(test_eval_with_options("\"a\" * 2".to_string(), lenient).to_error_message(),
+                   Some("type mismatch: StringType(\"a\") * Integer(2), at line: 1, column: 5"
+                            .to_string()));
+        assert_eq!(test_eval("\"5\" + 5".to_string()).to_error_message(),
+                   Some("type mismatch: StringType(\"5\") + Integer(5), at line: 1, column: 5"
+                            .to_string()));
+    }
+
+    #[test]
+    fn it_should_evaluate_chained_minus_operator() {
+        let expects = [("--5", 5), ("- -5", 5), ("-(-5)", 5), ("---5", -5), ("- - -5", -5),
+                       ("1 - -5", 6), ("1 --5", 6)];
+        for expect in expects.iter() {
+            let result = test_eval(expect.0.to_string());
+            assert_eq!(result.to_int().unwrap(), expect.1);
         }
     }
 
@@ -429,6 +1561,55 @@ mod tests {
         }
     }
 
+    #[test]
+    fn it_should_compare_arrays_element_wise() {
+        let expects = [("[1, 2] == [1, 2]", true),
+                       ("[1, 2] == [1, 3]", false),
+                       ("[1, 2] != [1, 3]", true),
+                       ("[1, 2] == [1, 2, 3]", false),
+                       ("[1, 2, 3] != [1, 2]", true),
+                       ("[] == []", true),
+                       ("[[1, [2]], \"a\"] == [[1, [2]], \"a\"]", true),
+                       ("[[1, [2]], \"a\"] == [[1, [3]], \"a\"]", false),
+                       ("[\"a\", \"b\"] == [\"a\", \"c\"]", false),
+                       ("[1, \"1\", true] == [1, \"1\", true]", true),
+                       ("[1] == [\"1\"]", false),
+                       ("[1] != [true]", true),
+                       ("[1, 2.5] == [1.0, 2.5]", true),
+                       ("let a = [1, [2]]; let b = a; a == b", true),
+                       ("freeze([1]) == [1]", true)];
+        for expect in expects.iter() {
+            assert_eq!(test_eval(expect.0.to_string()).to_bool(), Some(expect.1), "{}", expect.0);
+        }
+        assert_eq!(test_eval("[1] + [2]".to_string()).to_error_kind(),
+                   Some(ErrorKind::TypeError));
+        assert_eq!(test_eval("[1] == 1".to_string()).to_error_kind(),
+                   Some(ErrorKind::TypeError));
+    }
+
+    #[test]
+    fn it_should_compare_hashes_by_keys_and_values() {
+        let expects = [("{\"a\": 1} == {\"a\": 1}", true),
+                       ("{\"a\": 1, \"b\": 2} == {\"b\": 2, \"a\": 1}", true),
+                       ("{} == {}", true),
+                       ("{\"a\": 1} == {\"a\": 2}", false),
+                       ("{\"a\": 1} != {\"a\": 2}", true),
+                       ("{\"a\": 1} == {\"b\": 1}", false),
+                       ("{\"a\": 1} == {\"a\": 1, \"b\": 2}", false),
+                       ("{\"a\": 1, \"b\": 2} != {\"a\": 1}", true),
+                       ("{1: \"x\"} == {\"1\": \"x\"}", false),
+                       ("{\"n\": {\"m\": [1, 2]}} == {\"n\": {\"m\": [1, 2]}}", true),
+                       ("{\"n\": {\"m\": [1, 2]}} == {\"n\": {\"m\": [1, 3]}}", false),
+                       ("{\"a\": 1} == {\"a\": 1.0}", true),
+                       ("[{\"a\": [1]}] == [{\"a\": [1]}]", true),
+                       ("{\"a\": 1} == {\"a\": true}", false)];
+        for expect in expects.iter() {
+            assert_eq!(test_eval(expect.0.to_string()).to_bool(), Some(expect.1), "{}", expect.0);
+        }
+        assert_eq!(test_eval("{} == []".to_string()).to_error_kind(),
+                   Some(ErrorKind::TypeError));
+    }
+
     #[test]
     fn it_should_evaluate_bang_operator() {
         let expects = [("!true", false), ("!false", true)];
@@ -449,7 +1630,111 @@ mod tests {
                        ("if (1 < 2) { 13 } else { 20 }", Some(13))];
         for expect in expects.iter() {
             let result = test_eval(expect.0.to_string());
-            assert_eq!(result.to_i32(), expect.1);
+            assert_eq!(result.to_int(), expect.1);
+        }
+    }
+
+    #[test]
+    fn it_should_evaluate_while_expression() {
+        let expects = [("let i = 0; while (i < 5) { let i = i + 1; }; i", Some(5)),
+                       ("let i = 0; let sum = 0; \
+                         while (i < 4) { let i = i + 1; let sum = sum + i; }; sum",
+                        Some(10)),
+                       ("let i = 0; while (i < 3) { let i = i + 1; i * 10 }", Some(30)),
+                       ("while (false) { 1 }", None),
+                       ("let find = fn(xs, x) { let i = 0; \
+                         while (i < len(xs)) { if (xs[i] == x) { return i; }; let i = i + 1; }; \
+                         -1 }; find([4, 5, 6], 5)",
+                        Some(1)),
+                       ("let find = fn(xs, x) { let i = 0; \
+                         while (i < len(xs)) { if (xs[i] == x) { return i; }; let i = i + 1; }; \
+                         -1 }; find([4, 5, 6], 7)",
+                        Some(-1)),
+                       ("let i = 0; while (true) { if (i == 2) { return i * 100; }; \
+                         let i = i + 1; }; 0",
+                        Some(200))];
+        for expect in expects.iter() {
+            assert_eq!(test_eval(expect.0.to_string()).to_int(), expect.1, "{}", expect.0);
+        }
+
+        let errors = [("while (x) { 1 }", "identifier not found: x"),
+                      ("let i = 0; while (i < 3) { let i = i + 1; if (i == 2) { i + true } }",
+                       "type mismatch: Integer(2) + Boolean(true), at line: 1, column: 59")];
+        for expect in errors.iter() {
+            assert_eq!(test_eval(expect.0.to_string()).to_error_message(),
+                       Some(expect.1.to_string()),
+                       "{}",
+                       expect.0);
+        }
+    }
+
+    #[test]
+    fn it_should_evaluate_let_rec_group() {
+        let pair = "let rec isEven = fn(n) { if (n == 0) { true } else { isOdd(n - 1) } } \
+                    and isOdd = fn(n) { if (n == 0) { false } else { isEven(n - 1) } };";
+        let expects = [(format!("{} isEven(10)", pair), true),
+                       (format!("{} isEven(7)", pair), false),
+                       (format!("{} isOdd(7)", pair), true),
+                       (format!("{} let f = isOdd; let isEven = 1; f(3)", pair), true)];
+        for expect in expects.iter() {
+            assert_eq!(test_eval(expect.0.clone()).to_bool(), Some(expect.1), "{}", expect.0);
+        }
+
+        let fact = "let rec fact = fn(n) { if (n < 2) { 1 } else { n * fact(n - 1) } }; fact(5)";
+        assert_eq!(test_eval(fact.to_string()).to_int(), Some(120));
+        assert_eq!(test_eval("let rec = 2; let and = 3; rec * and".to_string()).to_int(), Some(6));
+
+        let shadowed = "let x = 1; let f = fn(x) { x }; f(5)";
+        assert_eq!(test_eval(shadowed.to_string()).to_int(), Some(5));
+    }
+
+    #[test]
+    fn it_should_evaluate_empty_program_and_blocks_to_null() {
+        let expects = ["",
+                       "   ",
+                       "fn() {}()",
+                       "if (true) {}",
+                       "if (false) { 1 } else {}",
+                       "let x = 1; if (x) {}"];
+        for expect in expects.iter() {
+            assert_eq!(test_eval(expect.to_string()), NULL);
+        }
+
+        // A top-level `{}` is an empty hash literal, not an empty block.
+        assert_eq!(test_eval("{}".to_string()), Object::new_hash(HashMap::new()));
+    }
+
+    #[test]
+    fn it_should_evaluate_logical_operators() {
+        let expects = [("true && true", true),
+                       ("true && false", false),
+                       ("false || true", true),
+                       ("false || false", false),
+                       ("1 && \"a\"", true),
+                       ("1 < 2 && 2 < 3", true),
+                       ("false && 1 / 0", false),
+                       ("true || 1 / 0", true)];
+        for expect in expects.iter() {
+            assert_eq!(test_eval(expect.0.to_string()),
+                       native_bool_to_boolean_obj(expect.1));
+        }
+    }
+
+    #[test]
+    fn it_should_evaluate_only_selected_operands() {
+        let expects = [("if (trace(1) > 0) { trace(\"then\") } else { trace(\"else\") }",
+                        vec!["trace: 1", "trace: then"]),
+                       ("if (trace(false)) { trace(\"then\") }", vec!["trace: false"]),
+                       ("trace(false) && trace(\"right\")", vec!["trace: false"]),
+                       ("trace(true) || trace(\"right\")", vec!["trace: true"]),
+                       ("trace(true) && trace(\"right\")", vec!["trace: true", "trace: right"]),
+                       ("len([trace(\"a\"), trace(\"b\")]) + trace(1)",
+                        vec!["trace: a", "trace: b", "trace: 1"]),
+                       ("let f = fn(a, b) { trace(\"body\") }; f(trace(1), trace(2))",
+                        vec!["trace: 1", "trace: 2", "trace: body"])];
+        for expect in expects.iter() {
+            let (_, lines) = output::capture(|| test_eval(expect.0.to_string()));
+            assert_eq!(lines, expect.1);
         }
     }
 
@@ -470,19 +1755,49 @@ mod tests {
                         10)];
         for expect in expects.iter() {
             let result = test_eval(expect.0.to_string());
-            assert_eq!(result.to_i32().unwrap(), expect.1);
+            assert_eq!(result.to_int().unwrap(), expect.1);
+        }
+    }
+
+    #[test]
+    fn it_should_check_function_argument_count() {
+        let expects = [("let add = fn(a, b) { a + b }; add(1)",
+                        "wrong number of arguments. got 1 want=2"),
+                       ("let add = fn(a, b) { a + b }; add(1, 2, 3)",
+                        "wrong number of arguments. got 3 want=2"),
+                       ("fn() { 1 }(2)", "wrong number of arguments. got 1 want=0")];
+        for expect in expects.iter() {
+            let result = test_eval(expect.0.to_string());
+            assert_eq!(result.to_error_kind(), Some(ErrorKind::ArgumentError));
+            assert_eq!(result.to_error_message(), Some(expect.1.to_string()));
         }
+        assert_eq!(test_eval("let add = fn(a, b) { a + b }; add(1, 2)".to_string()).to_int(),
+                   Some(3));
+    }
+
+    #[test]
+    fn it_should_evaluate_bare_return_to_null() {
+        assert_eq!(test_eval("fn() { return; }()".to_string()), NULL);
+        assert_eq!(test_eval("fn() { return }()".to_string()), NULL);
+        let (result, lines) = output::capture(|| test_eval("fn() { return; puts(1); 2 }()"
+            .to_string()));
+        assert_eq!(result, NULL);
+        assert!(lines.is_empty());
     }
 
     #[test]
     fn it_should_error_handling() {
-        let expects = [("5 + true;", "type mismatch: Integer(5) + Boolean(true)"),
-                       ("5 + true; 5;", "type mismatch: Integer(5) + Boolean(true)"),
-                       ("-true;", "unknown operator: -Boolean(true)"),
-                       ("true + false;", "unknown operator: Boolean(true) + Boolean(false)"),
-                       ("5; true + false; 5;", "unknown operator: Boolean(true) + Boolean(false)"),
+        let expects = [("5 + true;",
+                        "type mismatch: Integer(5) + Boolean(true), at line: 1, column: 3"),
+                       ("5 + true; 5;",
+                        "type mismatch: Integer(5) + Boolean(true), at line: 1, column: 3"),
+                       ("-true;", "unknown operator: -Boolean(true), at line: 1, column: 1"),
+                       ("true + false;",
+                        "unknown operator: Boolean(true) + Boolean(false), at line: 1, column: 6"),
+                       ("5; true + false; 5;",
+                        "unknown operator: Boolean(true) + Boolean(false), at line: 1, column: 9"),
                        ("if (10 > 1) { true + false; };",
-                        "unknown operator: Boolean(true) + Boolean(false)"),
+                        "unknown operator: Boolean(true) + Boolean(false), at line: 1, column: 20"),
                        ("
                        if (10 > 1) {
                             if (10 > 1) {
@@ -490,9 +1805,11 @@ mod tests {
                             };
                         };
                        ",
-                        "unknown operator: Boolean(true) + Boolean(false)"),
+                        "unknown operator: Boolean(true) + Boolean(false), at line: 4, column: 45"),
                        ("foobar", "identifier not found: foobar"),
-                       ("\"hello world\" - \"world\"", "unknown operator: String - String")];
+                       ("-(-9223372036854775807 - 1)", "integer overflow, at line: 1, column: 1"),
+                       ("\"hello world\" - \"world\"",
+                        "unknown operator: String - String, at line: 1, column: 15")];
         for expect in expects.iter() {
             let result = test_eval(expect.0.to_string());
             assert_eq!(result.to_error_message().unwrap(), expect.1);
@@ -507,7 +1824,17 @@ mod tests {
                        ("let a = 5; let b = a; let c = a + b + 5; c;", 15)];
         for expect in expects.iter() {
             let result = test_eval(expect.0.to_string());
-            assert_eq!(result.to_i32().unwrap(), expect.1);
+            assert_eq!(result.to_int().unwrap(), expect.1);
+        }
+    }
+
+    #[test]
+    fn it_should_evaluate_multiple_let_bindings() {
+        let expects = [("let a = 1, b = 2, c = 3; a + b + c", 6),
+                       ("let a = 1, b = a + 1, c = b * 10; c", 20),
+                       ("let a = 5; let a = 1, b = a; b", 1)];
+        for expect in expects.iter() {
+            assert_eq!(test_eval(expect.0.to_string()).to_int(), Some(expect.1));
         }
     }
 
@@ -521,7 +1848,7 @@ mod tests {
                        ("fn(x) {x;}(5)", 5)];
         for expect in expects.iter() {
             let result = test_eval(expect.0.to_string());
-            assert_eq!(result.to_i32().unwrap(), expect.1);
+            assert_eq!(result.to_int().unwrap(), expect.1);
         }
     }
 
@@ -535,31 +1862,183 @@ mod tests {
                        ("let myArray = [1, 2, 3]; myArray[2]", 3),
                        ("let a = 0;let b = 0; a + -b", 0),
                        ("let myArray = [1, 2, 3]; myArray[0] + myArray[1] + myArray[2];", 6),
-                       ("let myArray = [1, 2, 3]; let i = myArray[0]; myArray[i]", 2)];
+                       ("let myArray = [1, 2, 3]; let i = myArray[0]; myArray[i]", 2),
+                       ("[1, 2, 3][-1]", 3),
+                       ("[1, 2, 3][-3]", 1),
+                       ("let a = [1, 2, 3]; a[-len(a) + 1]", 2)];
         for expect in expects.iter() {
             let result = test_eval(expect.0.to_string());
-            assert_eq!(result.to_i32().unwrap(), expect.1);
+            assert_eq!(result.to_int().unwrap(), expect.1);
+        }
+    }
+
+    #[test]
+    fn it_should_assign_string_index() {
+        let expects = [(r#"let s = "cat"; s[0] = "b"; s;"#, "bat"),
+                       (r#"let s = "cat"; s[2] = "p"; s[1] = "u"; s;"#, "cup"),
+                       (r#"let s = "日本語"; s[1] = "x"; s;"#, "日x語"),
+                       (r#"let s = "abc"; s[1] = "é""#, "aéc"),
+                       (r#"let s = "cat"; s[-1] = "b"; s"#, "cab"),
+                       (r#"let s = "日本語"; s[-3] = "x"; s"#, "x本語")];
+        for expect in expects.iter() {
+            assert_eq!(test_eval(expect.0.to_string()),
+                       Object::new_string(expect.1.to_string()));
+        }
+
+        let errors = [(r#"let s = "cat"; s[0] = "br";"#,
+                       "string index assignment requires a single character. got StringType(\"br\")"),
+                      (r#"let s = "cat"; s[0] = 1;"#,
+                       "string index assignment requires a single character. got Integer(1)"),
+                      (r#"let s = "cat"; s[3] = "x";"#, "index out of range: max=2 got=3"),
+                      (r#"let s = "cat"; s[-4] = "x";"#, "index out of range: max=2 got=-4"),
+                      (r#"let s = ""; s[0] = "x";"#, "index out of range: string is empty got=0"),
+                      (r#"t[0] = "x";"#, "identifier not found: t")];
+        for expect in errors.iter() {
+            assert_eq!(test_eval(expect.0.to_string()).to_error_message(),
+                       Some(expect.1.to_string()));
+        }
+    }
+
+    #[test]
+    fn it_should_reassign_identifier() {
+        let expects = [("let x = 1; x = x + 1; x", 2),
+                       ("let i = 0; while (i < 3) { i = i + 1 }; i", 3),
+                       ("let x = 1; if (true) { x = 5 }; x", 5),
+                       ("let x = 1; let f = fn() { x = x + 1; x }; f()", 2),
+                       ("let x = 1; let f = fn() { let x = 10; x = x + 1; x }; f()", 11),
+                       ("let x = 1; let f = fn() { let x = 10; x = x + 1; x }; f(); x", 1),
+                       ("let x = 1; let f = fn(x) { x = x * 2; x }; f(4)", 8),
+                       ("let x = 1; let f = fn(x) { x = x * 2; x }; f(4); x", 1)];
+        for expect in expects.iter() {
+            assert_eq!(test_eval(expect.0.to_string()).to_int(), Some(expect.1), "{}", expect.0);
+        }
+
+        let errors = [("y = 1;", "identifier not found: y"),
+                      ("let f = fn() { y = 1 }; f()", "identifier not found: y"),
+                      ("let x = 1; x = x + true",
+                       "type mismatch: Integer(1) + Boolean(true), at line: 1, column: 18")];
+        for expect in errors.iter() {
+            assert_eq!(test_eval(expect.0.to_string()).to_error_message(),
+                       Some(expect.1.to_string()),
+                       "{}",
+                       expect.0);
+        }
+    }
+
+    #[test]
+    fn it_should_assign_array_index() {
+        let expects = [("let a = [1, 2, 3]; a[0] = 9; a", "[9, 2, 3]"),
+                       ("let a = [1, 2, 3]; a[0] = a[1] + a[2]; a", "[5, 2, 3]"),
+                       ("let a = [1, 2, 3]; a[1] = [4]; a", "[1, [4], 3]"),
+                       ("let a = [1, 2, 3]; let b = a; b[0] = 0; a", "[1, 2, 3]"),
+                       ("let a = [1]; a[0] = \"x\"", "[\"x\"]"),
+                       ("let a = [1, 2, 3]; a[-1] = 9; [a, a[-1]]", "[[1, 2, 9], 9]"),
+                       ("let a = [1, 2, 3]; a[-3] = 0; a", "[0, 2, 3]")];
+        for expect in expects.iter() {
+            assert_eq!(test_eval(expect.0.to_string()).to_source(),
+                       Some(expect.1.to_string()),
+                       "{}",
+                       expect.0);
+        }
+
+        let errors = [("let a = [1, 2, 3]; a[3] = 0;", "index out of range: max=2 got=3"),
+                      ("let a = [1, 2, 3]; a[-4] = 0;", "index out of range: max=2 got=-4"),
+                      ("let a = []; a[0] = 1;", "index out of range: array is empty got=0")];
+        for expect in errors.iter() {
+            assert_eq!(test_eval(expect.0.to_string()).to_error_message(),
+                       Some(expect.1.to_string()),
+                       "{}",
+                       expect.0);
         }
     }
 
     #[test]
     fn it_should_evaluate_array_error() {
         let expects = [("[1, 2, 3][3]", "index out of range: max=2 got=3"),
-                       ("[1, 2, 3][-1]", "index out of range: max=2 got=-1")];
+                       ("[1, 2, 3][-4]", "index out of range: max=2 got=-4"),
+                       ("[][0]", "index out of range: array is empty got=0"),
+                       ("let a = []; a[0]", "index out of range: array is empty got=0")];
         for expect in expects.iter() {
             let result = test_eval(expect.0.to_string());
             assert_eq!(result.to_error_message().unwrap(), expect.1);
         }
     }
 
+    #[test]
+    fn it_should_evaluate_string_index_expression() {
+        let expects = [("\"hello\"[1]", "e"),
+                       ("\"hello\"[0]", "h"),
+                       ("let s = \"hello\"; s[len(s) - 1]", "o"),
+                       ("\"héllo\"[1]", "é"),
+                       ("\"日本語\"[2]", "語"),
+                       ("\"hello\"[-1]", "o"),
+                       ("\"日本語\"[-3]", "日")];
+        for expect in expects.iter() {
+            assert_eq!(test_eval(expect.0.to_string()),
+                       Object::new_string(expect.1.to_string()),
+                       "{}",
+                       expect.0);
+        }
+
+        let errors = [("\"hello\"[5]", "index out of range: max=4 got=5"),
+                      ("\"日本語\"[3]", "index out of range: max=2 got=3"),
+                      ("\"hello\"[-6]", "index out of range: max=4 got=-6"),
+                      ("\"\"[0]", "index out of range: string is empty got=0"),
+                      ("\"hello\"[\"e\"]", "index operator not supported StringType(\"e\")")];
+        for expect in errors.iter() {
+            assert_eq!(test_eval(expect.0.to_string()).to_error_message(),
+                       Some(expect.1.to_string()),
+                       "{}",
+                       expect.0);
+        }
+        assert_eq!(test_eval("\"hello\"[9]".to_string()).to_error_kind(),
+                   Some(ErrorKind::IndexError));
+    }
+
+    #[test]
+    fn it_should_evaluate_slice_expression() {
+        let expects = [("[1, 2, 3, 4][1:3]", "[2, 3]"),
+                       ("[1, 2, 3, 4][:2]", "[1, 2]"),
+                       ("[1, 2, 3, 4][1:]", "[2, 3, 4]"),
+                       ("[1, 2, 3, 4][:]", "[1, 2, 3, 4]"),
+                       ("[1, 2, 3, 4][-2:]", "[3, 4]"),
+                       ("[1, 2, 3, 4][:-1]", "[1, 2, 3]"),
+                       ("[1, 2, 3, 4][2:10]", "[3, 4]"),
+                       ("[1, 2, 3, 4][-10:1]", "[1]"),
+                       ("[1, 2, 3, 4][3:1]", "[]"),
+                       ("[1, 2, 3, 4][5:]", "[]"),
+                       ("[][0:1]", "[]"),
+                       ("let a = [1, 2, 3]; let b = a[1:]; a", "[1, 2, 3]"),
+                       ("\"héllo\"[1:4]", "\"éll\""),
+                       ("\"hello\"[3:]", "\"lo\"")];
+        for expect in expects.iter() {
+            assert_eq!(test_eval(expect.0.to_string()).to_source(),
+                       Some(expect.1.to_string()),
+                       "{}",
+                       expect.0);
+        }
+
+        let errors = [("[1, 2][\"a\":]",
+                       "slice bound must be an integer. got StringType(\"a\")"),
+                      ("[1, 2][:true]", "slice bound must be an integer. got Boolean(true)"),
+                      ("5[1:2]", "slice operator not supported Integer(5)"),
+                      ("[1, 2][1 / 0:]", "division by zero, at line: 1, column: 10")];
+        for expect in errors.iter() {
+            assert_eq!(test_eval(expect.0.to_string()).to_error_message(),
+                       Some(expect.1.to_string()),
+                       "{}",
+                       expect.0);
+        }
+    }
+
     #[test]
     fn it_should_evaluate_array_index_expression() {
         let result = test_eval("[1, 2 * 2, 3 + 3]".to_string());
         if let ObjectType::Array(x) = result.object_type {
             assert_eq!(x.elements.len(), 3);
-            assert_eq!(x.elements[0].to_i32().unwrap(), 1);
-            assert_eq!(x.elements[1].to_i32().unwrap(), 4);
-            assert_eq!(x.elements[2].to_i32().unwrap(), 6);
+            assert_eq!(x.elements[0].to_int().unwrap(), 1);
+            assert_eq!(x.elements[1].to_int().unwrap(), 4);
+            assert_eq!(x.elements[2].to_int().unwrap(), 6);
         } else {
             assert!(false);
         }
@@ -570,17 +2049,718 @@ mod tests {
         let expects = [("len(\"\");", 0), ("len(\"four\");", 4), ("len(\"hello world\");", 11)];
         for expect in expects.iter() {
             let result = test_eval(expect.0.to_string());
-            assert_eq!(result.to_i32().unwrap(), expect.1);
+            assert_eq!(result.to_int().unwrap(), expect.1);
         }
 
         let error_expects = [("len(1);", "argument to \"len\" not supported. got Integer(1)"),
-                             ("len(\"one\", \"two\");", "wrong number of arguments. got 2 want=1")];
+                             ("len(\"one\", \"two\");", "wrong number of arguments. got 2 want=1"),
+                             ("deep_equal(1);", "wrong number of arguments. got 1 want=2"),
+                             ("unwatch();", "wrong number of arguments. got 0 want=1")];
         for expect in error_expects.iter() {
             let result = test_eval(expect.0.to_string());
             assert_eq!(result.to_error_message().unwrap(), expect.1);
         }
     }
 
+    #[test]
+    fn it_should_resolve_identifiers_through_scopes() {
+        let expects = [("let x = 1; let f = fn(x) { x }; f(2) * 10 + x", 21),
+                       ("let x = 1; let f = fn() { let x = 2; x }; f() * 10 + x", 21),
+                       ("let x = 1; let f = fn() { x }; let x = 5; f()", 1),
+                       ("let adder = fn(a) { fn(b) { a + b } }; adder(2)(3)", 5),
+                       ("let a = 1; let f = fn(b) { fn(c) { fn(d) { a + b + c + d } } }; \
+                         f(2)(3)(4)",
+                        10),
+                       ("let x = 1; if (true) { let x = 2; }; x", 2),
+                       ("let y = 1; let h = fn(c) { if (c) { let y = 3; }; y }; \
+                         h(true) * 100 + h(false) * 10 + y",
+                        311),
+                       ("let k = 1; let m = fn() { k = 7; k }; m() * 10 + k", 77),
+                       ("let k = 1; let m = fn() { let k = 7; k }; m() * 10 + k", 71),
+                       ("let k = 1; let m = fn() { fn() { k = k + 1; } }; let inc = m(); \
+                         inc(); inc(); k",
+                        3),
+                       ("let k = 1; let m = fn(k) { k = 5; k }; m(2) * 10 + k", 51),
+                       ("let f = 0; f = fn(n) { if (n < 1) { 0 } else { n + f(n - 1) } }; f(4)",
+                        10),
+                       ("let i = 0; let n = 0; while (i < 3) { let n = n + i; let i = i + 1; }; n",
+                        3),
+                       ("let x = 1; let f = fn() { let out = 0; let i = 0; \
+                         while (i < 2) { let out = out * 10 + x; let x = 5; let i = i + 1; }; \
+                         out }; f()",
+                        15),
+                       ("let f = fn(y) { fn(c) { if (c) { let y = 3; }; y } }; \
+                         f(1)(true) * 10 + f(1)(false)",
+                        31),
+                       ("let f = fn(y) { let g = fn() { y }; let y = 2; g() * 10 + y }; f(1)", 12),
+                       ("let f = fn() { let c = 0; let inc = fn() { c = c + 1; c }; inc(); inc(); \
+                         c }; f()",
+                        2),
+                       ("let f = fn(n) { let rec go = fn(i, acc) { \
+                         if (i > n) { acc } else { go(i + 1, acc + i) } }; go(1, 0) }; f(4)",
+                        10),
+                       ("let f = fn(a, a) { a }; f(1, 2)", 2)];
+        for expect in expects.iter() {
+            assert_eq!(test_eval(expect.0.to_string()).to_int(), Some(expect.1), "{}", expect.0);
+        }
+        assert_eq!(test_eval("let f = fn() { z }; let z = 1; f()".to_string()).to_error_kind(),
+                   Some(ErrorKind::NameError));
+        let cyclic = test_eval("let f = 0; f = fn() { f }; f".to_string());
+        assert!(cyclic.inspect().starts_with("Function"));
+    }
+
+    #[test]
+    fn it_should_limit_scope_depth() {
+        let nested = |n: usize| format!("{}1{}", "fn() { ".repeat(n), " }()".repeat(n));
+        let options = EvalOptions { max_scope_depth: 8, ..EvalOptions::new() };
+
+        assert_eq!(test_eval_with_options(nested(8), options.clone()),
+                   Object::new_int(1));
+        assert_eq!(test_eval_with_options(nested(9), options),
+                   Object::new_error(ErrorKind::LimitError,
+                                     "maximum scope depth exceeded".to_string()));
+        assert_eq!(test_eval(nested(9)), Object::new_int(1));
+    }
+
+    #[test]
+    fn it_should_estimate_size() {
+        let size = |input: &str| test_eval(format!("size_of({})", input)).to_int().unwrap();
+        assert!(size("[1, 2, 3]") > size("[1]"));
+        assert!(size("[1]") > size("[]"));
+        assert!(size("\"monkey\"") > size("\"m\""));
+        assert!(size("{\"a\": [1, 2]}") > size("{\"a\": 1}"));
+        assert!(size("[[1, 2], [3]]") > size("[1, 2, 3]"));
+        assert_eq!(size("1"), size("true"));
+    }
+
+    #[test]
+    fn it_should_limit_heap_size() {
+        let options = EvalOptions { max_heap_bytes: Some(2048), ..EvalOptions::new() };
+        let small = "let xs = [1, 2, 3]; len(xs)";
+        assert_eq!(test_eval_with_options(small.to_string(), options.clone()),
+                   Object::new_int(3));
+
+        let elements = vec!["1"; 200].join(",\n");
+        let big = format!("let xs = [{}]; len(xs)", elements);
+        assert_eq!(test_eval(big.clone()), Object::new_int(200));
+        assert_eq!(test_eval_with_options(big, options.clone()),
+                   Object::new_error(ErrorKind::LimitError, "heap limit exceeded".to_string()));
+
+        let growing = format!("let a = \"{}\"; let b = a + a + a + a; let c = b + b + b + b;",
+                              "x".repeat(100));
+        assert_eq!(test_eval_with_options(growing, options),
+                   Object::new_error(ErrorKind::LimitError, "heap limit exceeded".to_string()));
+    }
+
+    #[test]
+    fn it_should_report_error_kind() {
+        let expects = [("error_kind(1 / 0)", Some("DivByZero")),
+                       ("error_kind(10 % (5 - 5))", Some("DivByZero")),
+                       ("error_kind(1 + true)", Some("TypeError")),
+                       ("error_kind(foo)", Some("NameError")),
+                       ("error_kind([1][5])", Some("IndexError")),
+                       ("error_kind(len(1, 2))", Some("ArgumentError")),
+                       ("error_kind(assert_eq(1, 2))", Some("AssertionError")),
+                       ("error_kind(1 / 1)", None)];
+        for expect in expects.iter() {
+            let expected = match expect.1 {
+                Some(kind) => Object::new_string(kind.to_string()),
+                None => NULL,
+            };
+            assert_eq!(test_eval(expect.0.to_string()), expected);
+        }
+
+        assert_eq!(test_eval("error_kind()".to_string()).to_error_message(),
+                   Some("wrong number of arguments. got 0 want=1".to_string()));
+    }
+
+    #[test]
+    fn it_should_call_native_functions() {
+        let mut env = Enviroment::new();
+        env.register_native("double", |args| match args.first().and_then(|x| x.to_int()) {
+            Some(n) if args.len() == 1 => Object::new_int(n * 2),
+            _ => Object::new_error(ErrorKind::ArgumentError, "double takes an integer".to_string()),
+        });
+        let calls = std::rc::Rc::new(std::cell::RefCell::new(vec![]));
+        let seen = calls.clone();
+        env.register_native("log", move |args| {
+            seen.borrow_mut().push(args.iter().map(|x| x.inspect()).collect::<Vec<_>>().join(" "));
+            NULL
+        });
+
+        let input = "let quad = fn(x) { double(double(x)) }; log(\"quad\", quad(3)); quad(5)";
+        assert_eq!(test_eval_in(input.to_string(), &mut env), Object::new_int(20));
+        assert_eq!(*calls.borrow(), vec!["quad 12".to_string()]);
+
+        let expects = [("map_twice(double, 4)", Object::new_int(16)),
+                       ("let f = double; f(21)", Object::new_int(42)),
+                       ("double == double", TRUE),
+                       ("repr(double)", Object::new_string("double".to_string()))];
+        test_eval_in("let map_twice = fn(f, x) { f(f(x)) };".to_string(), &mut env);
+        for expect in expects.iter() {
+            assert_eq!(test_eval_in(expect.0.to_string(), &mut env), expect.1, "{}", expect.0);
+        }
+        assert_eq!(test_eval_in("double(\"a\")".to_string(), &mut env).to_error_message(),
+                   Some("double takes an integer".to_string()));
+        assert_eq!(test_eval("double(1)".to_string()).to_error_kind(), Some(ErrorKind::NameError));
+    }
+
+    #[test]
+    fn it_should_time_bench_thunk() {
+        let input = "let work = fn() { map(1..100, fn(x) { x * x })[-1] };
+                     let b = bench(work);
+                     [b[\"result\"], b[\"ms\"] > -1]";
+        assert_eq!(test_eval(input.to_string()).to_source(),
+                   Some("[9801, true]".to_string()));
+        let ms = test_eval("bench(fn() { 1 })[\"ms\"]".to_string());
+        assert!(ms.to_f64().is_some(), "{:?}", ms);
+
+        assert_eq!(test_eval("bench(fn() { 1 / 0 })".to_string()).to_error_kind(),
+                   Some(ErrorKind::DivByZero));
+        let result = test_eval("bench(fn(x) { x })".to_string());
+        assert_eq!(result.to_error_kind(), Some(ErrorKind::TypeError));
+        assert!(result.to_error_message()
+                    .unwrap()
+                    .starts_with("argument to \"bench\" must be a function with no parameters"));
+    }
+
+    #[test]
+    fn it_should_map_over_arrays() {
+        let expects = [("map([1, 2, 3], fn(x) { x * 2 })", "[2, 4, 6]"),
+                       ("map([], fn(x) { x })", "[]"),
+                       ("map([[1], [2, 3]], len)", "[1, 2]"),
+                       ("let k = 10; map(1..4, fn(x) { x + k })", "[11, 12, 13]"),
+                       ("let a = [1, 2]; map(a, fn(x) { -x }); a", "[1, 2]")];
+        for expect in expects.iter() {
+            assert_eq!(test_eval(expect.0.to_string()).to_source(),
+                       Some(expect.1.to_string()),
+                       "{}",
+                       expect.0);
+        }
+
+        let input = "map([1, 0, 2], fn(x) { puts(x); 10 / x })";
+        let (result, lines) = output::capture(|| test_eval(input.to_string()));
+        assert_eq!(result.to_error_message(),
+                   Some("division by zero, at line: 1, column: 36".to_string()));
+        assert_eq!(lines, vec!["1".to_string(), "0".to_string()]);
+
+        for input in ["map(1, fn(x) { x })", "map([1], 2)"].iter() {
+            assert_eq!(test_eval(input.to_string()).to_error_kind(),
+                       Some(ErrorKind::TypeError),
+                       "{}",
+                       input);
+        }
+        let errors = [("map([1], fn(x, y) { x })", "wrong number of arguments. got 1 want=2"),
+                      ("map([1])", "wrong number of arguments. got 1 want=2")];
+        for expect in errors.iter() {
+            assert_eq!(test_eval(expect.0.to_string()).to_error_message(),
+                       Some(expect.1.to_string()),
+                       "{}",
+                       expect.0);
+        }
+    }
+
+    #[test]
+    fn it_should_build_strings() {
+        let expects = [("let b = builder(); build_push(b, \"mon\"); build_push(b, \"key\"); \
+                         build_string(b)",
+                        "\"monkey\""),
+                       ("build_string(builder())", "\"\""),
+                       ("build_string(build_push(build_push(builder(), \"a\"), \"b\"))", "\"ab\""),
+                       ("let b = builder(); let s = build_string(build_push(b, \"a\")); \
+                         build_push(b, \"b\"); [s, build_string(b)]",
+                        "[\"a\", \"ab\"]"),
+                       ("let b = builder(); [b == b, b == builder()]", "[true, false]")];
+        for expect in expects.iter() {
+            assert_eq!(test_eval(expect.0.to_string()).to_source(),
+                       Some(expect.1.to_string()),
+                       "{}",
+                       expect.0);
+        }
+
+        let input = "let b = builder(); let s = \"\"; let i = 0; \
+                     while (i < 500) { build_push(b, \"ab\"); let s = s + \"ab\"; \
+                     let i = i + 1; }; \
+                     [len(build_string(b)), deep_equal(build_string(b), s)]";
+        assert_eq!(test_eval(input.to_string()).to_source(),
+                   Some("[1000, true]".to_string()));
+
+        let errors = [("build_push(builder(), 1)",
+                       "argument to \"build_push\" not supported. got Builder(StringBuilder { \
+                        buffer: \"\" }), Integer(1)"),
+                      ("build_push(\"a\", \"b\")",
+                       "argument to \"build_push\" not supported. got StringType(\"a\"), \
+                        StringType(\"b\")"),
+                      ("build_string(\"a\")",
+                       "argument to \"build_string\" not supported. got StringType(\"a\")"),
+                      ("builder(1)", "wrong number of arguments. got 1 want=0")];
+        for expect in errors.iter() {
+            assert_eq!(test_eval(expect.0.to_string()).to_error_message(),
+                       Some(expect.1.to_string()),
+                       "{}",
+                       expect.0);
+        }
+    }
+
+    #[test]
+    fn it_should_apply_functions_to_argument_arrays() {
+        let expects = [("let add = fn(a, b) { a + b }; apply(add, [1, 2])", "3"),
+                       ("apply(fn() { 7 }, [])", "7"),
+                       ("apply(len, [\"abc\"])", "3"),
+                       ("let args = [[1], 2]; apply(push, args)", "[1, 2]"),
+                       ("apply(apply, [fn(x) { -x }, [4]])", "-4")];
+        for expect in expects.iter() {
+            assert_eq!(test_eval(expect.0.to_string()).to_source(),
+                       Some(expect.1.to_string()),
+                       "{}",
+                       expect.0);
+        }
+
+        let errors = [("apply(fn(a, b) { a + b }, [1])", "wrong number of arguments. got 1 want=2"),
+                      ("apply(len, [])", "wrong number of arguments. got 0 want=1"),
+                      ("apply(1, [1])",
+                       "first argument to \"apply\" must be a function. got Integer(1)"),
+                      ("apply(len, \"abc\")",
+                       "second argument to \"apply\" must be an array. got StringType(\"abc\")")];
+        for expect in errors.iter() {
+            assert_eq!(test_eval(expect.0.to_string()).to_error_message(),
+                       Some(expect.1.to_string()),
+                       "{}",
+                       expect.0);
+        }
+    }
+
+    #[test]
+    fn it_should_take_and_drop_while() {
+        let expects = [("take_while([1, 2, 3, 0, 4], fn(x) { x > 0 })", "[1, 2, 3]"),
+                       ("drop_while([1, 2, 3, 0, 4], fn(x) { x > 0 })", "[0, 4]"),
+                       ("take_while([1, 2], fn(x) { true })", "[1, 2]"),
+                       ("drop_while([1, 2], fn(x) { true })", "[]"),
+                       ("take_while([1, 2], fn(x) { false })", "[]"),
+                       ("drop_while([1, 2], fn(x) { false })", "[1, 2]"),
+                       ("take_while([], fn(x) { true })", "[]"),
+                       ("drop_while([], fn(x) { true })", "[]"),
+                       ("take_while([[1], [], [2]], first)", "[[1]]")];
+        for expect in expects.iter() {
+            assert_eq!(test_eval(expect.0.to_string()).to_source(),
+                       Some(expect.1.to_string()),
+                       "{}",
+                       expect.0);
+        }
+
+        let input = "drop_while([1, 0, 2], fn(x) { puts(x); 1 / x })";
+        let (result, lines) = output::capture(|| test_eval(input.to_string()));
+        assert_eq!(result.to_error_kind(), Some(ErrorKind::DivByZero));
+        assert_eq!(lines, vec!["1".to_string(), "0".to_string()]);
+
+        let errors = [("take_while(1, fn(x) { x })",
+                       "first argument to \"take_while\" must be an array or an iterable. got \
+                        Integer(1)"),
+                      ("drop_while([1], 2)",
+                       "second argument to \"drop_while\" must be a function. got Integer(2)"),
+                      ("take_while([1], fn(x, y) { x })",
+                       "wrong number of arguments. got 1 want=2")];
+        for expect in errors.iter() {
+            assert_eq!(test_eval(expect.0.to_string()).to_error_message(),
+                       Some(expect.1.to_string()),
+                       "{}",
+                       expect.0);
+        }
+    }
+
+    #[test]
+    fn it_should_iterate_over_iterable_hashes() {
+        let counter = "let counter = fn(n) { {\"__iter__\": fn(i) { if (i < n) { i + 1 } }} };";
+        let expects = [("reduce(counter(4), 0, fn(acc, x) { acc + x })", "10"),
+                       ("map(counter(3), fn(x) { x * x })", "[1, 4, 9]"),
+                       ("map(counter(0), fn(x) { x })", "[]"),
+                       ("take_while(counter(5), fn(x) { x < 3 })", "[1, 2]"),
+                       ("drop_while(counter(5), fn(x) { x < 3 })", "[3, 4, 5]"),
+                       ("map({\"__iter__\": fn(i) { if (i < 2) { [\"a\", \"b\"][i] } }}, \
+                         fn(x) { x + x })",
+                        "[\"aa\", \"bb\"]")];
+        for expect in expects.iter() {
+            assert_eq!(test_eval(format!("{} {}", counter, expect.0)).to_source(),
+                       Some(expect.1.to_string()),
+                       "{}",
+                       expect.0);
+        }
+
+        let naturals = "let naturals = {\"__iter__\": fn(i) { i }};";
+        let expects = [("take_while(naturals, fn(x) { x < 4 })", "[0, 1, 2, 3]"),
+                       ("take_while(naturals, fn(x) { false })", "[]"),
+                       ("take_while(naturals, fn(x) { if (x < 2) { true } else { 1 / 0 } })",
+                        "division by zero, at line: 1, column: 101")];
+        for expect in expects.iter() {
+            let result = test_eval(format!("{} {}", naturals, expect.0));
+            assert_eq!(result.to_source().or(result.to_error_message()),
+                       Some(expect.1.to_string()),
+                       "{}",
+                       expect.0);
+        }
+
+        let input = "map({\"__iter__\": fn(i) { 10 / (1 - i) }}, fn(x) { x })";
+        assert_eq!(test_eval(input.to_string()).to_error_kind(), Some(ErrorKind::DivByZero));
+        let input = "map({\"__iter__\": fn() { 1 }}, fn(x) { x })";
+        assert_eq!(test_eval(input.to_string()).to_error_message(),
+                   Some("wrong number of arguments. got 1 want=0".to_string()));
+        let input = "map({\"__iter__\": 1}, fn(x) { x })";
+        assert_eq!(test_eval(input.to_string()).to_error_kind(), Some(ErrorKind::TypeError));
+        let input = "to_array({\"__iter__\": fn(i) { if (i < 2) { i } }})";
+        assert_eq!(test_eval(input.to_string()).to_source(), Some("[0, 1]".to_string()));
+    }
+
+    #[test]
+    fn it_should_load_the_prelude() {
+        let expects = [("filter([1, 2, 3, 4], fn(x) { x % 2 == 0 })", "[2, 4]"),
+                       ("filter([], fn(x) { true })", "[]"),
+                       ("filter(map([1, 2, 3], fn(x) { x * 2 }), fn(x) { x > 2 })", "[4, 6]"),
+                       ("let filter = fn(xs, f) { xs }; filter([1], fn(x) { false })", "[1]")];
+        for expect in expects.iter() {
+            assert_eq!(test_eval(expect.0.to_string()).to_source(),
+                       Some(expect.1.to_string()),
+                       "{}",
+                       expect.0);
+        }
+        let input = "filter([1, 0], fn(x) { 1 / x })";
+        assert_eq!(test_eval(input.to_string()).to_error_kind(), Some(ErrorKind::DivByZero));
+
+        let bare = EvalOptions { load_prelude: false, ..EvalOptions::new() };
+        let input = "filter([1], fn(x) { true })";
+        assert_eq!(test_eval_with_options(input.to_string(), bare.clone()).to_error_message(),
+                   Some("identifier not found: filter".to_string()));
+        for name in ["map", "reduce"].iter() {
+            let input = format!("{}([1], fn(x) {{ x }})", name);
+            assert_eq!(test_eval_with_options(input, bare.clone()).to_error_message(),
+                       Some(format!("identifier not found: {}", name)));
+        }
+
+        let input = "map([1, 2, 3], fn(x) { x * 2 })";
+        assert_eq!(test_eval(input.to_string()).to_source(), Some("[2, 4, 6]".to_string()));
+        let input = "reduce(map([1, 2, 3], fn(x) { x * 2 }), 0, fn(acc, x) { acc + x })";
+        assert_eq!(test_eval(input.to_string()).to_source(), Some("12".to_string()));
+
+        // Prelude code runs under the caller's limits.
+        let limited = EvalOptions { max_heap_bytes: Some(256), ..EvalOptions::new() };
+        let input = "map(0..100, fn(x) { x })";
+        assert_eq!(test_eval_with_options(input.to_string(), limited).to_error_kind(),
+                   Some(ErrorKind::LimitError));
+    }
+
+    #[test]
+    fn it_should_reduce_arrays() {
+        let expects = [("reduce([1, 2, 3], 0, fn(acc, x) { acc + x })", "6"),
+                       ("reduce([], 42, fn(acc, x) { acc + x })", "42"),
+                       ("reduce([1, 2], [], push)", "[1, 2]"),
+                       ("reduce([\"a\", \"b\"], \"\", fn(acc, x) { x + acc })", "\"ba\"")];
+        for expect in expects.iter() {
+            assert_eq!(test_eval(expect.0.to_string()).to_source(),
+                       Some(expect.1.to_string()),
+                       "{}",
+                       expect.0);
+        }
+
+        let input = "reduce([2, 0, 1], 10, fn(acc, x) { puts(x); acc / x })";
+        let (result, lines) = output::capture(|| test_eval(input.to_string()));
+        assert_eq!(result.to_error_message(),
+                   Some("division by zero, at line: 1, column: 49".to_string()));
+        assert_eq!(lines, vec!["2".to_string(), "0".to_string()]);
+
+        for input in ["reduce(1, 0, fn(acc, x) { acc })", "reduce([1], 0, 2)"].iter() {
+            assert_eq!(test_eval(input.to_string()).to_error_kind(),
+                       Some(ErrorKind::TypeError),
+                       "{}",
+                       input);
+        }
+        let errors = [("reduce([1], 0, fn(x) { x })", "wrong number of arguments. got 2 want=1"),
+                      ("reduce([1], 0)", "wrong number of arguments. got 2 want=3")];
+        for expect in errors.iter() {
+            assert_eq!(test_eval(expect.0.to_string()).to_error_message(),
+                       Some(expect.1.to_string()),
+                       "{}",
+                       expect.0);
+        }
+    }
+
+    #[test]
+    fn it_should_run_once_thunk_a_single_time() {
+        let input = "let config = once(fn() { puts(\"loading\"); {\"level\": 3} });
+                     let a = config();
+                     let b = config();
+                     [a[\"level\"], b[\"level\"], config()[\"level\"]]";
+        let (result, lines) = output::capture(|| test_eval(input.to_string()));
+        assert_eq!(result.to_source(), Some("[3, 3, 3]".to_string()));
+        assert_eq!(lines, vec!["loading".to_string()]);
+
+        let input = "let a = once(fn() { puts(\"a\"); 1 });
+                     let b = once(fn() { puts(\"b\"); 2 });
+                     a() + b() + a() + b()";
+        let (result, lines) = output::capture(|| test_eval(input.to_string()));
+        assert_eq!(result, Object::new_int(6));
+        assert_eq!(lines, vec!["a".to_string(), "b".to_string()]);
+
+        let input = "let failing = once(fn() { puts(\"try\"); 1 / 0 });
+                     error_kind(failing());
+                     failing()";
+        let (result, lines) = output::capture(|| test_eval(input.to_string()));
+        assert_eq!(result.to_error_kind(), Some(ErrorKind::DivByZero));
+        assert_eq!(lines, vec!["try".to_string(), "try".to_string()]);
+
+        assert_eq!(test_eval("once(fn() { 1 })(2)".to_string()).to_error_message(),
+                   Some("wrong number of arguments. got 1 want=0".to_string()));
+        assert_eq!(test_eval("once(fn(x) { x })".to_string()).to_error_kind(),
+                   Some(ErrorKind::TypeError));
+        assert_eq!(test_eval("once(1)".to_string()).to_error_kind(), Some(ErrorKind::TypeError));
+    }
+
+    #[test]
+    fn it_should_report_error_data() {
+        let input = "let check = fn(x) { assert_eq(x * 2, 4); x };
+                     let data = error_data(check(3));
+                     [data[\"actual\"], data[\"expected\"]]";
+        assert_eq!(test_eval(input.to_string()).to_source(), Some("[6, 4]".to_string()));
+
+        let expects = ["error_data(1 / 0)",
+                       "error_data(foo)",
+                       "error_data(check(2))",
+                       "error_data(5)"];
+        for expect in expects.iter() {
+            let input = format!("let check = fn(x) {{ assert_eq(x, 2) }}; {}", expect);
+            assert_eq!(test_eval(input), NULL, "{}", expect);
+        }
+
+        let input = "error_data(-assert_eq([1], [2]))[\"actual\"]";
+        assert_eq!(test_eval(input.to_string()).to_source(), Some("[1]".to_string()));
+        assert_eq!(test_eval("error_data()".to_string()).to_error_kind(),
+                   Some(ErrorKind::ArgumentError));
+    }
+
+    #[test]
+    fn it_should_assert_equality() {
+        assert_eq!(test_eval("assert_eq(1 + 1, 2)".to_string()), NULL);
+        assert_eq!(test_eval("assert_eq([1, \"a\"], [1, \"a\"])".to_string()), NULL);
+        let result = test_eval("assert_eq(1, 2); 3".to_string());
+        assert_eq!(result.to_error_kind(), Some(ErrorKind::AssertionError));
+        assert_eq!(result.to_error_message(),
+                   Some("assertion failed: got 1, expected 2".to_string()));
+        assert_eq!(test_eval("assert_eq(\"a\", true)".to_string()).to_error_message(),
+                   Some("assertion failed: got a, expected true".to_string()));
+    }
+
+    #[test]
+    fn it_should_trace_and_return_argument() {
+        let input = "let y = trace(1 + 2) * 2; trace(\"ok\"); y";
+        let (result, lines) = output::capture(|| test_eval(input.to_string()));
+        assert_eq!(result, Object::new_int(6));
+        assert_eq!(lines, vec!["trace: 3".to_string(), "trace: ok".to_string()]);
+    }
+
+    #[test]
+    fn it_should_take_first_and_rest() {
+        let expects = [("first([1, 2, 3])", "1"),
+                       ("first([])", "null"),
+                       ("rest([1, 2, 3])", "[2, 3]"),
+                       ("rest([1])", "[]"),
+                       ("rest([])", "null"),
+                       ("first(\"abc\")", "\"a\""),
+                       ("first(\"\")", "null"),
+                       ("rest(\"abc\")", "\"bc\""),
+                       ("rest(\"a\")", "\"\""),
+                       ("rest(\"\")", "\"\"")];
+        for expect in expects.iter() {
+            let result = test_eval(expect.0.to_string());
+            assert_eq!(result.to_source().unwrap_or("null".to_string()), expect.1);
+        }
+        assert_eq!(test_eval("first(1)".to_string()).to_error_message(),
+                   Some("argument to \"first\" not supported. got Integer(1)".to_string()));
+    }
+
+    #[test]
+    fn it_should_append_to_strings() {
+        let expects = [("append(\"ab\", \"c\")", "abc"),
+                       ("append(append(\"a\", \"b\"), \"c\")", "abc"),
+                       ("append(\"n=\", 1 + 2)", "n=3"),
+                       ("append(\"\", false)", "false"),
+                       ("append(\"a\", 1.5)", "a1.5"),
+                       ("append(\"a\", [1, \"b\"])", "a[1, \"b\"]"),
+                       ("append(\"a\", {})", "a{}"),
+                       ("append(\"a\", if (false) { 1 })", "anull")];
+        for expect in expects.iter() {
+            assert_eq!(test_eval(expect.0.to_string()),
+                       Object::new_string(expect.1.to_string()),
+                       "{}",
+                       expect.0);
+        }
+        let error_expects = [("append(1, \"a\")",
+                              "argument to \"append\" not supported. got Integer(1), \
+                               StringType(\"a\")")];
+        for expect in error_expects.iter() {
+            assert_eq!(test_eval(expect.0.to_string()).to_error_message(),
+                       Some(expect.1.to_string()));
+        }
+    }
+
+    #[test]
+    fn it_should_push_into_a_new_array() {
+        let expects = [("push([1, 2], 3)", "[1, 2, 3]"),
+                       ("push([], \"a\")", "[\"a\"]"),
+                       ("push(push([1], [2]), 3)", "[1, [2], 3]"),
+                       ("let a = [1, 2]; let b = push(a, 3); a", "[1, 2]"),
+                       ("let a = freeze([1]); push(a, 2)", "[1, 2]")];
+        for expect in expects.iter() {
+            assert_eq!(test_eval(expect.0.to_string()).to_source().unwrap(), expect.1);
+        }
+        assert_eq!(test_eval("push(1, 2)".to_string()).to_error_message(),
+                   Some("argument to \"push\" not supported. got Integer(1)".to_string()));
+        assert_eq!(test_eval("push([1])".to_string()).to_error_message(),
+                   Some("wrong number of arguments. got 1 want=2".to_string()));
+    }
+
+    #[test]
+    fn it_should_convert_to_array() {
+        let expects = [("to_array(\"abc\")", "[\"a\", \"b\", \"c\"]"),
+                       ("to_array(\"\")", "[]"),
+                       ("to_array({\"a\": 1})", "[[\"a\", 1]]"),
+                       ("to_array({2: true, 1: false})", "[[1, false], [2, true]]"),
+                       ("to_array([1, [2]])", "[1, [2]]")];
+        for expect in expects.iter() {
+            assert_eq!(test_eval(expect.0.to_string()).to_source().unwrap(), expect.1);
+        }
+        assert_eq!(test_eval("to_array(1)".to_string()).to_error_message(),
+                   Some("argument to \"to_array\" not supported. got Integer(1)".to_string()));
+    }
+
+    #[test]
+    fn it_should_evaluate_range_operator() {
+        let expects = [("1..4", "[1, 2, 3]"),
+                       ("1..=4", "[1, 2, 3, 4]"),
+                       ("3..3", "[]"),
+                       ("3..=3", "[3]"),
+                       ("5..1", "[]"),
+                       ("-2..1", "[-2, -1, 0]"),
+                       ("let n = 2; 0..n * 2", "[0, 1, 2, 3]"),
+                       ("2147483646..=2147483647", "[2147483646, 2147483647]"),
+                       ("[(0..10)[3], len(0..100)]", "[3, 100]"),
+                       ("let r = 2..5; [r[0], r[len(r) - 1], 4 in r, 5 in r]",
+                        "[2, 4, true, false]")];
+        for expect in expects.iter() {
+            assert_eq!(test_eval(expect.0.to_string()).to_source().unwrap(),
+                       expect.1,
+                       "{}",
+                       expect.0);
+        }
+
+        let errors = [("\"a\"..\"c\"", "unknown operator: String .. String, at line: 1, column: 4"),
+                      ("1..true",
+                       "type mismatch: Integer(1) .. Boolean(true), at line: 1, column: 2")];
+        for expect in errors.iter() {
+            assert_eq!(test_eval(expect.0.to_string()).to_error_message(),
+                       Some(expect.1.to_string()),
+                       "{}",
+                       expect.0);
+        }
+
+        let limited = EvalOptions { max_heap_bytes: Some(2048), ..EvalOptions::new() };
+        assert_eq!(test_eval_with_options("0..2147483647".to_string(), limited).to_error_kind(),
+                   Some(ErrorKind::LimitError));
+
+        let huge = ["0..9223372036854775807", "-9223372036854775807..=0", "0..=1048576"];
+        for input in huge.iter() {
+            assert_eq!(test_eval(input.to_string()).to_error_kind(),
+                       Some(ErrorKind::LimitError),
+                       "{}",
+                       input);
+        }
+    }
+
+    #[test]
+    fn it_should_evaluate_in_operator() {
+        let expects = [("2 in [1, 2, 3]", true),
+                       ("4 in [1, 2, 3]", false),
+                       ("\"2\" in [1, 2, 3]", false),
+                       ("[1] in [[1], 2]", true),
+                       ("1 in [1.0]", true),
+                       ("1.0 in [2, 1]", true),
+                       ("[1] in [[1.0]]", true),
+                       ("1.5 in [1, 2]", false),
+                       ("1 in []", false),
+                       ("\"a\" in {\"a\": 1}", true),
+                       ("\"b\" in {\"a\": 1}", false),
+                       ("true in {true: 1}", true),
+                       ("\"ell\" in \"hello\"", true),
+                       ("\"\" in \"hello\"", true),
+                       ("\"x\" in \"hello\"", false),
+                       ("1 + 1 in [2] == true", true)];
+        for expect in expects.iter() {
+            assert_eq!(test_eval(expect.0.to_string()),
+                       native_bool_to_boolean_obj(expect.1),
+                       "{}",
+                       expect.0);
+        }
+
+        let errors = [("1 in 2",
+                       "unknown operator: Integer(1) in Integer(2), at line: 1, column: 3"),
+                      ("[1] in {}", "unusable as hash key: Array(Array { elements: \
+                                     [Object { object_type: Integer(1) }] }), \
+                                     at line: 1, column: 5"),
+                      ("1 in \"1\"",
+                       "type mismatch: Integer(1) in StringType(\"1\"), at line: 1, column: 3")];
+        for expect in errors.iter() {
+            assert_eq!(test_eval(expect.0.to_string()).to_error_message(),
+                       Some(expect.1.to_string()),
+                       "{}",
+                       expect.0);
+        }
+    }
+
+    #[test]
+    fn it_should_check_containers_for_members() {
+        let expects = [("contains({\"a\": 1}, \"a\")", true),
+                       ("contains({\"a\": 1}, \"b\")", false),
+                       ("contains({1: \"a\"}, \"1\")", false),
+                       ("contains({true: 1}, true)", true),
+                       ("contains([1, 2, 3], 2)", true),
+                       ("contains([1, 2, 3], 4)", false),
+                       ("contains([[1], 2], [1])", true),
+                       ("contains([], 1)", false),
+                       ("contains([1, 2], 2.0)", true),
+                       ("contains([1.5], 1)", false),
+                       ("contains([[1, 2.0]], [1.0, 2])", true),
+                       ("contains([{\"a\": 1}], {\"a\": 1.0})", true)];
+        for expect in expects.iter() {
+            assert_eq!(test_eval(expect.0.to_string()),
+                       native_bool_to_boolean_obj(expect.1),
+                       "{}",
+                       expect.0);
+        }
+
+        // `contains` and `in` agree on what equal means.
+        let pairs = [("[1, 2]", "2.0"), ("[[1]]", "[1.0]"), ("[0.1 + 0.2]", "0.3"), ("[\"1\"]", "1")];
+        for &(array, value) in pairs.iter() {
+            assert_eq!(test_eval(format!("contains({}, {})", array, value)),
+                       test_eval(format!("{} in {}", value, array)),
+                       "{} {}",
+                       array,
+                       value);
+        }
+
+        let errors = [("contains({}, [1])",
+                       "unusable as hash key: Array(Array { elements: \
+                        [Object { object_type: Integer(1) }] })"),
+                      ("contains({}, {})",
+                       "unusable as hash key: HashType(HashType { pairs: {} })"),
+                      ("contains(\"abc\", \"a\")",
+                       "argument to \"contains\" not supported. got StringType(\"abc\")")];
+        for expect in errors.iter() {
+            let result = test_eval(expect.0.to_string());
+            assert_eq!(result.to_error_kind(), Some(ErrorKind::TypeError), "{}", expect.0);
+            assert_eq!(result.to_error_message(),
+                       Some(expect.1.to_string()),
+                       "{}",
+                       expect.0);
+        }
+    }
+
     #[test]
     fn it_should_evaluate_hash_literal() {
         let result = test_eval("
@@ -598,16 +2778,16 @@ mod tests {
             let key_1 = HashKey::StringType("one".to_string());
             let key_2 = HashKey::StringType("two".to_string());
             let key_3 = HashKey::StringType("three".to_string());
-            let key_4 = HashKey::Integer(4 as i32);
+            let key_4 = HashKey::Integer(4 as i64);
             let key_5 = HashKey::Boolean(true);
             let key_6 = HashKey::Boolean(false);
 
-            let value_1 = Object::new_i32(1);
-            let value_2 = Object::new_i32(2);
-            let value_3 = Object::new_i32(3);
-            let value_4 = Object::new_i32(4);
-            let value_5 = Object::new_i32(5);
-            let value_6 = Object::new_i32(6);
+            let value_1 = Object::new_int(1);
+            let value_2 = Object::new_int(2);
+            let value_3 = Object::new_int(3);
+            let value_4 = Object::new_int(4);
+            let value_5 = Object::new_int(5);
+            let value_6 = Object::new_int(6);
 
             assert_eq!(*x.pairs.get(&key_1).unwrap(), value_1);
             assert_eq!(*x.pairs.get(&key_2).unwrap(), value_2);
@@ -621,6 +2801,33 @@ mod tests {
         }
     }
 
+    #[test]
+    fn it_should_treat_identifier_hash_keys_as_strings() {
+        let expects = [("{name: 1}[\"name\"]", Some(1)),
+                       ("let name = \"other\"; {name: 1}[\"name\"]", Some(1)),
+                       ("let name = \"other\"; {name: 1}[\"other\"]", None),
+                       ("{a: {b: 2}}[\"a\"][\"b\"]", Some(2)),
+                       ("{\"a\" + \"b\": 3}[\"ab\"]", Some(3)),
+                       ("let k = \"x\"; {k + \"y\": 4}[\"xy\"]", Some(4)),
+                       ("let v = 5; {v: v}[\"v\"]", Some(5))];
+        for expect in expects.iter() {
+            assert_eq!(test_eval(expect.0.to_string()).to_int(), expect.1, "{}", expect.0);
+        }
+        assert_eq!(test_eval("{x: 1, y: [true]}".to_string()),
+                   test_eval("{\"x\": 1, \"y\": [true]}".to_string()));
+    }
+
+    #[test]
+    fn it_should_reject_unusable_hash_keys() {
+        let expects = [("{[]: 1}", "unusable as hash key: Array(Array { elements: [] })"),
+                       ("{\"a\": 1}[{}]",
+                        "unusable as hash key: HashType(HashType { pairs: {} })")];
+        for expect in expects.iter() {
+            let result = test_eval(expect.0.to_string());
+            assert_eq!(result.to_error_message().unwrap(), expect.1);
+        }
+    }
+
     #[test]
     fn it_should_evaluate_hash_index_expression() {
         let expects = [("{\"foo\": 5}[\"foo\"]", Some(5)),
@@ -632,8 +2839,446 @@ mod tests {
                        ("{false: 5}[false]", Some(5))];
         for expect in expects.iter() {
             let result = test_eval(expect.0.to_string());
-            assert_eq!(result.to_i32(), expect.1);
+            assert_eq!(result.to_int(), expect.1);
         }
     }
-}
 
+    #[test]
+    fn it_should_round_trip_source_representation() {
+        let expects = ["[1, {\"a\": 2}]",
+                       "{true: [\"x\", -1], 2: \"two\"}",
+                       "len"];
+        for expect in expects.iter() {
+            let original = test_eval(expect.to_string());
+            let source = original.to_source().unwrap();
+            assert_eq!(test_eval(source), original);
+        }
+
+        let function = test_eval("fn(x, y) { let z = x * y; if (z > 10) { z } else { -z } }"
+                                     .to_string());
+        let source = function.to_source().unwrap();
+        assert_eq!(source,
+                   "fn(x, y) { let z = (x * y); if ((z > 10)) { z; } else { (-z); }; }");
+        assert_eq!(test_eval(format!("{}(3, 4)", source)).to_int().unwrap(), 12);
+        assert_eq!(test_eval(format!("{}(1, 2)", source)).to_int().unwrap(), -2);
+
+        let repr = test_eval("repr([1, \"two\"])".to_string());
+        assert_eq!(repr.to_string().unwrap(), "[1, \"two\"]");
+    }
+
+    #[test]
+    fn it_should_convert_objects_to_strings() {
+        let expects = [("str(42)", "42"),
+                       ("str(-1.5)", "-1.5"),
+                       ("str(true)", "true"),
+                       ("str(false)", "false"),
+                       ("str(\"monkey\")", "monkey"),
+                       ("str([1, 2])", "[1, 2]"),
+                       ("str([\"a\", [true]])", "[\"a\", [true]]"),
+                       ("str({\"a\": 1})", "{\"a\": 1}"),
+                       ("str(if (false) { 1 })", "null"),
+                       ("str(len)", "len"),
+                       ("str(fn(x) { x })", "fn(x) { x; }"),
+                       ("\"n = \" + str(1 + 2)", "n = 3")];
+        for expect in expects.iter() {
+            assert_eq!(test_eval(expect.0.to_string()).to_string(),
+                       Some(expect.1.to_string()),
+                       "{}",
+                       expect.0);
+        }
+
+        assert_eq!(test_eval("str()".to_string()).to_error_message(),
+                   Some("wrong number of arguments. got 0 want=1".to_string()));
+        assert_eq!(test_eval("str(1, 2)".to_string()).to_error_kind(),
+                   Some(ErrorKind::ArgumentError));
+    }
+
+    #[test]
+    fn it_should_represent_strings_with_escapes() {
+        let quoted = Object::new_string("say \"hi\"\n".to_string());
+        assert_eq!(quoted.to_source(), Some("\"say \\\"hi\\\"\\n\"".to_string()));
+        let mut pairs = HashMap::new();
+        pairs.insert(HashKey::StringType("a\"b".to_string()), Object::new_int(1));
+        assert_eq!(Object::new_hash(pairs).to_source(),
+                   Some("{\"a\\\"b\": 1}".to_string()));
+
+        let input = r#"let s = "say \"hi\"\n"; repr(s)"#;
+        let repr = test_eval(input.to_string()).to_string().unwrap();
+        assert_eq!(test_eval(repr.clone()).to_string(),
+                   Some("say \"hi\"\n".to_string()),
+                   "{}",
+                   repr);
+    }
+
+    #[test]
+    fn it_should_evaluate_string_escapes() {
+        let expects = [(r#""line1\nline2""#, "line1\nline2"),
+                       (r#""a\tb""#, "a\tb"),
+                       (r#""say \"hi\"""#, "say \"hi\""),
+                       (r#""back\\slash""#, "back\\slash"),
+                       (r#""\\n""#, "\\n")];
+        for expect in expects.iter() {
+            assert_eq!(test_eval(expect.0.to_string()).to_string(),
+                       Some(expect.1.to_string()),
+                       "{}",
+                       expect.0);
+        }
+        assert_eq!(test_eval(r#"len("\n\t")"#.to_string()).to_int(), Some(2));
+    }
+
+    #[test]
+    fn it_should_compare_deeply_with_build_in() {
+        let expects = [("deep_equal([1, [2]], [1, [2]])", true),
+                       ("deep_equal([1, [2]], [1, [3]])", false),
+                       ("deep_equal([1, [2]], [1, [2], 3])", false),
+                       ("deep_equal({\"a\": [1, {\"b\": 2}]}, {\"a\": [1, {\"b\": 2}]})", true),
+                       ("deep_equal({\"a\": [1, {\"b\": 2}]}, {\"a\": [1, {\"b\": \"2\"}]})", false),
+                       ("deep_equal({\"a\": 1, \"b\": 2}, {\"b\": 2, \"a\": 1})", true),
+                       ("deep_equal(1, \"1\")", false),
+                       ("let f = fn(x) { x }; deep_equal(f, f)", true),
+                       ("deep_equal(fn(x) { x }, fn(y) { y })", false),
+                       ("deep_equal(fn(x) { x }, fn(x) { x })", false),
+                       ("let make = fn() { fn(x) { x } }; deep_equal(make(), make())", false),
+                       ("let make = fn() { fn(x) { x } }; let f = make(); deep_equal([f], [f])",
+                        true)];
+        for expect in expects.iter() {
+            let result = test_eval(expect.0.to_string());
+            assert_eq!(result.to_bool().unwrap(), expect.1);
+        }
+    }
+
+    #[test]
+    fn it_should_convert_to_number() {
+        let expects = [("num(\"42\")", Object::new_int(42)),
+                       ("num(\" -7 \")", Object::new_int(-7)),
+                       ("num(\"3.5\")", Object::new_f64(3.5)),
+                       ("num(\"1e3\")", Object::new_f64(1000.0)),
+                       ("num(12)", Object::new_int(12)),
+                       ("num(0.25)", Object::new_f64(0.25)),
+                       ("num(\"2\") + num(\"0.5\")", Object::new_f64(2.5))];
+        for expect in expects.iter() {
+            assert_eq!(test_eval(expect.0.to_string()), expect.1, "{}", expect.0);
+        }
+
+        let errors = [("num(\"abc\")", "could not parse \"abc\" as a number"),
+                      ("num(\"\")", "could not parse \"\" as a number"),
+                      ("num(\"nan\")", "could not parse \"nan\" as a number"),
+                      ("num(true)", "argument to \"num\" not supported. got Boolean(true)")];
+        for expect in errors.iter() {
+            let result = test_eval(expect.0.to_string());
+            assert_eq!(result.to_error_kind(), Some(ErrorKind::TypeError), "{}", expect.0);
+            assert_eq!(result.to_error_message(), Some(expect.1.to_string()), "{}", expect.0);
+        }
+    }
+
+    #[test]
+    fn it_should_convert_to_integer() {
+        let expects = [("int(\"42\")", 42),
+                       ("int(\" -7 \")", -7),
+                       ("int(3.9)", 3),
+                       ("int(-3.9)", -3),
+                       ("int(0.5)", 0),
+                       ("int(12)", 12),
+                       ("int(\"9223372036854775807\")", i64::MAX),
+                       ("int(num(\"2.5\") * 4.0)", 10)];
+        for expect in expects.iter() {
+            assert_eq!(test_eval(expect.0.to_string()), Object::new_int(expect.1), "{}", expect.0);
+        }
+
+        let errors = [("int(\"abc\")",
+                       ErrorKind::TypeError,
+                       "could not parse \"abc\" as an integer"),
+                      ("int(\"3.9\")",
+                       ErrorKind::TypeError,
+                       "could not parse \"3.9\" as an integer"),
+                      ("int(true)",
+                       ErrorKind::TypeError,
+                       "argument to \"int\" not supported. got Boolean(true)"),
+                      ("int(num(\"1e19\"))",
+                       ErrorKind::OverflowError,
+                       "10000000000000000000 is out of integer range")];
+        for expect in errors.iter() {
+            let result = test_eval(expect.0.to_string());
+            assert_eq!(result.to_error_kind(), Some(expect.1), "{}", expect.0);
+            assert_eq!(result.to_error_message(), Some(expect.2.to_string()), "{}", expect.0);
+        }
+    }
+
+    #[test]
+    fn it_should_compare_with_cmp_build_in() {
+        let expects = [("cmp(1, 2)", -1),
+                       ("cmp(2, 2)", 0),
+                       ("cmp(3, 2)", 1),
+                       ("cmp(-5, 2)", -1),
+                       ("cmp(\"apple\", \"banana\")", -1),
+                       ("cmp(\"b\", \"b\")", 0),
+                       ("cmp(\"b\", \"abc\")", 1),
+                       ("cmp(false, true)", -1),
+                       ("cmp([1, 2], [1, 2])", 0),
+                       ("let by = fn(a, b) { cmp(b, a) }; by(1, 2)", 1)];
+        for expect in expects.iter() {
+            assert_eq!(test_eval(expect.0.to_string()).to_int(), Some(expect.1), "{}", expect.0);
+        }
+
+        let result = test_eval("cmp(1, \"1\")".to_string());
+        assert_eq!(result.to_error_kind(), Some(ErrorKind::TypeError));
+        assert_eq!(result.to_error_message(),
+                   Some("arguments to \"cmp\" not comparable. got Integer(1), StringType(\"1\")"
+                            .to_string()));
+        assert_eq!(test_eval("cmp([1], [2])".to_string()).to_error_kind(),
+                   Some(ErrorKind::TypeError));
+    }
+
+    #[test]
+    fn it_should_hash_values_deterministically() {
+        let abc = test_eval("hash(\"abc\")".to_string()).to_int().unwrap();
+        assert_eq!(test_eval("let s = \"ab\"; hash(s + \"c\")".to_string()).to_int(), Some(abc));
+        assert_ne!(test_eval("hash(\"abd\")".to_string()).to_int(), Some(abc));
+        assert_ne!(test_eval("hash(1)".to_string()), test_eval("hash(\"1\")".to_string()));
+        assert_ne!(test_eval("hash(true)".to_string()), test_eval("hash(1)".to_string()));
+
+        let result = test_eval("hash([1])".to_string());
+        assert_eq!(result.to_error_kind(), Some(ErrorKind::TypeError));
+        assert_eq!(test_eval("hash(fn(x) { x })".to_string()).to_error_kind(),
+                   Some(ErrorKind::TypeError));
+    }
+
+    #[test]
+    fn it_should_round_trip_files() {
+        let file = std::env::temp_dir()
+            .join(format!("monkey-read-write-{}.txt", std::process::id()));
+        let path = Object::new_string(file.to_string_lossy().into_owned()).to_source().unwrap();
+
+        let input = format!("write_file({}, \"a\\nb\"); read_file({})", path, path);
+        assert_eq!(test_eval(input), Object::new_string("a\nb".to_string()));
+        let input = format!("write_file({}, \"\")", path);
+        assert_eq!(test_eval(input), NULL);
+
+        let sandboxed = EvalOptions { allow_file_access: false, ..EvalOptions::new() };
+        let result = test_eval_with_options(format!("read_file({})", path), sandboxed.clone());
+        assert_eq!(result.to_error_kind(), Some(ErrorKind::LimitError));
+        assert_eq!(result.to_error_message(),
+                   Some("read_file is disabled: file access is not allowed".to_string()));
+        let result = test_eval_with_options(format!("write_file({}, \"x\")", path), sandboxed);
+        assert_eq!(result.to_error_kind(), Some(ErrorKind::LimitError));
+        assert_eq!(test_eval(format!("read_file({})", path)), Object::new_string("".to_string()));
+        std::fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn it_should_read_environment_variables() {
+        std::env::set_var("MONKEY_GETENV_TEST", "from env");
+        std::env::remove_var("MONKEY_GETENV_UNSET");
+        assert_eq!(test_eval("getenv(\"MONKEY_GETENV_TEST\")".to_string()),
+                   Object::new_string("from env".to_string()));
+        assert_eq!(test_eval("getenv(\"MONKEY_GETENV_UNSET\")".to_string()), NULL);
+        assert_eq!(test_eval("getenv(1)".to_string()).to_error_kind(),
+                   Some(ErrorKind::TypeError));
+
+        let sandboxed = EvalOptions { allow_env_access: false, ..EvalOptions::new() };
+        let result = test_eval_with_options("getenv(\"MONKEY_GETENV_TEST\")".to_string(),
+                                            sandboxed);
+        assert_eq!(result.to_error_kind(), Some(ErrorKind::LimitError));
+        assert_eq!(result.to_error_message(),
+                   Some("getenv is disabled: environment access is not allowed".to_string()));
+    }
+
+    #[test]
+    fn it_should_return_current_time_fields() {
+        let fixed = EvalOptions {
+            fixed_time: Some(UNIX_EPOCH + std::time::Duration::from_millis(1717245296789)),
+            ..EvalOptions::new()
+        };
+        let input = "let t = now(); [t[\"year\"], t[\"month\"], t[\"day\"], t[\"hour\"], \
+                     t[\"minute\"], t[\"second\"]]";
+        assert_eq!(test_eval_with_options(input.to_string(), fixed).to_source(),
+                   Some("[2024, 6, 1, 12, 34, 56]".to_string()));
+
+        let year = test_eval("now()[\"year\"]".to_string()).to_int().unwrap();
+        assert!(year >= 2024);
+        assert_eq!(test_eval("now(1)".to_string()).to_error_kind(),
+                   Some(ErrorKind::ArgumentError));
+    }
+
+    #[test]
+    fn it_should_report_file_errors() {
+        let path = std::env::temp_dir().join("monkey-missing-dir").join("missing.txt");
+        let path = path.to_string_lossy().into_owned();
+        let input = format!("read_file({})", Object::new_string(path.clone()).to_source().unwrap());
+        let result = test_eval(input);
+        assert_eq!(result.to_error_kind(), Some(ErrorKind::IOError));
+        assert!(result.to_error_message().unwrap().starts_with(&format!("{}: ", path)));
+        assert_eq!(test_eval("error_kind(read_file(\"/nonexistent/x\"))".to_string()),
+                   Object::new_string("IOError".to_string()));
+
+        let input = format!("write_file({}, \"x\")",
+                            Object::new_string(path).to_source().unwrap());
+        assert_eq!(test_eval(input).to_error_kind(), Some(ErrorKind::IOError));
+        assert_eq!(test_eval("read_file(1)".to_string()).to_error_kind(),
+                   Some(ErrorKind::TypeError));
+    }
+
+    #[test]
+    fn it_should_evaluate_with_seeded_enviroment() {
+        let mut globals = HashMap::new();
+        globals.insert("name".to_string(), Object::new_string("monkey".to_string()));
+        globals.insert("pi".to_string(), Object::new_int(3));
+        let mut env = Enviroment::from_hashmap(globals);
+
+        let l = lexer::Lexer::new("len(name) * pi".to_string());
+        let program = parser::Parser::new(l).parse_program();
+        let result = eval(program.to_enum().to_ast(), &mut env);
+        assert_eq!(result.to_int().unwrap(), 18);
+
+        let l = lexer::Lexer::new("\"hello \" + name".to_string());
+        let program = parser::Parser::new(l).parse_program();
+        let result = eval(program.to_enum().to_ast(), &mut env);
+        assert_eq!(result.to_string().unwrap(), "hello monkey");
+    }
+
+    #[test]
+    fn it_should_put_each_argument_on_its_own_line() {
+        let expects = [("puts()", vec![]),
+                       ("puts(1)", vec!["1"]),
+                       ("puts(1, \"a\", true)", vec!["1", "a", "true"])];
+        for expect in expects.iter() {
+            let (result, lines) = output::capture(|| test_eval(expect.0.to_string()));
+            assert_eq!(result, NULL);
+            assert_eq!(lines, expect.1);
+        }
+
+        let (_, lines) = output::capture(|| test_eval("puts([1, 2], [])".to_string()));
+        assert_eq!(lines, vec![Object::new_array(vec![Object::new_int(1), Object::new_int(2)])
+                                   .inspect(),
+                               Object::new_array(vec![]).inspect()]);
+    }
+
+    #[test]
+    fn it_should_notify_watcher_on_rebinding() {
+        let expects = [("let x = \"a\"; watch(\"x\", fn(old, new) { puts(old, new) }); \
+                         let x = \"b\"; let x = \"c\";",
+                        vec!["a", "b", "b", "c"]),
+                       ("watch(\"x\", fn(old, new) { puts(old, new) }); let x = \"a\";", vec![]),
+                       ("let x = 1; watch(\"x\", fn(old, new) { puts(old, new) }); \
+                         unwatch(\"x\"); let x = 2;",
+                        vec![]),
+                       ("let x = 1; watch(\"x\", fn(old, new) { puts(old + new) }); \
+                         let f = fn() { let x = 5; }; f();",
+                        vec![])];
+        for expect in expects.iter() {
+            let (result, lines) = output::capture(|| test_eval(expect.0.to_string()));
+            assert!(!is_error(&result), "{}", expect.0);
+            assert_eq!(lines, expect.1);
+        }
+
+        let (result, lines) = output::capture(|| {
+            test_eval("let x = 1; watch(\"x\", fn(old, new) { puts(new); new * 10 }); \
+                       let x = 2;"
+                .to_string())
+        });
+        assert_eq!(result.to_int(), Some(2));
+        assert_eq!(lines, vec!["2"]);
+
+        assert_eq!(test_eval("watch(1, len)".to_string()).to_error_message(),
+                   Some("argument to \"watch\" not supported. got Integer(1), BuildIn(Len(Len))"
+                            .to_string()));
+    }
+
+    #[test]
+    fn it_should_freeze_containers() {
+        let result = test_eval("freeze([1, 2, 3])".to_string());
+        match result.object_type {
+            ObjectType::Array(ref x) => assert!(x.frozen),
+            _ => assert!(false),
+        }
+        assert_eq!(result, test_eval("[1, 2, 3]".to_string()));
+        assert_eq!(result.inspect(), test_eval("[1, 2, 3]".to_string()).inspect());
+        assert!(!test_eval("freeze({})".to_string()).inspect().contains("frozen"));
+
+        let result = test_eval("let h = {\"a\": 1}; freeze(h); h".to_string());
+        match result.object_type {
+            ObjectType::HashType(ref x) => assert!(!x.frozen),
+            _ => assert!(false),
+        }
+
+        let result = test_eval("freeze(1)".to_string());
+        assert_eq!(result.to_error_message().unwrap(),
+                   "argument to \"freeze\" not supported. got Integer(1)");
+    }
+
+    #[test]
+    fn it_should_reject_assignment_into_frozen_containers() {
+        let frozen = ["let a = freeze([1]); a[0] = 2;",
+                      "let h = freeze({\"a\": 1}); h[\"a\"] = 2;",
+                      "let h = freeze({\"a\": 1}); h[\"b\"] = 2;"];
+        for input in frozen.iter() {
+            let result = test_eval(input.to_string());
+            assert_eq!(result.to_error_kind(), Some(ErrorKind::TypeError));
+            assert_eq!(result.to_error_message(),
+                       Some("cannot mutate frozen value".to_string()));
+        }
+
+        let expects = [("let a = [1, 2]; a[0] = 9; a", "[9, 2]"),
+                       ("let h = {\"a\": 1}; h[\"a\"] = 2; h[\"a\"]", "2"),
+                       ("let h = {}; h[true] = 3; h[true]", "3")];
+        for expect in expects.iter() {
+            assert_eq!(test_eval(expect.0.to_string()).to_source().unwrap(), expect.1);
+        }
+        assert_eq!(test_eval("let a = [1]; a[1] = 2;".to_string()).to_error_message(),
+                   Some("index out of range: max=0 got=1".to_string()));
+        assert_eq!(test_eval("let h = {}; h[fn() {}] = 1;".to_string()).to_error_kind(),
+                   Some(ErrorKind::TypeError));
+    }
+
+    #[test]
+    fn it_should_deep_freeze_containers() {
+        let frozen = ["let a = freeze([[1]]); let b = a[0]; b[0] = 2;",
+                      "let h = freeze({\"k\": {\"x\": 1}}); let i = h[\"k\"]; i[\"x\"] = 2;",
+                      "let a = freeze([{\"k\": [1]}]); let b = a[0][\"k\"]; b[0] = 2;"];
+        for input in frozen.iter() {
+            assert_eq!(test_eval(input.to_string()).to_error_message(),
+                       Some("cannot mutate frozen value".to_string()),
+                       "{}",
+                       input);
+        }
+
+        let input = "let inner = [1]; let a = freeze([inner]); inner[0] = 2; [inner, a]";
+        assert_eq!(test_eval(input.to_string()).to_source().unwrap(), "[[2], [[1]]]");
+    }
+
+    #[test]
+    fn it_should_copy_shared_containers_on_write() {
+        let expects = [("let a = [1, 2]; let b = a; b[0] = 9; [a, b]", "[[1, 2], [9, 2]]"),
+                       ("let h = {\"k\": 1}; let i = h; i[\"k\"] = 2; [h[\"k\"], i[\"k\"]]",
+                        "[1, 2]"),
+                       ("let a = [1]; let f = fn(xs) { xs[0] = 3; xs }; [f(a), a]", "[[3], [1]]"),
+                       ("let a = [1]; let f = fn() { a }; a[0] = 2; [f(), a]", "[[2], [2]]"),
+                       ("let a = [1]; let f = fn() { a[0] = 3; a }; [f(), a]", "[[3], [3]]")];
+        for expect in expects.iter() {
+            assert_eq!(test_eval(expect.0.to_string()).to_source().unwrap(),
+                       expect.1,
+                       "{}",
+                       expect.0);
+        }
+
+        let storage = |env: &Enviroment, name: &str| match env.get(&name.to_string()) {
+            Some(Object { object_type: ObjectType::Array(ref x) }) => Rc::as_ptr(&x.elements),
+            x => panic!("expected an array, got {:?}", x),
+        };
+        let mut env = Enviroment::new();
+        test_eval_in("let a = [1, 2, 3];".to_string(), &mut env);
+        let unshared = storage(&env, "a");
+        test_eval_in("a[0] = 9;".to_string(), &mut env);
+        assert_eq!(storage(&env, "a"), unshared);
+
+        test_eval_in("let b = a; b[1] = 8;".to_string(), &mut env);
+        assert_eq!(storage(&env, "a"), unshared);
+        assert!(storage(&env, "b") != unshared);
+        assert_eq!(test_eval_in("[a, b]".to_string(), &mut env).to_source().unwrap(),
+                   "[[9, 2, 3], [9, 8, 3]]");
+
+        let result = test_eval_in("a[5] = 1;".to_string(), &mut env);
+        assert_eq!(result.to_error_kind(), Some(ErrorKind::IndexError));
+        assert_eq!(storage(&env, "a"), unshared);
+    }
+}
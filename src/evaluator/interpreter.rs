@@ -0,0 +1,213 @@
+use parser::ast::{Node, Program, Statements, Expressions, BlockStatement, WhileExpression};
+use evaluator::evaluator::{eval, NULL};
+use evaluator::object::{Object, ObjectType, Enviroment};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum StepResult {
+    // The value of the node that was just executed.
+    Continue(Object),
+    // The value of the whole program, or the error that stopped it.
+    Done(Object),
+}
+
+#[derive(Debug, Clone)]
+enum Frame {
+    Block { statements: Vec<Statements>, position: usize },
+    While { expression: WhileExpression },
+}
+
+// A resumable alternative to `eval` for a program. Each step runs one top-level statement,
+// one statement of an `if` or `while` block, or one condition of an `if` or `while`
+// statement. Anything nested deeper, such as a function call, runs in a single step.
+pub struct Interpreter {
+    env: Enviroment,
+    frames: Vec<Frame>,
+    result: Object,
+    finished: Option<Object>,
+}
+
+impl Interpreter {
+    pub fn new(program: Program, env: Enviroment) -> Self {
+        Interpreter {
+            env,
+            frames: vec![Frame::Block {
+                             statements: program.statements,
+                             position: 0,
+                         }],
+            result: NULL,
+            finished: None,
+        }
+    }
+
+    pub fn env(&self) -> &Enviroment {
+        &self.env
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.finished.is_some()
+    }
+
+    pub fn step(&mut self) -> StepResult {
+        if let Some(ref x) = self.finished {
+            return StepResult::Done(x.clone());
+        }
+
+        loop {
+            let statement = match self.frames.last_mut() {
+                Some(&mut Frame::Block { ref statements, ref mut position }) => {
+                    *position += 1;
+                    statements.get(*position - 1).cloned()
+                }
+                Some(&mut Frame::While { ref expression }) => {
+                    let expression = expression.clone();
+                    return self.step_while_condition(&expression);
+                }
+                None => return self.finish(self.result.clone()),
+            };
+            match statement {
+                Some(x) => return self.step_statement(&x),
+                None => {
+                    self.frames.pop();
+                }
+            }
+        }
+    }
+
+    pub fn run(&mut self) -> Object {
+        loop {
+            if let StepResult::Done(x) = self.step() {
+                return x;
+            }
+        }
+    }
+
+    fn step_statement(&mut self, statement: &Statements) -> StepResult {
+        if let Statements::ExpressionStatement(ref x) = *statement {
+            match x.expression {
+                Expressions::IfExpression(ref x) => {
+                    let condition = eval(x.condition.to_ast(), &mut self.env);
+                    if let ObjectType::Error(_, _, _) = condition.object_type {
+                        return self.finish(condition);
+                    }
+                    match (condition.is_truthy(), x.alternative.as_ref()) {
+                        (true, _) => self.push_block(x.consequence.clone()),
+                        (false, Some(y)) => self.push_block(y.clone()),
+                        (false, None) => self.result = NULL,
+                    }
+                    return StepResult::Continue(condition);
+                }
+                Expressions::WhileExpression(ref x) => {
+                    self.result = NULL;
+                    self.frames.push(Frame::While { expression: x.clone() });
+                    return self.step();
+                }
+                _ => {}
+            }
+        }
+
+        let result = eval(statement.to_ast(), &mut self.env);
+        match result.object_type {
+            ObjectType::Return(x) => self.finish(*x),
+            ObjectType::Error(_, _, _) => self.finish(result),
+            _ => {
+                self.result = result.clone();
+                StepResult::Continue(result)
+            }
+        }
+    }
+
+    fn step_while_condition(&mut self, x: &WhileExpression) -> StepResult {
+        let condition = eval(x.condition.to_ast(), &mut self.env);
+        if let ObjectType::Error(_, _, _) = condition.object_type {
+            return self.finish(condition);
+        }
+        if condition.is_truthy() {
+            self.push_block(x.body.clone());
+        } else {
+            self.frames.pop();
+        }
+        StepResult::Continue(condition)
+    }
+
+    fn push_block(&mut self, x: BlockStatement) {
+        self.result = NULL;
+        self.frames.push(Frame::Block {
+                             statements: x.statements,
+                             position: 0,
+                         });
+    }
+
+    fn finish(&mut self, x: Object) -> StepResult {
+        self.frames.clear();
+        self.finished = Some(x.clone());
+        StepResult::Done(x)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lexer::lexer;
+    use parser::parser;
+    use evaluator::evaluator::{TRUE, FALSE};
+
+    fn new_interpreter(input: &str) -> Interpreter {
+        let mut p = parser::Parser::new(lexer::Lexer::new(input.to_string()));
+        let program = p.parse_program();
+        Interpreter::new(program, Enviroment::new())
+    }
+
+    #[test]
+    fn it_should_step_through_statements() {
+        let mut interpreter = new_interpreter("let a = 1; let b = a + 1; a + b");
+
+        assert_eq!(interpreter.step(), StepResult::Continue(Object::new_int(1)));
+        assert_eq!(interpreter.env().get(&"a".to_string()), Some(Object::new_int(1)));
+        assert_eq!(interpreter.env().get(&"b".to_string()), None);
+
+        assert_eq!(interpreter.step(), StepResult::Continue(Object::new_int(2)));
+        assert_eq!(interpreter.env().get(&"b".to_string()), Some(Object::new_int(2)));
+
+        assert_eq!(interpreter.step(), StepResult::Continue(Object::new_int(3)));
+        assert!(!interpreter.is_done());
+        assert_eq!(interpreter.step(), StepResult::Done(Object::new_int(3)));
+        assert!(interpreter.is_done());
+        assert_eq!(interpreter.step(), StepResult::Done(Object::new_int(3)));
+    }
+
+    #[test]
+    fn it_should_step_into_if_and_while_blocks() {
+        let mut interpreter = new_interpreter("let i = 0; while (i < 2) { let i = i + 1; }; i");
+        let steps = (0..7).map(|_| interpreter.step()).collect::<Vec<_>>();
+        assert_eq!(steps,
+                   vec![StepResult::Continue(Object::new_int(0)),
+                        StepResult::Continue(TRUE),
+                        StepResult::Continue(Object::new_int(1)),
+                        StepResult::Continue(TRUE),
+                        StepResult::Continue(Object::new_int(2)),
+                        StepResult::Continue(FALSE),
+                        StepResult::Continue(Object::new_int(2))]);
+
+        let mut interpreter = new_interpreter("if (1 < 2) { 10 } else { 20 }");
+        assert_eq!(interpreter.step(), StepResult::Continue(TRUE));
+        assert_eq!(interpreter.step(), StepResult::Continue(Object::new_int(10)));
+        assert_eq!(interpreter.step(), StepResult::Done(Object::new_int(10)));
+    }
+
+    #[test]
+    fn it_should_run_like_eval() {
+        let expects = [("let i = 0; while (i < 5) { let i = i + 1; }", Object::new_int(5)),
+                       ("if (false) { 1 }", NULL),
+                       ("while (true) { return 7; }; 1", Object::new_int(7)),
+                       ("let x = 1; if (x) { return x + 1; }; 3", Object::new_int(2))];
+        for expect in expects.iter() {
+            assert_eq!(new_interpreter(expect.0).run(), expect.1, "{}", expect.0);
+        }
+
+        let mut interpreter = new_interpreter("let x = 1; if (x) { x + true }; 2");
+        assert_eq!(interpreter.run().to_error_message(),
+                   Some("type mismatch: Integer(1) + Boolean(true), at line: 1, column: 23"
+                            .to_string()));
+        assert!(interpreter.is_done());
+    }
+}
@@ -1,2 +1,5 @@
 pub mod evaluator;
-pub mod object;
\ No newline at end of file
+pub mod object;
+pub mod options;
+pub mod interpreter;
+pub mod prelude;
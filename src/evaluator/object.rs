@@ -1,8 +1,19 @@
-use std::fmt::{Display, Formatter, Result};
-use std::collections::HashMap;
+use std::cell::RefCell;
+use std::cmp;
+use std::fmt::{Debug, Display, Formatter, Result};
+use std::collections::{BTreeMap, HashMap};
+use std::mem;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
-use parser::ast::{Identifier, BlockStatement};
+use serde_json::{Value, Map, Number};
+
+use lexer::token::Token;
+use parser::ast::{Identifier, BlockStatement, Resolved, function_source};
 use buildin::BuildIn;
+use evaluator::options::EvalOptions;
+use evaluator::prelude;
+use utils::quote_string;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Null;
@@ -13,21 +24,156 @@ impl Display for Null {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+static NEXT_FUNCTION_ID: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Clone)]
 pub struct Function {
+    pub id: usize,
     pub parameters: Vec<Identifier>,
     pub body: BlockStatement,
     pub env: Enviroment,
+    pub group: Vec<RecFunction>,
+}
+
+// The captured enviroment is left out: it can hold the function itself, once a captured
+// binding is reassigned to it.
+impl Debug for Function {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        f.debug_struct("Function")
+            .field("id", &self.id)
+            .field("parameters", &self.parameters)
+            .field("body", &self.body)
+            .field("group", &self.group)
+            .finish()
+    }
 }
 
+// One member of a `let rec` group. Calling any member rebinds every name of its group
+// inside the call, which is how the members see each other.
 #[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecFunction {
+    pub id: usize,
+    pub name: String,
+    pub parameters: Vec<Identifier>,
+    pub body: BlockStatement,
+}
+
+impl PartialEq for Function {
+    fn eq(&self, other: &Function) -> bool {
+        self.id == other.id
+    }
+}
+
+// A host function registered with `Enviroment::register_native`. Natives are equal only
+// when they share the same closure.
+#[derive(Clone)]
+pub struct NativeFunction {
+    pub name: String,
+    function: Rc<dyn Fn(Vec<Object>) -> Object>,
+}
+
+impl NativeFunction {
+    pub fn new<F>(name: &str, f: F) -> Self
+        where F: Fn(Vec<Object>) -> Object + 'static
+    {
+        NativeFunction {
+            name: name.to_string(),
+            function: Rc::new(f),
+        }
+    }
+
+    pub fn call(&self, args: Vec<Object>) -> Object {
+        (self.function)(args)
+    }
+}
+
+impl Debug for NativeFunction {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        write!(f, "NativeFunction {{ name: {:?} }}", self.name)
+    }
+}
+
+impl PartialEq for NativeFunction {
+    fn eq(&self, other: &NativeFunction) -> bool {
+        self.name == other.name && Rc::ptr_eq(&self.function, &other.function)
+    }
+}
+
+impl Eq for NativeFunction {}
+
+// A string buffer created by `builder()`. Copies of the object share the buffer, so
+// `build_push` appends in place instead of copying the string built so far.
+#[derive(Clone)]
+pub struct StringBuilder {
+    buffer: Rc<RefCell<String>>,
+}
+
+impl StringBuilder {
+    pub fn new() -> Self {
+        StringBuilder { buffer: Rc::new(RefCell::new(String::new())) }
+    }
+
+    pub fn push(&self, x: &str) {
+        self.buffer.borrow_mut().push_str(x);
+    }
+
+    pub fn build(&self) -> String {
+        self.buffer.borrow().clone()
+    }
+
+    pub fn len(&self) -> usize {
+        self.buffer.borrow().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buffer.borrow().is_empty()
+    }
+}
+
+impl Default for StringBuilder {
+    fn default() -> Self {
+        StringBuilder::new()
+    }
+}
+
+impl Debug for StringBuilder {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        write!(f, "StringBuilder {{ buffer: {:?} }}", self.buffer.borrow())
+    }
+}
+
+impl PartialEq for StringBuilder {
+    fn eq(&self, other: &StringBuilder) -> bool {
+        Rc::ptr_eq(&self.buffer, &other.buffer)
+    }
+}
+
+impl Eq for StringBuilder {}
+
+#[derive(Clone)]
 pub struct Array {
-    pub elements: Vec<Object>,
+    pub elements: Rc<Vec<Object>>,
+    pub frozen: bool,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+// Containers share their storage until one holder mutates it, and comparing two holders of
+// the same storage skips the element-wise walk.
+// `frozen` is left out, so printed values look the same whether or not they are frozen.
+impl Debug for Array {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        f.debug_struct("Array").field("elements", &self.elements).finish()
+    }
+}
+
+impl PartialEq for Array {
+    fn eq(&self, other: &Array) -> bool {
+        Rc::ptr_eq(&self.elements, &other.elements) || self.elements == other.elements
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum HashKey {
-    Integer(i32),
+    Integer(i64),
     StringType(String),
     Boolean(bool),
 }
@@ -41,69 +187,399 @@ impl HashKey {
             _ => None,
         }
     }
+
+    pub fn to_object(&self) -> Object {
+        match *self {
+            HashKey::Integer(x) => Object::new_int(x),
+            HashKey::StringType(ref x) => Object::new_string(x.clone()),
+            HashKey::Boolean(x) => Object { object_type: ObjectType::Boolean(x) },
+        }
+    }
+
+    // FNV-1a over a type tag and the value's bytes, folded to 32 bits. Unlike the std
+    // hashers it is fixed, so the result is the same across runs and builds.
+    pub fn stable_hash(&self) -> i32 {
+        let (tag, bytes) = match *self {
+            HashKey::Integer(x) => (0u8, x.to_le_bytes().to_vec()),
+            HashKey::StringType(ref x) => (1u8, x.as_bytes().to_vec()),
+            HashKey::Boolean(x) => (2u8, vec![x as u8]),
+        };
+        let mut hash: u64 = 0xcbf29ce484222325;
+        for b in Some(tag).into_iter().chain(bytes) {
+            hash ^= b as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+        ((hash >> 32) ^ hash) as u32 as i32
+    }
+
+    pub fn to_source(&self) -> String {
+        match *self {
+            HashKey::Integer(ref x) => format!("{}", x),
+            HashKey::StringType(ref x) => quote_string(x),
+            HashKey::Boolean(ref x) => format!("{}", x),
+        }
+    }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Clone)]
 pub struct HashType {
-    pub pairs: HashMap<HashKey, Object>,
+    pub pairs: Rc<HashMap<HashKey, Object>>,
+    pub frozen: bool,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+impl Debug for HashType {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        f.debug_struct("HashType").field("pairs", &self.pairs).finish()
+    }
+}
+
+impl PartialEq for HashType {
+    fn eq(&self, other: &HashType) -> bool {
+        Rc::ptr_eq(&self.pairs, &other.pairs) || self.pairs == other.pairs
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    TypeError,
+    NameError,
+    ArgumentError,
+    IndexError,
+    DivByZero,
+    OverflowError,
+    AssertionError,
+    LimitError,
+    IOError,
+}
+
+impl ErrorKind {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            ErrorKind::TypeError => "TypeError",
+            ErrorKind::NameError => "NameError",
+            ErrorKind::ArgumentError => "ArgumentError",
+            ErrorKind::IndexError => "IndexError",
+            ErrorKind::DivByZero => "DivByZero",
+            ErrorKind::OverflowError => "OverflowError",
+            ErrorKind::AssertionError => "AssertionError",
+            ErrorKind::LimitError => "LimitError",
+            ErrorKind::IOError => "IOError",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub enum ObjectType {
-    Integer(i32),
+    Integer(i64),
+    Float(f64),
     StringType(String),
     Boolean(bool),
     Null(Null),
     Return(Box<Object>),
-    Error(String),
-    Function(Function),
+    Error(ErrorKind, String, Option<Box<Object>>),
+    Function(Box<Function>),
     BuildIn(BuildIn),
+    Native(NativeFunction),
+    Builder(StringBuilder),
     Array(Array),
     HashType(HashType),
 }
 
 impl ObjectType {
     pub fn to_type(&self) -> i32 {
-        match self {
-            &ObjectType::Integer(_) => 0,
-            &ObjectType::Boolean(_) => 1,
-            &ObjectType::Null(_) => 2,
-            &ObjectType::Return(_) => 3,
-            &ObjectType::Error(_) => 4,
-            &ObjectType::Function(_) => 5,
-            &ObjectType::StringType(_) => 6,
-            &ObjectType::BuildIn(_) => 7,
-            &ObjectType::Array(_) => 8,
-            &ObjectType::HashType(_) => 9,
+        match *self {
+            ObjectType::Integer(_) => 0,
+            ObjectType::Boolean(_) => 1,
+            ObjectType::Null(_) => 2,
+            ObjectType::Return(_) => 3,
+            ObjectType::Error(_, _, _) => 4,
+            ObjectType::Function(_) => 5,
+            ObjectType::StringType(_) => 6,
+            ObjectType::BuildIn(_) => 7,
+            ObjectType::Array(_) => 8,
+            ObjectType::HashType(_) => 9,
+            ObjectType::Float(_) => 10,
+            ObjectType::Native(_) => 11,
+            ObjectType::Builder(_) => 12,
         }
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+// Unlike IEEE equality, NaN equals NaN, so `x == x` holds for every float value.
+pub fn float_eq(x: f64, y: f64) -> bool {
+    x == y || (x.is_nan() && y.is_nan())
+}
+
+// Display never uses an exponent, so adding `.0` to whole numbers is enough to give a
+// float literal. Infinities and NaN have no literal.
+fn float_source(x: f64) -> Option<String> {
+    if !x.is_finite() {
+        return None;
+    }
+    let s = format!("{}", x);
+    match s.contains('.') {
+        true => Some(s),
+        false => Some(format!("{}.0", s)),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct Object {
     pub object_type: ObjectType,
 }
 
+// Integers, strings and booleans order among their own type and numbers order with each
+// other; any other pair is only comparable when it is equal. NaN is not comparable.
+impl PartialOrd for Object {
+    fn partial_cmp(&self, other: &Object) -> Option<cmp::Ordering> {
+        match (&self.object_type, &other.object_type) {
+            (ObjectType::Integer(x), ObjectType::Integer(y)) => x.partial_cmp(y),
+            (ObjectType::Float(x), ObjectType::Float(y)) => x.partial_cmp(y),
+            (ObjectType::Integer(x), ObjectType::Float(y)) => (*x as f64).partial_cmp(y),
+            (ObjectType::Float(x), ObjectType::Integer(y)) => x.partial_cmp(&(*y as f64)),
+            (ObjectType::StringType(x), ObjectType::StringType(y)) => x.partial_cmp(y),
+            (ObjectType::Boolean(x), ObjectType::Boolean(y)) => x.partial_cmp(y),
+            _ if self == other => Some(cmp::Ordering::Equal),
+            _ => None,
+        }
+    }
+}
+
 impl Object {
     pub fn inspect(&self) -> String {
         match self.object_type {
             ObjectType::Integer(ref x) => format!("{}", x),
+            ObjectType::Float(x) => float_source(x).unwrap_or(format!("{}", x)),
             ObjectType::StringType(ref x) => x.clone(),
             ObjectType::Boolean(ref x) => format!("{}", x),
             ObjectType::Null(ref x) => format!("{}", x),
             ObjectType::Return(ref x) => format!("{:?}", x),
-            ObjectType::Error(ref x) => format!("Error: {}", x),
+            ObjectType::Error(_, ref x, _) => format!("Error: {}", x),
             ObjectType::Function(ref x) => format!("Function: {:?}", x),
             ObjectType::BuildIn(ref x) => format!("BuildIn: {:?}", x),
+            ObjectType::Native(ref x) => format!("Native: {:?}", x),
+            ObjectType::Builder(ref x) => format!("Builder: {:?}", x),
             ObjectType::Array(ref x) => format!("Array: {:?}", x),
             ObjectType::HashType(ref x) => format!("HashType: {:?}", x),
         }
     }
 
-    pub fn new_i32(x: i32) -> Self {
+    pub fn inspect_with(&self, options: &EvalOptions) -> String {
+        match self.object_type {
+            ObjectType::Array(_) |
+            ObjectType::HashType(_) if options.stable_hash_order => {
+                match self.source_with(true) {
+                    Some(s) => s,
+                    None => self.inspect(),
+                }
+            }
+            _ => self.inspect(),
+        }
+    }
+
+    pub fn to_source(&self) -> Option<String> {
+        self.source_with(false)
+    }
+
+    fn source_with(&self, sorted: bool) -> Option<String> {
+        match self.object_type {
+            ObjectType::Integer(ref x) => Some(format!("{}", x)),
+            ObjectType::Float(x) => float_source(x),
+            ObjectType::StringType(ref x) => Some(quote_string(x)),
+            ObjectType::Boolean(ref x) => Some(format!("{}", x)),
+            ObjectType::Function(ref x) => Some(function_source(&x.parameters, &x.body)),
+            ObjectType::BuildIn(ref x) => Some(x.name().to_string()),
+            ObjectType::Native(ref x) => Some(x.name.clone()),
+            ObjectType::Array(ref x) => {
+                let mut elements: Vec<String> = vec![];
+                for element in x.elements.iter() {
+                    elements.push(element.source_with(sorted)?);
+                }
+                Some(format!("[{}]", elements.join(", ")))
+            }
+            ObjectType::HashType(ref x) => {
+                let mut keys = x.pairs.keys().collect::<Vec<_>>();
+                if sorted {
+                    keys.sort();
+                }
+                let mut pairs: Vec<String> = vec![];
+                for key in keys {
+                    let value = x.pairs[key].source_with(sorted)?;
+                    pairs.push(format!("{}: {}", key.to_object().source_with(sorted)?, value));
+                }
+                Some(format!("{{{}}}", pairs.join(", ")))
+            }
+            ObjectType::Null(_) |
+            ObjectType::Return(_) |
+            ObjectType::Error(_, _, _) |
+            ObjectType::Builder(_) => None,
+        }
+    }
+
+    pub fn to_serde_value(&self) -> Value {
+        match self.object_type {
+            ObjectType::Integer(ref x) => Value::Number(Number::from(*x)),
+            ObjectType::Float(x) => Number::from_f64(x).map_or(Value::Null, Value::Number),
+            ObjectType::StringType(ref x) => Value::String(x.clone()),
+            ObjectType::Boolean(ref x) => Value::Bool(*x),
+            ObjectType::Return(ref x) => x.to_serde_value(),
+            ObjectType::Array(ref x) => {
+                Value::Array(x.elements.iter().map(|e| e.to_serde_value()).collect())
+            }
+            ObjectType::HashType(ref x) => {
+                let mut map = Map::new();
+                for (key, value) in x.pairs.iter() {
+                    let key = match *key {
+                        HashKey::StringType(ref k) => k.clone(),
+                        ref k => k.to_source(),
+                    };
+                    map.insert(key, value.to_serde_value());
+                }
+                Value::Object(map)
+            }
+            ObjectType::Null(_) |
+            ObjectType::Error(_, _, _) |
+            ObjectType::Function(_) |
+            ObjectType::BuildIn(_) |
+            ObjectType::Native(_) |
+            ObjectType::Builder(_) => Value::Null,
+        }
+    }
+
+    pub fn from_serde_value(value: &Value) -> Self {
+        match *value {
+            Value::Null => Object { object_type: ObjectType::Null(Null) },
+            Value::Bool(ref x) => Object { object_type: ObjectType::Boolean(*x) },
+            Value::Number(ref x) => {
+                match x.as_i64() {
+                    Some(n) => Object::new_int(n),
+                    _ if x.is_i64() || x.is_u64() => {
+                        Object::new_error(ErrorKind::OverflowError,
+                                          format!("integer out of range: {}", x))
+                    }
+                    _ => {
+                        match x.as_f64() {
+                            Some(f) => Object::new_f64(f),
+                            None => {
+                                Object::new_error(ErrorKind::TypeError,
+                                                  format!("unsupported number: {}", x))
+                            }
+                        }
+                    }
+                }
+            }
+            Value::String(ref x) => Object::new_string(x.clone()),
+            Value::Array(ref x) => {
+                Object::new_array(x.iter().map(Object::from_serde_value).collect())
+            }
+            Value::Object(ref x) => {
+                let mut pairs = HashMap::new();
+                for (key, value) in x.iter() {
+                    pairs.insert(HashKey::StringType(key.clone()), Object::from_serde_value(value));
+                }
+                Object::new_hash(pairs)
+            }
+        }
+    }
+
+    pub fn pretty(&self, indent: usize) -> String {
+        self.pretty_with_depth(indent, 0)
+    }
+
+    fn pretty_with_depth(&self, indent: usize, depth: usize) -> String {
+        let outer = " ".repeat(indent * depth);
+        let inner = " ".repeat(indent * (depth + 1));
+        match self.object_type {
+            ObjectType::Array(ref x) => {
+                if x.elements.is_empty() {
+                    return "[]".to_string();
+                }
+                let elements = x.elements
+                    .iter()
+                    .map(|e| format!("{}{}", inner, e.pretty_with_depth(indent, depth + 1)))
+                    .collect::<Vec<_>>()
+                    .join(",\n");
+                format!("[\n{}\n{}]", elements, outer)
+            }
+            ObjectType::HashType(ref x) => {
+                if x.pairs.is_empty() {
+                    return "{}".to_string();
+                }
+                let mut keys = x.pairs.keys().collect::<Vec<_>>();
+                keys.sort();
+                let pairs = keys.iter()
+                    .map(|k| {
+                             format!("{}{}: {}",
+                                     inner,
+                                     k.to_source(),
+                                     x.pairs[*k].pretty_with_depth(indent, depth + 1))
+                         })
+                    .collect::<Vec<_>>()
+                    .join(",\n");
+                format!("{{\n{}\n{}}}", pairs, outer)
+            }
+            _ => self.to_source().unwrap_or(self.inspect()),
+        }
+    }
+
+    pub fn estimated_size(&self) -> usize {
+        let own = mem::size_of::<Object>();
+        match self.object_type {
+            ObjectType::StringType(ref x) => own + x.len(),
+            ObjectType::Builder(ref x) => own + x.len(),
+            ObjectType::Return(ref x) => own + x.estimated_size(),
+            ObjectType::Error(_, ref x, ref data) => {
+                own + x.len() + data.as_ref().map_or(0, |d| d.estimated_size())
+            }
+            ObjectType::Array(ref x) => {
+                own + x.elements.iter().map(|e| e.estimated_size()).sum::<usize>()
+            }
+            ObjectType::HashType(ref x) => {
+                own +
+                x.pairs
+                    .iter()
+                    .map(|(k, v)| mem::size_of::<HashKey>() + k.to_source().len() + v.estimated_size())
+                    .sum::<usize>()
+            }
+            _ => own,
+        }
+    }
+
+    // Only null and false are falsy; empty strings and containers count as true.
+    pub fn is_truthy(&self) -> bool {
+        match self.object_type {
+            ObjectType::Null(_) => false,
+            ObjectType::Boolean(x) => x,
+            _ => true,
+        }
+    }
+
+    pub fn deep_equal(&self, other: &Object) -> bool {
+        match (&self.object_type, &other.object_type) {
+            (ObjectType::Array(x), ObjectType::Array(y)) => {
+                x.elements.len() == y.elements.len() &&
+                x.elements.iter().zip(y.elements.iter()).all(|(a, b)| a.deep_equal(b))
+            }
+            (ObjectType::HashType(x), ObjectType::HashType(y)) => {
+                x.pairs.len() == y.pairs.len() &&
+                x.pairs.iter().all(|(k, a)| match y.pairs.get(k) {
+                    Some(b) => a.deep_equal(b),
+                    None => false,
+                })
+            }
+            (ObjectType::Float(x), ObjectType::Float(y)) => float_eq(*x, *y),
+            _ => self == other,
+        }
+    }
+
+    pub fn new_int(x: i64) -> Self {
         Object { object_type: ObjectType::Integer(x) }
     }
 
+    pub fn new_f64(x: f64) -> Self {
+        Object { object_type: ObjectType::Float(x) }
+    }
+
     pub fn new_string(x: String) -> Self {
         Object { object_type: ObjectType::StringType(x) }
     }
@@ -112,33 +588,96 @@ impl Object {
         Object { object_type: ObjectType::Return(Box::new(x)) }
     }
 
-    pub fn new_error(x: String) -> Self {
-        Object { object_type: ObjectType::Error(x) }
+    pub fn new_error(kind: ErrorKind, x: String) -> Self {
+        Object { object_type: ObjectType::Error(kind, x, None) }
+    }
+
+    // The data is returned by `error_data`, for errors that carry more than a message.
+    pub fn new_error_with_data(kind: ErrorKind, x: String, data: Object) -> Self {
+        Object { object_type: ObjectType::Error(kind, x, Some(Box::new(data))) }
+    }
+
+    // Appends the position of `token` to an error message, in the same form the parser uses.
+    // Any other object is returned unchanged.
+    pub fn with_position(self, token: &Token) -> Self {
+        match self.object_type {
+            ObjectType::Error(kind, x, data) => {
+                let x = format!("{}, at line: {}, column: {}", x, token.line_num, token.column_num);
+                Object { object_type: ObjectType::Error(kind, x, data) }
+            }
+            _ => self,
+        }
     }
 
     pub fn new_function(p: Vec<Identifier>, b: BlockStatement, e: &mut Enviroment) -> Self {
+        let id = NEXT_FUNCTION_ID.fetch_add(1, Ordering::Relaxed);
         Object {
-            object_type: ObjectType::Function(Function {
-                                                  parameters: p,
-                                                  body: b,
-                                                  env: e.clone(),
-                                              }),
+            object_type: ObjectType::Function(Box::new(Function {
+                                                           id,
+                                                           parameters: p,
+                                                           body: b,
+                                                           env: e.clone(),
+                                                           group: vec![],
+                                                       })),
+        }
+    }
+
+    pub fn new_rec_function(parameters: Vec<Identifier>,
+                            body: BlockStatement,
+                            name: String)
+                            -> RecFunction {
+        RecFunction {
+            id: NEXT_FUNCTION_ID.fetch_add(1, Ordering::Relaxed),
+            name,
+            parameters,
+            body,
+        }
+    }
+
+    pub fn from_rec_function(f: &RecFunction, group: &[RecFunction], e: &Enviroment) -> Self {
+        Object {
+            object_type: ObjectType::Function(Box::new(Function {
+                                                           id: f.id,
+                                                           parameters: f.parameters.clone(),
+                                                           body: f.body.clone(),
+                                                           env: e.clone(),
+                                                           group: group.to_vec(),
+                                                       })),
         }
     }
 
     pub fn new_array(x: Vec<Object>) -> Self {
-        Object { object_type: ObjectType::Array(Array { elements: x }) }
+        Object {
+            object_type: ObjectType::Array(Array {
+                                               elements: Rc::new(x),
+                                               frozen: false,
+                                           }),
+        }
+    }
+
+    pub fn new_hash(x: HashMap<HashKey, Object>) -> Self {
+        Object {
+            object_type: ObjectType::HashType(HashType {
+                                                  pairs: Rc::new(x),
+                                                  frozen: false,
+                                              }),
+        }
     }
 
-    #[allow(dead_code)]
-    pub fn to_i32(&self) -> Option<i32> {
+    pub fn to_int(&self) -> Option<i64> {
         match self.object_type {
             ObjectType::Integer(ref x) => Some(x.clone()),
             _ => None,
         }
     }
 
-    #[allow(dead_code)]
+    pub fn to_f64(&self) -> Option<f64> {
+        match self.object_type {
+            ObjectType::Float(x) => Some(x),
+            _ => None,
+        }
+    }
+
     pub fn to_string(&self) -> Option<String> {
         match self.object_type {
             ObjectType::StringType(ref x) => Some(x.clone()),
@@ -146,7 +685,6 @@ impl Object {
         }
     }
 
-    #[allow(dead_code)]
     pub fn to_bool(&self) -> Option<bool> {
         match self.object_type {
             ObjectType::Boolean(ref x) => Some(x.clone()),
@@ -154,25 +692,88 @@ impl Object {
         }
     }
 
-    #[allow(dead_code)]
+    pub fn to_error_kind(&self) -> Option<ErrorKind> {
+        match self.object_type {
+            ObjectType::Error(ref kind, _, _) => Some(*kind),
+            _ => None,
+        }
+    }
+
     pub fn to_error_message(&self) -> Option<String> {
         match self.object_type {
-            ObjectType::Error(ref x) => Some(x.clone()),
+            ObjectType::Error(_, ref x, _) => Some(x.clone()),
+            _ => None,
+        }
+    }
+
+    pub fn to_error_data(&self) -> Option<Object> {
+        match self.object_type {
+            ObjectType::Error(_, _, Some(ref data)) => Some(*data.clone()),
             _ => None,
         }
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
-pub struct Enviroment {
+#[derive(Debug, Clone, PartialEq)]
+pub struct EnvSnapshot {
     store: HashMap<String, Object>,
+}
+
+// Each binding lives in a shared cell. `let` always makes a new cell, while reassignment
+// writes into the existing one, so closures that captured the binding see the new value.
+type Binding = Rc<RefCell<Object>>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Enviroment {
+    store: HashMap<String, Binding>,
+    // The same cells as `store`, indexed by the slots the resolver gave the bindings of a
+    // function scope. Only function calls fill these in.
+    slots: Vec<Option<Binding>>,
+    watchers: HashMap<String, Object>,
+    options: EvalOptions,
+    depth: usize,
+    heap_bytes: usize,
+    // The prelude functions, loaded with the enviroment's own options and shared by every
+    // enclosed enviroment. They are looked up after the bindings, so scripts can shadow them.
+    prelude: Rc<HashMap<String, Object>>,
     outer: Option<Box<Enviroment>>,
 }
 
+impl Default for Enviroment {
+    fn default() -> Self {
+        Enviroment::new()
+    }
+}
+
 impl Enviroment {
     pub fn new() -> Self {
+        Enviroment::with_options(EvalOptions::new())
+    }
+
+    pub fn with_options(options: EvalOptions) -> Self {
         Enviroment {
             store: HashMap::new(),
+            slots: vec![],
+            watchers: HashMap::new(),
+            prelude: prelude::load(&options),
+            options,
+            depth: 0,
+            heap_bytes: 0,
+            outer: None,
+        }
+    }
+
+    pub fn from_hashmap(store: HashMap<String, Object>) -> Self {
+        let heap_bytes = store.values().map(|v| v.estimated_size()).sum();
+        let options = EvalOptions::new();
+        Enviroment {
+            store: store.into_iter().map(|(k, v)| (k, Rc::new(RefCell::new(v)))).collect(),
+            slots: vec![],
+            watchers: HashMap::new(),
+            prelude: prelude::load(&options),
+            options,
+            depth: 0,
+            heap_bytes,
             outer: None,
         }
     }
@@ -180,26 +781,183 @@ impl Enviroment {
     pub fn new_enclosed_enviroment(outer: Self) -> Self {
         Enviroment {
             store: HashMap::new(),
+            slots: vec![],
+            watchers: HashMap::new(),
+            options: outer.options.clone(),
+            prelude: outer.prelude.clone(),
+            depth: outer.depth + 1,
+            heap_bytes: outer.heap_bytes,
             outer: Some(Box::new(outer)),
         }
     }
 
-    pub fn get(&self, key: &String) -> Option<&Object> {
-        match self.outer {
-            Some(ref o) => {
-                match o.store.get(key) {
-                    Some(v) => Some(v),
-                    None => self.store.get(key),
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    pub fn heap_bytes(&self) -> usize {
+        self.heap_bytes
+    }
+
+    pub fn options(&self) -> &EvalOptions {
+        &self.options
+    }
+
+    pub fn get(&self, key: &String) -> Option<Object> {
+        match self.store.get(key) {
+            Some(v) => Some(v.borrow().clone()),
+            None => {
+                match self.outer {
+                    Some(ref o) => o.get(key),
+                    None => None,
                 }
             }
-            None => self.store.get(key),
         }
     }
 
+    // The binding at a resolved slot, or None while the slot is still empty.
+    pub fn get_at(&self, at: Resolved) -> Option<Object> {
+        let mut env = self;
+        for _ in 0..at.depth {
+            env = env.outer.as_ref()?;
+        }
+        let cell = env.slots.get(at.slot)?.as_ref()?;
+        Some(cell.borrow().clone())
+    }
+
+    pub fn get_prelude(&self, key: &str) -> Option<Object> {
+        self.prelude.get(key).cloned()
+    }
+
+    // Every binding visible from here, sorted by name. Inner bindings shadow outer ones.
+    pub fn bindings(&self) -> Vec<(String, Object)> {
+        let mut all: BTreeMap<String, Object> = match self.outer {
+            Some(ref o) => o.bindings().into_iter().collect(),
+            None => BTreeMap::new(),
+        };
+        all.extend(self.store.iter().map(|(k, v)| (k.clone(), v.borrow().clone())));
+        all.into_iter().collect()
+    }
+
+    // Binds `name` to a host closure. Scripts call it like any other function, with the
+    // evaluated arguments.
+    pub fn register_native<F>(&mut self, name: &str, f: F)
+        where F: Fn(Vec<Object>) -> Object + 'static
+    {
+        let native = NativeFunction::new(name, f);
+        self.set(name.to_string(), Object { object_type: ObjectType::Native(native) });
+    }
+
     pub fn set(&mut self, key: String, value: Object) -> Object {
-        self.store.insert(key, value.clone());
+        self.assign(key, value.clone());
         value
     }
+
+    // Binds `key` in this enviroment to a new cell and returns the value it shadows here.
+    pub fn assign(&mut self, key: String, value: Object) -> Option<Object> {
+        self.heap_bytes += self.accounted_size(&value);
+        let old = self.store.insert(key, Rc::new(RefCell::new(value)));
+        let old = old.map(|o| o.borrow().clone());
+        if let Some(ref o) = old {
+            self.heap_bytes -= self.accounted_size(o);
+        }
+        old
+    }
+
+    // Updates the nearest binding of `key` in the chain and returns the previous value, or
+    // None when no enviroment binds it.
+    pub fn reassign(&mut self, key: String, value: Object) -> Option<Object> {
+        let size = self.accounted_size(&value);
+        let old = match self.store.get(&key) {
+            Some(cell) => mem::replace(&mut *cell.borrow_mut(), value),
+            None => self.outer.as_mut()?.reassign(key, value)?,
+        };
+        self.heap_bytes = self.heap_bytes + size - self.accounted_size(&old);
+        Some(old)
+    }
+
+    // Like `assign`, also filling in `slot` of this enviroment.
+    pub fn assign_at(&mut self, key: String, slot: usize, value: Object) -> Option<Object> {
+        self.heap_bytes += self.accounted_size(&value);
+        let cell = Rc::new(RefCell::new(value));
+        if self.slots.len() <= slot {
+            self.slots.resize(slot + 1, None);
+        }
+        self.slots[slot] = Some(cell.clone());
+        let old = self.store.insert(key, cell).map(|o| o.borrow().clone());
+        if let Some(ref o) = old {
+            self.heap_bytes -= self.accounted_size(o);
+        }
+        old
+    }
+
+    // Like `reassign`, for a resolved binding. None while the slot is still empty.
+    pub fn reassign_at(&mut self, at: Resolved, value: Object) -> Option<Object> {
+        let size = self.accounted_size(&value);
+        let old = match at.depth {
+            0 => {
+                let cell = self.slots.get(at.slot)?.as_ref()?;
+                mem::replace(&mut *cell.borrow_mut(), value)
+            }
+            _ => {
+                let at = Resolved { depth: at.depth - 1, ..at };
+                self.outer.as_mut()?.reassign_at(at, value)?
+            }
+        };
+        self.heap_bytes = self.heap_bytes + size - self.accounted_size(&old);
+        Some(old)
+    }
+
+    // Sizing walks the whole value, so it is skipped unless there is a heap limit to check.
+    fn accounted_size(&self, x: &Object) -> usize {
+        match self.options.max_heap_bytes {
+            Some(_) => x.estimated_size(),
+            None => 0,
+        }
+    }
+
+    // Moves the value out of the nearest binding of `key`, leaving null in its place until
+    // it is reassigned.
+    pub fn take(&mut self, key: &str) -> Option<Object> {
+        self.reassign(key.to_string(), Object { object_type: ObjectType::Null(Null) })
+    }
+
+    pub fn watch(&mut self, key: String, callback: Object) {
+        self.watchers.insert(key, callback);
+    }
+
+    pub fn unwatch(&mut self, key: &String) -> bool {
+        self.watchers.remove(key).is_some()
+    }
+
+    pub fn get_watcher(&self, key: &String) -> Option<&Object> {
+        match self.watchers.get(key) {
+            Some(w) => Some(w),
+            None => {
+                match self.outer {
+                    Some(ref o) => o.get_watcher(key),
+                    None => None,
+                }
+            }
+        }
+    }
+
+    pub fn snapshot(&self) -> EnvSnapshot {
+        EnvSnapshot {
+            store: self.store.iter().map(|(k, v)| (k.clone(), v.borrow().clone())).collect(),
+        }
+    }
+
+    pub fn restore(&mut self, snapshot: EnvSnapshot) {
+        let outer_bytes = self.outer.as_ref().map_or(0, |o| o.heap_bytes);
+        self.heap_bytes = outer_bytes +
+                          snapshot.store.values().map(|v| v.estimated_size()).sum::<usize>();
+        self.store = snapshot.store
+            .into_iter()
+            .map(|(k, v)| (k, Rc::new(RefCell::new(v))))
+            .collect();
+        self.slots.clear();
+    }
 }
 
 #[cfg(test)]
@@ -213,8 +971,172 @@ mod tests {
         let v = Object::new_string("monkey".to_string());
         let mut p = HashMap::new();
         p.insert(k1.clone(), v);
-        let h = HashType { pairs: p };
+        let h = HashType {
+            pairs: Rc::new(p),
+            frozen: false,
+        };
         assert_eq!(h.pairs.get(&k1), h.pairs.get(&k2));
     }
-}
 
+    #[test]
+    fn it_should_hash_keys_stably() {
+        assert_eq!(HashKey::StringType("abc".to_string()).stable_hash(), 596568179);
+        assert_eq!(HashKey::Integer(42).stable_hash(), -1699670636);
+        assert_eq!(HashKey::Boolean(true).stable_hash(), -1127716981);
+    }
+
+    #[test]
+    fn it_should_decide_truthiness() {
+        let function = Object::new_function(vec![],
+                                            BlockStatement {
+                                                token: Token::new("{".to_string(), false, 1, 1),
+                                                statements: vec![],
+                                            },
+                                            &mut Enviroment::new());
+        let expects = [(Object { object_type: ObjectType::Null(Null) }, false),
+                       (Object { object_type: ObjectType::Boolean(false) }, false),
+                       (Object { object_type: ObjectType::Boolean(true) }, true),
+                       (Object::new_int(0), true),
+                       (Object::new_int(-1), true),
+                       (Object::new_string("".to_string()), true),
+                       (Object::new_array(vec![]), true),
+                       (Object::new_hash(HashMap::new()), true),
+                       (Object::new_error(ErrorKind::TypeError, "x".to_string()), true),
+                       (function, true),
+                       (BuildIn::set_from_string(&"len".to_string()).unwrap(), true)];
+        for expect in expects.iter() {
+            assert_eq!(expect.0.is_truthy(), expect.1, "{:?}", expect.0);
+        }
+    }
+
+    #[test]
+    fn it_should_compare_shared_and_distinct_containers() {
+        let array = Array {
+            elements: Rc::new((0..1000).map(Object::new_int).collect()),
+            frozen: false,
+        };
+        let copy = array.clone();
+        assert!(array == array);
+        assert!(array == copy);
+        assert!(array != Array { elements: Rc::new(vec![]), frozen: false });
+
+        let mut pairs = HashMap::new();
+        pairs.insert(HashKey::Integer(1), Object::new_int(2));
+        let hash = HashType {
+            pairs: Rc::new(pairs),
+            frozen: false,
+        };
+        let copy = hash.clone();
+        assert!(hash == hash);
+        assert!(hash == copy);
+        assert!(hash != HashType { pairs: Rc::new(HashMap::new()), frozen: false });
+    }
+
+    #[test]
+    fn it_should_restore_enviroment_snapshot() {
+        let mut env = Enviroment::new();
+        env.set("x".to_string(), Object::new_int(1));
+        let snapshot = env.snapshot();
+
+        env.set("x".to_string(), Object::new_int(2));
+        env.set("y".to_string(), Object::new_int(3));
+        assert_eq!(env.get(&"x".to_string()), Some(Object::new_int(2)));
+
+        env.restore(snapshot);
+        assert_eq!(env.get(&"x".to_string()), Some(Object::new_int(1)));
+        assert_eq!(env.get(&"y".to_string()), None);
+    }
+
+    #[test]
+    fn it_should_list_visible_bindings() {
+        let mut outer = Enviroment::new();
+        outer.set("b".to_string(), Object::new_int(1));
+        outer.set("a".to_string(), Object::new_int(2));
+        let mut env = Enviroment::new_enclosed_enviroment(outer);
+        env.set("b".to_string(), Object::new_int(3));
+
+        assert_eq!(env.bindings(),
+                   vec![("a".to_string(), Object::new_int(2)),
+                        ("b".to_string(), Object::new_int(3))]);
+        assert_eq!(Enviroment::new().bindings(), vec![]);
+    }
+
+    #[test]
+    fn it_should_pretty_print_nested_structures() {
+        let mut inner = HashMap::new();
+        inner.insert(HashKey::StringType("b".to_string()), Object::new_array(vec![]));
+        inner.insert(HashKey::StringType("a".to_string()),
+                     Object::new_array(vec![Object::new_int(1), Object::new_string("x".to_string())]));
+        let mut outer = HashMap::new();
+        outer.insert(HashKey::StringType("nested".to_string()), Object::new_hash(inner));
+        outer.insert(HashKey::Integer(1), Object::new_hash(HashMap::new()));
+
+        let expect = ["{",
+                      "  1: {},",
+                      "  \"nested\": {",
+                      "    \"a\": [",
+                      "      1,",
+                      "      \"x\"",
+                      "    ],",
+                      "    \"b\": []",
+                      "  }",
+                      "}"];
+        assert_eq!(Object::new_hash(outer).pretty(2), expect.join("\n"));
+    }
+
+    #[test]
+    fn it_should_inspect_hashes_in_stable_order() {
+        let mut pairs = HashMap::new();
+        pairs.insert(HashKey::StringType("b".to_string()), Object::new_int(1));
+        pairs.insert(HashKey::StringType("a".to_string()), Object::new_int(2));
+        let hash = Object::new_hash(pairs);
+
+        let stable = EvalOptions { stable_hash_order: true, ..EvalOptions::new() };
+        assert_eq!(hash.inspect_with(&stable), "{\"a\": 2, \"b\": 1}");
+        assert_eq!(Object::new_array(vec![hash.clone()]).inspect_with(&stable),
+                   "[{\"a\": 2, \"b\": 1}]");
+        assert_eq!(hash.inspect_with(&EvalOptions::new()), hash.inspect());
+    }
+
+    #[test]
+    fn it_should_round_trip_serde_value() {
+        let mut inner = HashMap::new();
+        inner.insert(HashKey::StringType("tags".to_string()),
+                     Object::new_array(vec![Object::new_string("a".to_string()),
+                                            Object { object_type: ObjectType::Boolean(true) },
+                                            Object { object_type: ObjectType::Null(Null) }]));
+        let mut outer = HashMap::new();
+        outer.insert(HashKey::StringType("id".to_string()), Object::new_int(-7));
+        outer.insert(HashKey::StringType("meta".to_string()), Object::new_hash(inner));
+        let object = Object::new_hash(outer);
+
+        let value = object.to_serde_value();
+        assert_eq!(value.to_string(),
+                   r#"{"id":-7,"meta":{"tags":["a",true,null]}}"#);
+        assert_eq!(Object::from_serde_value(&value), object);
+
+        let f = BuildIn::set_from_string(&"len".to_string()).unwrap();
+        assert_eq!(f.to_serde_value(), Value::Null);
+
+        let expects = [("9223372036854775808",
+                        ErrorKind::OverflowError,
+                        "integer out of range: 9223372036854775808"),
+                       ("18446744073709551615",
+                        ErrorKind::OverflowError,
+                        "integer out of range: 18446744073709551615")];
+        for expect in expects.iter() {
+            let value: Value = expect.0.parse().unwrap();
+            assert_eq!(Object::from_serde_value(&value),
+                       Object::new_error(expect.1, expect.2.to_string()));
+        }
+        let edge: Value = "-9223372036854775808".parse().unwrap();
+        assert_eq!(Object::from_serde_value(&edge), Object::new_int(i64::MIN));
+        let large: Value = "4294967296".parse().unwrap();
+        assert_eq!(Object::from_serde_value(&large), Object::new_int(4294967296));
+
+        let float: Value = "1.5".parse().unwrap();
+        assert_eq!(Object::from_serde_value(&float), Object::new_f64(1.5));
+        assert_eq!(Object::new_f64(2.0).to_serde_value().to_string(), "2.0");
+        assert_eq!(Object::new_f64(f64::NAN).to_serde_value(), Value::Null);
+    }
+}
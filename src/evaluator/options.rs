@@ -0,0 +1,95 @@
+use std::time::SystemTime;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DivisionMode {
+    Truncate,
+    Floor,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModuloMode {
+    Truncated,
+    Floored,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EvalOptions {
+    pub division_mode: DivisionMode,
+    pub modulo_mode: ModuloMode,
+    pub max_scope_depth: usize,
+    pub max_heap_bytes: Option<usize>,
+    pub coerce_numeric_strings: bool,
+    pub stable_hash_order: bool,
+    pub allow_file_access: bool,
+    pub allow_env_access: bool,
+    // When set, `now()` reports this time instead of reading the system clock.
+    pub fixed_time: Option<SystemTime>,
+    // Makes the functions in `prelude` visible to scripts.
+    pub load_prelude: bool,
+}
+
+impl EvalOptions {
+    pub fn new() -> Self {
+        EvalOptions {
+            division_mode: DivisionMode::Truncate,
+            modulo_mode: ModuloMode::Truncated,
+            max_scope_depth: 1000,
+            max_heap_bytes: None,
+            coerce_numeric_strings: false,
+            stable_hash_order: false,
+            allow_file_access: true,
+            allow_env_access: true,
+            fixed_time: None,
+            load_prelude: true,
+        }
+    }
+}
+
+impl Default for EvalOptions {
+    fn default() -> Self {
+        EvalOptions::new()
+    }
+}
+
+// Both return None when the result does not fit in an i64, i.e. i64::MIN divided by -1.
+pub fn divide(left: i64, right: i64, mode: DivisionMode) -> Option<i64> {
+    let quotient = left.checked_div(right)?;
+    match mode {
+        DivisionMode::Truncate => Some(quotient),
+        DivisionMode::Floor => {
+            if left.checked_rem(right)? != 0 && (left < 0) != (right < 0) {
+                quotient.checked_sub(1)
+            } else {
+                Some(quotient)
+            }
+        }
+    }
+}
+
+pub fn modulo(left: i64, right: i64, mode: ModuloMode) -> Option<i64> {
+    let remainder = left.checked_rem(right)?;
+    match mode {
+        ModuloMode::Truncated => Some(remainder),
+        ModuloMode::Floored => {
+            if remainder != 0 && (remainder < 0) != (right < 0) {
+                remainder.checked_add(right)
+            } else {
+                Some(remainder)
+            }
+        }
+    }
+}
+
+pub fn float_modulo(left: f64, right: f64, mode: ModuloMode) -> f64 {
+    let remainder = left % right;
+    match mode {
+        ModuloMode::Truncated => remainder,
+        ModuloMode::Floored => {
+            if remainder != 0.0 && (remainder < 0.0) != (right < 0.0) {
+                remainder + right
+            } else {
+                remainder
+            }
+        }
+    }
+}
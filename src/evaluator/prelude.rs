@@ -0,0 +1,97 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use lexer::lexer::Lexer;
+use parser::parser::Parser;
+use parser::ast::Node;
+use evaluator::evaluator::eval;
+use evaluator::object::{Object, Enviroment};
+use evaluator::options::EvalOptions;
+
+// Standard functions written in Monkey. Iterables go through `to_array` first. Results are
+// written by index into a copy of the input: building them with `push` or `rest` would copy
+// the whole array on every step.
+const SOURCE: &str = "
+let map = fn(xs, f) {
+    let xs = to_array(xs);
+    let mapped = xs;
+    let i = 0;
+    while (i < len(xs)) {
+        mapped[i] = f(xs[i]);
+        i = i + 1;
+    };
+    mapped
+};
+
+let filter = fn(xs, f) {
+    let xs = to_array(xs);
+    let kept = xs;
+    let n = 0;
+    let i = 0;
+    while (i < len(xs)) {
+        if (f(xs[i])) {
+            kept[n] = xs[i];
+            n = n + 1;
+        };
+        i = i + 1;
+    };
+    kept[0:n]
+};
+
+let reduce = fn(xs, acc, f) {
+    let xs = to_array(xs);
+    let i = 0;
+    while (i < len(xs)) {
+        acc = f(acc, xs[i]);
+        i = i + 1;
+    };
+    acc
+};
+";
+
+type Prelude = Rc<HashMap<String, Object>>;
+
+thread_local! {
+    static LOADED: RefCell<Vec<(EvalOptions, Prelude)>> = const { RefCell::new(Vec::new()) };
+}
+
+// Evaluates the prelude with `options`, so the caller's limits apply to prelude code too.
+// Each set of options is evaluated once per thread and shared by the enviroments using it.
+// Nothing is loaded when `options.load_prelude` is off.
+pub fn load(options: &EvalOptions) -> Prelude {
+    if !options.load_prelude {
+        return Rc::new(HashMap::new());
+    }
+    let cached = LOADED.with(|l| {
+        l.borrow().iter().find(|(o, _)| o == options).map(|(_, p)| p.clone())
+    });
+    if let Some(prelude) = cached {
+        return prelude;
+    }
+    let mut p = Parser::new(Lexer::new(SOURCE.to_string()));
+    let program = p.parse_program();
+    assert!(p.errors.is_empty(), "prelude failed to parse: {:?}", p.errors);
+    let mut env = Enviroment::with_options(EvalOptions { load_prelude: false, ..options.clone() });
+    eval(program.to_enum().to_ast(), &mut env);
+    let prelude = Rc::new(env.bindings().into_iter().collect::<HashMap<_, _>>());
+    LOADED.with(|l| l.borrow_mut().push((options.clone(), prelude.clone())));
+    prelude
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_should_define_prelude_functions() {
+        let prelude = load(&EvalOptions::new());
+        let mut names = prelude.keys().cloned().collect::<Vec<_>>();
+        names.sort();
+        assert_eq!(names, vec!["filter", "map", "reduce"]);
+        assert!(prelude.values().all(|f| f.to_source().is_some()));
+
+        let bare = EvalOptions { load_prelude: false, ..EvalOptions::new() };
+        assert!(load(&bare).is_empty());
+    }
+}
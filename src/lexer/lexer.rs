@@ -1,14 +1,37 @@
+use std::fmt;
+
 use lexer::token;
 use utils::{is_digit, is_letter, EMPTY_STR};
 
+const MULTI_CHAR_OPERATORS: &[&str] = &["==", "!=", "&&", "||", "..", "..="];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LexError {
+    pub literal: String,
+    pub line_num: usize,
+    pub column_num: usize,
+}
+
+impl fmt::Display for LexError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f,
+               "illegal character {:?} at line {} column {}",
+               self.literal,
+               self.line_num,
+               self.column_num)
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Lexer {
     input: String,
     current_char: String,
     position: i32,
     read_position: i32,
-    line: u8,
-    column: u8,
+    line: usize,
+    column: usize,
+    last_token_line: usize,
+    emit_newlines: bool,
 }
 
 impl Lexer {
@@ -20,11 +43,21 @@ impl Lexer {
             read_position: 1,
             line: 1,
             column: 0,
+            last_token_line: 0,
+            emit_newlines: false,
         };
         l.read_char();
         l
     }
 
+    // Like `new`, but a line break after a token becomes a NEWLINE token. Blank lines,
+    // whitespace and comment-only lines after it are folded into that one token.
+    pub fn with_newlines(input: String) -> Self {
+        let mut l = Lexer::new(input);
+        l.emit_newlines = true;
+        l
+    }
+
     fn get_char(&self, position: i32) -> String {
         match self.input.chars().nth(position as usize) {
             Some(x) => x.to_string(),
@@ -39,12 +72,61 @@ impl Lexer {
         self.read_position += 1;
     }
 
-    fn peak_char(&self) -> String {
-        self.get_char(self.read_position - 1)
+    fn peek_char(&self) -> String {
+        self.peek_char_at(0)
+    }
+
+    fn peek_char_at(&self, offset: i32) -> String {
+        self.get_char(self.read_position - 1 + offset)
+    }
+
+    fn read_operator(&mut self, operators: &[&str]) -> Option<String> {
+        let mut matched: Option<&str> = None;
+        for op in operators.iter() {
+            let is_match = op.chars().enumerate().all(|(i, c)| {
+                let x = if i == 0 {
+                    self.current_char.clone()
+                } else {
+                    self.peek_char_at(i as i32 - 1)
+                };
+                x == c.to_string()
+            });
+            if is_match && matched.is_none_or(|m| op.len() > m.len()) {
+                matched = Some(op);
+            }
+        }
+
+        matched.map(|op| {
+                        for _ in 0..op.chars().count() {
+                            self.read_char();
+                        }
+                        op.to_string()
+                    })
     }
 
     pub fn next_token(&mut self) -> token::Token {
         self.skip_white_space();
+        let mut comments = vec![];
+        while self.current_char == "/" && self.peek_char() == "/" {
+            let is_trailing = self.line == self.last_token_line;
+            let comment = self.read_comment();
+            if !is_trailing {
+                comments.push(comment);
+                self.skip_newlines();
+            } else {
+                self.skip_white_space();
+            }
+        }
+
+        if self.current_char == "\n" {
+            let t = token::Token::new("\n".to_string(), false, self.line, self.column);
+            self.skip_newlines();
+            if self.last_token_line == 0 {
+                return self.next_token();
+            }
+            return t;
+        }
+
         let current_char = &self.current_char.clone();
         let position = self.column;
 
@@ -52,44 +134,89 @@ impl Lexer {
         let seed = match current_char {
             x if is_letter(x) => self.read_identifier(),
             x if is_digit(x) => self.read_digit(),
-            x if x == "=" && self.peak_char() == "=" => {
-                self.read_char();
-                self.read_char();
-                format!("{}{}", x, "=")
-            },
-            x if x == "!" && self.peak_char() == "=" => {
-                self.read_char();
-                self.read_char();
-                format!("{}{}", x, "=")
-            },
             x if x == "\"" => {
-                is_string = true;
-                self.read_string()
+                match self.read_string() {
+                    Ok(s) => {
+                        is_string = true;
+                        s
+                    }
+                    Err(escape) => escape,
+                }
             },
             x => {
-                self.read_char();
-                x.clone()
+                match self.read_operator(MULTI_CHAR_OPERATORS) {
+                    Some(op) => op,
+                    None => {
+                        self.read_char();
+                        x.clone()
+                    }
+                }
             },
         };
 
-        token::Token::new(seed, is_string, self.line, position)
+        self.last_token_line = self.line;
+        let mut t = token::Token::new(seed, is_string, self.line, position);
+        t.comments = comments;
+        t
     }
 
-    fn read_string(&mut self) -> String {
-        self.read_char();
-        let start = (self.position - 1) as usize;
+    pub fn try_next_token(&mut self) -> Result<token::Token, LexError> {
+        let t = self.next_token();
+        match t.token_type {
+            token::TokenType::ILLEGAL => {
+                Err(LexError {
+                        literal: t.literal,
+                        line_num: t.line_num,
+                        column_num: t.column_num,
+                    })
+            }
+            _ => Ok(t),
+        }
+    }
 
-        while self.current_char != "\"" {
+    fn read_comment(&mut self) -> String {
+        self.read_char();
+        self.read_char();
+        let mut comment = String::new();
+        while self.current_char != "\n" && self.current_char != EMPTY_STR {
+            comment.push_str(&self.current_char);
             self.read_char();
         }
+        comment.trim().to_string()
+    }
 
-        let input_chars = self.input.chars().collect::<Vec<char>>();
-        let end = (self.position - 1) as usize;
+    // Reads up to the closing quote even after an invalid escape, so lexing resumes after
+    // the literal. The first invalid escape is returned as the error.
+    fn read_string(&mut self) -> Result<String, String> {
+        self.read_char();
+        let mut value = String::new();
+        let mut invalid = None;
+
+        while self.current_char != "\"" && self.current_char != EMPTY_STR {
+            if self.current_char == "\\" {
+                self.read_char();
+                match self.current_char.as_str() {
+                    "n" => value.push('\n'),
+                    "t" => value.push('\t'),
+                    "\"" => value.push('"'),
+                    "\\" => value.push('\\'),
+                    x => {
+                        if invalid.is_none() {
+                            invalid = Some(format!("\\{}", x));
+                        }
+                    }
+                }
+            } else {
+                value.push_str(&self.current_char);
+            }
+            self.read_char();
+        }
         self.read_char();
 
-        (&input_chars[start..end])
-            .iter()
-            .fold("".to_string(), |acc, &s| { format!("{}{}", acc, s.to_string()) })
+        match invalid {
+            Some(x) => Err(x),
+            None => Ok(value),
+        }
     }
 
     fn read_identifier(&mut self) -> String {
@@ -105,12 +232,19 @@ impl Lexer {
         (*splited).to_string()
     }
 
+    // A `.` only continues the number when a digit follows it, so `1..4` stays a range.
     fn read_digit(&mut self) -> String {
         let start = (self.position - 1) as usize;
 
         while is_digit(&self.current_char) {
             self.read_char();
         }
+        if self.current_char == "." && is_digit(&self.peek_char()) {
+            self.read_char();
+            while is_digit(&self.current_char) {
+                self.read_char();
+            }
+        }
 
         let input_chars = self.input.chars().collect::<Vec<char>>();
         let end = (self.position - 1) as usize;
@@ -118,6 +252,7 @@ impl Lexer {
         (*splited).to_string()
     }
 
+    // In newline mode this stops at a line break, leaving it for `next_token`.
     pub fn skip_white_space(&mut self) {
         let (is_whitespace, is_newline) = match self.current_char.chars().last() {
             Some(x) => {
@@ -125,6 +260,9 @@ impl Lexer {
             },
             _ => (false, false),
         };
+        if is_newline && self.emit_newlines {
+            return;
+        }
         if is_newline {
             self.column = 0;
             self.line = self.line + 1;
@@ -134,6 +272,13 @@ impl Lexer {
             self.skip_white_space()
         };
     }
+
+    fn skip_newlines(&mut self) {
+        let emit_newlines = self.emit_newlines;
+        self.emit_newlines = false;
+        self.skip_white_space();
+        self.emit_newlines = emit_newlines;
+    }
 }
 
 #[cfg(test)]
@@ -143,15 +288,16 @@ mod tests {
 
     #[test]
     fn it_should_analysis_arithmetic() {
-        let mut l = Lexer::new("!-*/<>".to_string());
+        let mut l = Lexer::new("!-*/%<>".to_string());
         let expects = vec![
             (BANG, "!", 1),
             (MINUS, "-", 2),
             (MULTIPLY, "*", 3),
             (DIVIDE, "/", 4),
-            (LT, "<", 5),
-            (GT, ">", 6),
-            (EOF, "", 7)
+            (MODULO, "%", 5),
+            (LT, "<", 6),
+            (GT, ">", 7),
+            (EOF, "", 8)
         ];
 
         for (token_type, literal, column_num) in expects {
@@ -180,6 +326,18 @@ mod tests {
         }
     }
 
+    #[test]
+    fn it_should_count_positions_past_long_lines() {
+        let input = format!("let x = {};\n\n  y \"s\" z", "a".repeat(300));
+        let mut l = Lexer::new(input);
+        let expects = vec![(1, 1), (1, 5), (1, 7), (1, 9), (1, 309), (3, 3), (3, 5), (3, 9)];
+
+        for (line_num, column_num) in expects {
+            let t = l.next_token();
+            assert_eq!((t.line_num, t.column_num), (line_num, column_num), "{:?}", t);
+        }
+    }
+
     #[test]
     fn it_should_analysis_control_syntax() {
         let mut l = Lexer::new("
@@ -336,4 +494,179 @@ mod tests {
             assert_eq!(t.literal, literal);
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn it_should_attach_leading_comments() {
+        let mut l = Lexer::new("
+            // the answer
+            // to everything
+            let x = 42; // trailing
+            x
+        ".to_string());
+        let t = l.next_token();
+        assert_eq!(t.token_type, LET);
+        assert_eq!(t.comments, vec!["the answer".to_string(), "to everything".to_string()]);
+
+        for _ in 0..4 {
+            assert_eq!(l.next_token().comments, Vec::<String>::new());
+        }
+        let t = l.next_token();
+        assert_eq!(t.token_type, IDENT("x".to_string()));
+        assert_eq!(t.comments, Vec::<String>::new());
+    }
+
+    #[test]
+    fn it_should_emit_newlines_when_asked() {
+        let input = "
+            let x = 1
+
+
+            // own line
+            x // trailing
+        ";
+        let expects = vec![LET,
+                           IDENT("x".to_string()),
+                           ASSIGN,
+                           INT("1".to_string()),
+                           NEWLINE,
+                           IDENT("x".to_string()),
+                           NEWLINE,
+                           EOF];
+        let mut l = Lexer::with_newlines(input.to_string());
+        let tokens = expects.iter().map(|_| l.next_token()).collect::<Vec<_>>();
+        assert_eq!(tokens.iter().map(|t| t.token_type.clone()).collect::<Vec<_>>(), expects);
+        assert_eq!((tokens[4].line_num, tokens[4].column_num), (2, 22));
+        assert_eq!(tokens[5].comments, vec!["own line".to_string()]);
+
+        let mut l = Lexer::new(input.to_string());
+        let expects = vec![LET, IDENT("x".to_string()), ASSIGN, INT("1".to_string())];
+        for expect in expects {
+            assert_eq!(l.next_token().token_type, expect);
+        }
+        assert_eq!(l.next_token().token_type, IDENT("x".to_string()));
+    }
+
+    #[test]
+    fn it_should_skip_line_comments_between_divisions() {
+        let mut l = Lexer::new("
+            // own line
+            let x = 10 / 2; // after a statement
+            x / x // end of input".to_string());
+        let expects = vec![LET,
+                           IDENT("x".to_string()),
+                           ASSIGN,
+                           INT("10".to_string()),
+                           DIVIDE,
+                           INT("2".to_string()),
+                           SEMICOLON,
+                           IDENT("x".to_string()),
+                           DIVIDE,
+                           IDENT("x".to_string()),
+                           EOF];
+        for expect in expects {
+            assert_eq!(l.next_token().token_type, expect);
+        }
+
+        let mut l = Lexer::new("a / / b".to_string());
+        let expects = vec![IDENT("a".to_string()),
+                           DIVIDE,
+                           DIVIDE,
+                           IDENT("b".to_string()),
+                           EOF];
+        for expect in expects {
+            assert_eq!(l.next_token().token_type, expect);
+        }
+    }
+
+    #[test]
+    fn it_should_read_string_escapes() {
+        let mut l = Lexer::new(r#""a\nb\t\"c\"\\" "bad \q" "ok""#.to_string());
+        assert_eq!(l.next_token().token_type, STRING("a\nb\t\"c\"\\".to_string()));
+        let t = l.next_token();
+        assert_eq!((t.token_type, t.literal), (ILLEGAL, "\\q".to_string()));
+        assert_eq!(l.next_token().token_type, STRING("ok".to_string()));
+        assert_eq!(l.next_token().token_type, EOF);
+
+        let mut l = Lexer::new(r#"let s = "\q";"#.to_string());
+        for _ in 0..3 {
+            assert!(l.try_next_token().is_ok());
+        }
+        assert_eq!(l.try_next_token().unwrap_err().literal, "\\q");
+    }
+
+    #[test]
+    fn it_should_peek_ahead() {
+        let l = Lexer::new("abcd".to_string());
+        assert_eq!(l.current_char, "a");
+        assert_eq!(l.peek_char(), "b");
+        assert_eq!(l.peek_char_at(1), "c");
+        assert_eq!(l.peek_char_at(2), "d");
+        assert_eq!(l.peek_char_at(3), EMPTY_STR);
+    }
+
+    #[test]
+    fn it_should_read_longest_operator() {
+        let mut l = Lexer::new("**= **+".to_string());
+        let operators = ["*", "**", "**="];
+        assert_eq!(l.read_operator(&operators), Some("**=".to_string()));
+        l.skip_white_space();
+        assert_eq!(l.read_operator(&operators), Some("**".to_string()));
+        assert_eq!(l.read_operator(&operators), None);
+        assert_eq!(l.next_token().token_type, PLUS);
+    }
+
+    #[test]
+    fn it_should_analysis_multi_char_operators() {
+        let mut l = Lexer::new("a == b != !c = d && e || f".to_string());
+        let expects = vec![IDENT("a".to_string()), EQ, IDENT("b".to_string()), NOTEQ, BANG,
+                           IDENT("c".to_string()), ASSIGN, IDENT("d".to_string()), AND,
+                           IDENT("e".to_string()), OR, IDENT("f".to_string()), EOF];
+        for token_type in expects {
+            assert_eq!(l.next_token().token_type, token_type);
+        }
+    }
+
+    #[test]
+    fn it_should_analysis_range_operators() {
+        let mut l = Lexer::new("1..4 a..=b .".to_string());
+        let expects = vec![INT("1".to_string()), DOTDOT, INT("4".to_string()),
+                           IDENT("a".to_string()), DOTDOTEQ, IDENT("b".to_string()), ILLEGAL, EOF];
+        for token_type in expects {
+            assert_eq!(l.next_token().token_type, token_type);
+        }
+    }
+
+    #[test]
+    fn it_should_analysis_float_literals() {
+        let mut l = Lexer::new("3.14 1..4 1.5.. 2.".to_string());
+        let expects = vec![FLOAT("3.14".to_string()), INT("1".to_string()), DOTDOT,
+                           INT("4".to_string()), FLOAT("1.5".to_string()), DOTDOT,
+                           INT("2".to_string()), ILLEGAL, EOF];
+        for token_type in expects {
+            assert_eq!(l.next_token().token_type, token_type);
+        }
+    }
+
+    #[test]
+    fn it_should_return_result_tokens() {
+        let mut l = Lexer::new("let x = 5;".to_string());
+        let expects = vec![LET, IDENT("x".to_string()), ASSIGN, INT("5".to_string()), SEMICOLON, EOF];
+        for token_type in expects {
+            assert_eq!(l.try_next_token().map(|t| t.token_type), Ok(token_type));
+        }
+
+        let mut l = Lexer::new("let x = 1;\nx @ 2".to_string());
+        for _ in 0..6 {
+            assert!(l.try_next_token().is_ok());
+        }
+        let err = l.try_next_token().unwrap_err();
+        assert_eq!(err,
+                   LexError {
+                       literal: "@".to_string(),
+                       line_num: 2,
+                       column_num: 3,
+                   });
+        assert_eq!(err.to_string(), "illegal character \"@\" at line 2 column 3");
+        assert_eq!(l.try_next_token().map(|t| t.token_type), Ok(INT("2".to_string())));
+    }
+}
@@ -1,17 +1,22 @@
+use std::fmt;
+
 use utils::{is_digit, is_letter};
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum TokenType {
     ILLEGAL,
     EOF,
+    NEWLINE,
     IDENT(String),
     INT(String),
+    FLOAT(String),
     STRING(String),
     ASSIGN,
     PLUS,
     MINUS,
     MULTIPLY,
     DIVIDE,
+    MODULO,
     LT,
     GT,
     BANG,
@@ -31,8 +36,15 @@ pub enum TokenType {
     IF,
     ELSE,
     RETURN,
+    TEST,
     EQ,
     NOTEQ,
+    AND,
+    OR,
+    IN,
+    DOTDOT,
+    DOTDOTEQ,
+    WHILE,
 }
 
 impl TokenType {
@@ -42,6 +54,7 @@ impl TokenType {
             "-" => TokenType::MINUS,
             "*" => TokenType::MULTIPLY,
             "/" => TokenType::DIVIDE,
+            "%" => TokenType::MODULO,
             "<" => TokenType::LT,
             ">" => TokenType::GT,
             "," => TokenType::COMMA,
@@ -59,29 +72,92 @@ impl TokenType {
             "false" => TokenType::FALSE,
             "if" => TokenType::IF,
             "else" => TokenType::ELSE,
+            "while" => TokenType::WHILE,
             "return" => TokenType::RETURN,
+            "test" => TokenType::TEST,
+            "in" => TokenType::IN,
             "" => TokenType::EOF,
+            "\n" => TokenType::NEWLINE,
             "=" => TokenType::ASSIGN,
             "!" => TokenType::BANG,
             "==" => TokenType::EQ,
             "!=" => TokenType::NOTEQ,
+            "&&" => TokenType::AND,
+            "||" => TokenType::OR,
+            ".." => TokenType::DOTDOT,
+            "..=" => TokenType::DOTDOTEQ,
+            _ if is_digit(s) && s.contains('.') => TokenType::FLOAT(s.clone()),
             _ if is_digit(s) => TokenType::INT(s.clone()),
             _ if is_letter(s) => TokenType::IDENT(s.clone()),
             _ => TokenType::ILLEGAL,
         }
     }
+
+    pub fn to_str(&self) -> String {
+        let s = match *self {
+            TokenType::IDENT(ref x) |
+            TokenType::INT(ref x) |
+            TokenType::FLOAT(ref x) => return x.clone(),
+            TokenType::STRING(ref x) => return format!("\"{}\"", x),
+            TokenType::ILLEGAL => "ILLEGAL",
+            TokenType::EOF => "EOF",
+            TokenType::NEWLINE => "NEWLINE",
+            TokenType::ASSIGN => "=",
+            TokenType::PLUS => "+",
+            TokenType::MINUS => "-",
+            TokenType::MULTIPLY => "*",
+            TokenType::DIVIDE => "/",
+            TokenType::MODULO => "%",
+            TokenType::LT => "<",
+            TokenType::GT => ">",
+            TokenType::BANG => "!",
+            TokenType::COMMA => ",",
+            TokenType::COLON => ":",
+            TokenType::SEMICOLON => ";",
+            TokenType::LPAREN => "(",
+            TokenType::RPAREN => ")",
+            TokenType::LBRACE => "{",
+            TokenType::RBRACE => "}",
+            TokenType::LBRACKET => "[",
+            TokenType::RBRACKET => "]",
+            TokenType::FUNCTION => "fn",
+            TokenType::LET => "let",
+            TokenType::TRUE => "true",
+            TokenType::FALSE => "false",
+            TokenType::IF => "if",
+            TokenType::ELSE => "else",
+            TokenType::WHILE => "while",
+            TokenType::RETURN => "return",
+            TokenType::TEST => "test",
+            TokenType::EQ => "==",
+            TokenType::NOTEQ => "!=",
+            TokenType::AND => "&&",
+            TokenType::OR => "||",
+            TokenType::IN => "in",
+            TokenType::DOTDOT => "..",
+            TokenType::DOTDOTEQ => "..=",
+        };
+        s.to_string()
+    }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+impl fmt::Display for TokenType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_str())
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Hash)]
 pub struct Token {
     pub token_type: TokenType,
     pub literal: String,
-    pub line_num: u8,
-    pub column_num: u8,
+    pub line_num: usize,
+    pub column_num: usize,
+    pub comments: Vec<String>,
 }
 
 impl Token {
-    pub fn new(s: String, is_string: bool, line_num: u8, column_num: u8) -> Self {
+    pub fn new(s: String, is_string: bool, line_num: usize, column_num: usize) -> Self {
         if is_string {
             return Token::new_string(s, line_num, column_num);
         }
@@ -90,16 +166,49 @@ impl Token {
             literal: s,
             line_num: line_num,
             column_num: column_num,
+            comments: vec![],
         }
     }
 
-    fn new_string(s: String, line_num: u8, column_num: u8) -> Self {
+    fn new_string(s: String, line_num: usize, column_num: usize) -> Self {
         Token {
             token_type: TokenType::STRING(s.clone()),
             literal: s,
             line_num: line_num,
             column_num: column_num,
+            comments: vec![],
         }
     }
 }
 
+impl fmt::Debug for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f,
+               "Token({:?}, {:?}, line: {}, column: {})",
+               self.token_type,
+               self.literal,
+               self.line_num,
+               self.column_num)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_should_format_token() {
+        let expects = [(TokenType::NOTEQ, "!="),
+                       (TokenType::FUNCTION, "fn"),
+                       (TokenType::IN, "in"),
+                       (TokenType::IDENT("foo".to_string()), "foo"),
+                       (TokenType::STRING("bar".to_string()), "\"bar\""),
+                       (TokenType::EOF, "EOF")];
+        for expect in expects.iter() {
+            assert_eq!(format!("{}", expect.0), expect.1);
+        }
+
+        let t = Token::new("let".to_string(), false, 2, 5);
+        assert_eq!(format!("{:?}", t), r#"Token(LET, "let", line: 2, column: 5)"#);
+    }
+}
@@ -0,0 +1,26 @@
+extern crate serde_json;
+
+pub mod lexer;
+pub mod parser;
+pub mod evaluator;
+pub mod buildin;
+pub mod output;
+
+mod utils;
+
+use lexer::lexer::Lexer;
+use parser::parser::Parser;
+use parser::ast::Node;
+use evaluator::evaluator::eval;
+use evaluator::object::{Enviroment, Object};
+
+// Parses and evaluates `input` in a fresh environment. Parse errors are returned as `Err`,
+// while a runtime error is an error object in `Ok`, as it is from `eval`.
+pub fn eval_str(input: &str) -> Result<Object, Vec<String>> {
+    let mut p = Parser::new(Lexer::new(input.to_string()));
+    let program = p.parse_program();
+    if !p.errors.is_empty() {
+        return Err(p.errors);
+    }
+    Ok(eval(program.to_enum().to_ast(), &mut Enviroment::new()))
+}
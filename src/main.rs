@@ -1,11 +1,90 @@
-mod lexer;
-mod parser;
-mod evaluator;
+extern crate monkey;
+extern crate rustyline;
+
+use std::env;
+use std::fs::File;
+use std::io::Read;
+use std::process;
+
+use monkey::{lexer, parser, evaluator};
 
-mod utils;
-mod buildin;
 mod repl;
+mod runner;
+
+fn run_test_file(path: &str) -> i32 {
+    let mut input = String::new();
+    if let Err(e) = File::open(path).and_then(|mut f| f.read_to_string(&mut input)) {
+        println!("{}: {}", path, e);
+        return 1;
+    }
+
+    match runner::run_tests(&input) {
+        Ok(report) => {
+            for line in report.lines() {
+                println!("{}", line);
+            }
+            if report.failed.is_empty() { 0 } else { 1 }
+        }
+        Err(errors) => {
+            for error in errors.iter() {
+                println!("{}", error);
+            }
+            1
+        }
+    }
+}
+
+fn run_script_file(path: &str) -> i32 {
+    let mut input = String::new();
+    if let Err(e) = File::open(path).and_then(|mut f| f.read_to_string(&mut input)) {
+        println!("{}: {}", path, e);
+        return 1;
+    }
+
+    match runner::run_script(&input, &mut evaluator::object::Enviroment::new()) {
+        Ok(_) => 0,
+        Err(errors) => {
+            for error in errors.iter() {
+                println!("{}", error);
+            }
+            1
+        }
+    }
+}
+
+fn run_script_file_collecting(path: &str) -> i32 {
+    let mut input = String::new();
+    if let Err(e) = File::open(path).and_then(|mut f| f.read_to_string(&mut input)) {
+        println!("{}: {}", path, e);
+        return 1;
+    }
+
+    let env = &mut evaluator::object::Enviroment::new();
+    match runner::run_script_collecting(&input, env) {
+        Ok((_, ref errors)) if errors.is_empty() => 0,
+        Ok((_, errors)) => {
+            for error in errors.iter() {
+                println!("{}", error.line());
+            }
+            1
+        }
+        Err(errors) => {
+            for error in errors.iter() {
+                println!("{}", error);
+            }
+            1
+        }
+    }
+}
 
 fn main() {
-    repl::run();
+    let args: Vec<String> = env::args().collect();
+    match args.get(1).map(|s| s.as_str()) {
+        Some("test") if args.len() == 3 => process::exit(run_test_file(&args[2])),
+        Some("run") if args.len() == 3 => process::exit(run_script_file(&args[2])),
+        Some("run") if args.len() == 4 && args[2] == "--keep-going" => {
+            process::exit(run_script_file_collecting(&args[3]))
+        }
+        _ => repl::run(),
+    }
 }
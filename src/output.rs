@@ -0,0 +1,49 @@
+use std::cell::RefCell;
+
+thread_local! {
+    static CAPTURED: RefCell<Option<Vec<String>>> = const { RefCell::new(None) };
+}
+
+pub fn write_line(line: String) {
+    let rest = CAPTURED.with(|c| match *c.borrow_mut() {
+        Some(ref mut lines) => {
+            lines.push(line);
+            None
+        }
+        None => Some(line),
+    });
+    if let Some(line) = rest {
+        println!("{}", line);
+    }
+}
+
+pub fn capture<F, T>(f: F) -> (T, Vec<String>)
+    where F: FnOnce() -> T
+{
+    let previous = CAPTURED.with(|c| c.borrow_mut().replace(vec![]));
+    let result = f();
+    let lines = CAPTURED.with(|c| {
+        let mut c = c.borrow_mut();
+        let lines = c.take().unwrap_or(vec![]);
+        *c = previous;
+        lines
+    });
+    (result, lines)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_should_capture_lines() {
+        let (result, lines) = capture(|| {
+            write_line("outer".to_string());
+            let (_, inner) = capture(|| write_line("inner".to_string()));
+            assert_eq!(inner, vec!["inner".to_string()]);
+            1
+        });
+        assert_eq!(result, 1);
+        assert_eq!(lines, vec!["outer".to_string()]);
+    }
+}
@@ -1,6 +1,7 @@
 use std::collections::HashMap;
 use std::hash::{Hash, Hasher};
 use lexer::token::Token;
+use utils::quote_string;
 
 pub trait Node {
     fn token_literal(&self) -> String;
@@ -23,16 +24,22 @@ pub enum AST {
     LetStatement(LetStatement),
     ReturnStatement(ReturnStatement),
     ExpressionStatement(ExpressionStatement),
+    TestStatement(TestStatement),
+    AssignStatement(AssignStatement),
+    LetRecStatement(LetRecStatement),
     Identifier(Identifier),
     IntegerLiteral(IntegerLiteral),
+    FloatLiteral(FloatLiteral),
     StringLiteral(StringLiteral),
     ArrayLiteral(ArrayLiteral),
     HashLiteral(HashLiteral),
     PrefixExpression(PrefixExpression),
     InfixExpression(InfixExpression),
     IndexExpression(IndexExpression),
+    SliceExpression(SliceExpression),
     Boolean(Boolean),
     IfExpression(IfExpression),
+    WhileExpression(WhileExpression),
     FunctionLiteral(FunctionLiteral),
     CallExpression(CallExpression),
 }
@@ -73,6 +80,9 @@ pub enum Statements {
     LetStatement(LetStatement),
     ReturnStatement(ReturnStatement),
     ExpressionStatement(ExpressionStatement),
+    TestStatement(TestStatement),
+    AssignStatement(AssignStatement),
+    LetRecStatement(LetRecStatement),
 }
 
 impl Statements {
@@ -85,40 +95,120 @@ impl Statements {
     pub fn new_expression_statement(x: ExpressionStatement) -> Self {
         Statements::ExpressionStatement(x)
     }
+    pub fn new_test_statement(x: TestStatement) -> Self {
+        Statements::TestStatement(x)
+    }
+    pub fn new_assign_statement(x: AssignStatement) -> Self {
+        Statements::AssignStatement(x)
+    }
+
+    pub fn new_let_rec_statement(x: LetRecStatement) -> Self {
+        Statements::LetRecStatement(x)
+    }
+
+    // The keyword or first token of the statement, except for an assignment, whose token is
+    // its `=`.
+    pub fn token(&self) -> &Token {
+        match *self {
+            Statements::BlockStatement(ref x) => &x.token,
+            Statements::LetStatement(ref x) => &x.token,
+            Statements::ReturnStatement(ref x) => &x.token,
+            Statements::ExpressionStatement(ref x) => &x.token,
+            Statements::TestStatement(ref x) => &x.token,
+            Statements::AssignStatement(ref x) => &x.token,
+            Statements::LetRecStatement(ref x) => &x.token,
+        }
+    }
+
+    pub fn to_source(&self) -> String {
+        match *self {
+            Statements::BlockStatement(ref x) => x.to_source(),
+            Statements::LetStatement(ref x) => {
+                format!("let {} = {};", x.name.value, x.value.to_source())
+            }
+            Statements::ReturnStatement(ref x) => {
+                match x.return_value {
+                    Some(ref v) => format!("return {};", v.to_source()),
+                    None => "return;".to_string(),
+                }
+            }
+            Statements::ExpressionStatement(ref x) => format!("{};", x.expression.to_source()),
+            Statements::TestStatement(ref x) => {
+                format!("test {} {}", quote_string(&x.name), x.body.to_source())
+            }
+            Statements::AssignStatement(ref x) => {
+                format!("{} = {};", x.target.to_source(), x.value.to_source())
+            }
+            Statements::LetRecStatement(ref x) => {
+                let bindings = x.bindings
+                    .iter()
+                    .map(|b| format!("{} = {}", b.name.value, b.value.to_source()))
+                    .collect::<Vec<_>>()
+                    .join(" and ");
+                format!("let rec {};", bindings)
+            }
+        }
+    }
 }
 
 impl Node for Statements {
     fn token_literal(&self) -> String {
-        match self {
-            &Statements::BlockStatement(ref x) => x.token.literal.clone(),
-            &Statements::LetStatement(ref x) => x.token.literal.clone(),
-            &Statements::ReturnStatement(ref x) => x.token.literal.clone(),
-            &Statements::ExpressionStatement(ref x) => x.token.literal.clone(),
+        match *self {
+            Statements::BlockStatement(ref x) => x.token.literal.clone(),
+            Statements::LetStatement(ref x) => x.token.literal.clone(),
+            Statements::ReturnStatement(ref x) => x.token.literal.clone(),
+            Statements::ExpressionStatement(ref x) => x.token.literal.clone(),
+            Statements::TestStatement(ref x) => x.token.literal.clone(),
+            Statements::AssignStatement(ref x) => x.token.literal.clone(),
+            Statements::LetRecStatement(ref x) => x.token.literal.clone(),
         }
     }
 
     fn string(&self) -> String {
-        match self {
-            &Statements::BlockStatement(ref x) => fold_statements(&x.statements),
-            &Statements::LetStatement(ref x) => {
+        match *self {
+            Statements::BlockStatement(ref x) => fold_statements(&x.statements),
+            Statements::LetStatement(ref x) => {
                 format!("{} {} = {}",
                         self.token_literal(),
                         x.name.to_enum().string(),
                         x.value.string())
             }
-            &Statements::ReturnStatement(ref x) => {
-                format!("{} {};", self.token_literal(), x.return_value.string())
+            Statements::ReturnStatement(ref x) => {
+                match x.return_value {
+                    Some(ref v) => format!("{} {};", self.token_literal(), v.string()),
+                    None => format!("{};", self.token_literal()),
+                }
+            }
+            Statements::ExpressionStatement(ref x) => x.expression.string(),
+            Statements::TestStatement(ref x) => {
+                format!("{} \"{}\" {}",
+                        self.token_literal(),
+                        x.name,
+                        fold_statements(&x.body.statements))
+            }
+            Statements::AssignStatement(ref x) => {
+                format!("{} = {}", x.target.string(), x.value.string())
+            }
+            Statements::LetRecStatement(ref x) => {
+                let bindings = x.bindings
+                    .iter()
+                    .map(|b| format!("{} = {}", b.name.to_enum().string(), b.value.string()))
+                    .collect::<Vec<_>>()
+                    .join(" and ");
+                format!("{} rec {}", self.token_literal(), bindings)
             }
-            &Statements::ExpressionStatement(ref x) => format!("{}", x.expression.string()),
         }
     }
 
     fn to_ast(&self) -> AST {
-        match self {
-            &Statements::BlockStatement(ref x) => AST::BlockStatement(x.clone()),
-            &Statements::LetStatement(ref x) => AST::LetStatement(x.clone()),
-            &Statements::ReturnStatement(ref x) => AST::ReturnStatement(x.clone()),
-            &Statements::ExpressionStatement(ref x) => AST::ExpressionStatement(x.clone()),
+        match *self {
+            Statements::BlockStatement(ref x) => AST::BlockStatement(x.clone()),
+            Statements::LetStatement(ref x) => AST::LetStatement(x.clone()),
+            Statements::ReturnStatement(ref x) => AST::ReturnStatement(x.clone()),
+            Statements::ExpressionStatement(ref x) => AST::ExpressionStatement(x.clone()),
+            Statements::TestStatement(ref x) => AST::TestStatement(x.clone()),
+            Statements::AssignStatement(ref x) => AST::AssignStatement(x.clone()),
+            Statements::LetRecStatement(ref x) => AST::LetRecStatement(x.clone()),
         }
     }
 }
@@ -129,14 +219,17 @@ impl Statement for Statements {}
 pub enum Expressions {
     Identifier(Identifier),
     IntegerLiteral(IntegerLiteral),
+    FloatLiteral(FloatLiteral),
     StringLiteral(StringLiteral),
     ArrayLiteral(ArrayLiteral),
     HashLiteral(HashLiteral),
     PrefixExpression(PrefixExpression),
     InfixExpression(InfixExpression),
     IndexExpression(IndexExpression),
+    SliceExpression(SliceExpression),
     Boolean(Boolean),
     IfExpression(IfExpression),
+    WhileExpression(WhileExpression),
     FunctionLiteral(FunctionLiteral),
     CallExpression(CallExpression),
 }
@@ -148,6 +241,9 @@ impl Expressions {
     pub fn new_integer_literal(x: IntegerLiteral) -> Self {
         Expressions::IntegerLiteral(x)
     }
+    pub fn new_float_literal(x: FloatLiteral) -> Self {
+        Expressions::FloatLiteral(x)
+    }
     pub fn new_string_literal(x: StringLiteral) -> Self {
         Expressions::StringLiteral(x)
     }
@@ -166,38 +262,117 @@ impl Expressions {
     pub fn new_if_expression(x: IfExpression) -> Self {
         Expressions::IfExpression(x)
     }
+    pub fn new_while_expression(x: WhileExpression) -> Self {
+        Expressions::WhileExpression(x)
+    }
     pub fn new_function_literal(x: FunctionLiteral) -> Self {
         Expressions::FunctionLiteral(x)
     }
     pub fn new_call_expression(x: CallExpression) -> Self {
         Expressions::CallExpression(x)
     }
+
+    pub fn to_source(&self) -> String {
+        match *self {
+            Expressions::Identifier(ref x) => x.value.clone(),
+            Expressions::IntegerLiteral(ref x) => format!("{}", x.value),
+            Expressions::FloatLiteral(ref x) => x.token.literal.clone(),
+            Expressions::StringLiteral(ref x) => quote_string(&x.value),
+            Expressions::ArrayLiteral(ref x) => {
+                let elements = x.elements
+                    .iter()
+                    .map(|e| e.to_source())
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                format!("[{}]", elements)
+            }
+            Expressions::HashLiteral(ref x) => {
+                let pairs = x.keys
+                    .iter()
+                    .zip(x.values.iter())
+                    .map(|(k, v)| format!("{}: {}", k.to_source(), v.to_source()))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                format!("{{{}}}", pairs)
+            }
+            Expressions::PrefixExpression(ref x) => {
+                format!("({}{})", x.operator, x.right.to_source())
+            }
+            Expressions::InfixExpression(ref x) => {
+                format!("({} {} {})",
+                        x.left.to_source(),
+                        x.operator,
+                        x.right.to_source())
+            }
+            Expressions::IndexExpression(ref x) => {
+                format!("({}[{}])", x.left.to_source(), x.index.to_source())
+            }
+            Expressions::SliceExpression(ref x) => {
+                let bound = |b: &Option<Box<Expressions>>| {
+                    b.as_ref().map_or("".to_string(), |e| e.to_source())
+                };
+                format!("({}[{}:{}])", x.left.to_source(), bound(&x.start), bound(&x.end))
+            }
+            Expressions::Boolean(ref x) => format!("{}", x.value),
+            Expressions::IfExpression(ref x) => {
+                match x.alternative {
+                    Some(ref a) => {
+                        format!("if ({}) {} else {}",
+                                x.condition.to_source(),
+                                x.consequence.to_source(),
+                                a.to_source())
+                    }
+                    None => {
+                        format!("if ({}) {}",
+                                x.condition.to_source(),
+                                x.consequence.to_source())
+                    }
+                }
+            }
+            Expressions::WhileExpression(ref x) => {
+                format!("while ({}) {}", x.condition.to_source(), x.body.to_source())
+            }
+            Expressions::FunctionLiteral(ref x) => function_source(&x.parameters, &x.body),
+            Expressions::CallExpression(ref x) => {
+                let arguments = x.arguments
+                    .iter()
+                    .map(|a| a.to_source())
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                format!("{}({})", x.function.to_source(), arguments)
+            }
+        }
+    }
 }
 
 impl Node for Expressions {
     fn token_literal(&self) -> String {
-        match self {
-            &Expressions::Identifier(ref x) => x.token.literal.clone(),
-            &Expressions::IntegerLiteral(ref x) => x.token.literal.clone(),
-            &Expressions::StringLiteral(ref x) => x.token.literal.clone(),
-            &Expressions::ArrayLiteral(ref x) => x.token.literal.clone(),
-            &Expressions::HashLiteral(ref x) => x.token.literal.clone(),
-            &Expressions::PrefixExpression(ref x) => x.token.literal.clone(),
-            &Expressions::InfixExpression(ref x) => x.token.literal.clone(),
-            &Expressions::Boolean(ref x) => x.token.literal.clone(),
-            &Expressions::IfExpression(ref x) => x.token.literal.clone(),
-            &Expressions::FunctionLiteral(ref x) => x.token.literal.clone(),
-            &Expressions::CallExpression(ref x) => x.token.literal.clone(),
-            &Expressions::IndexExpression(ref x) => x.token.literal.clone(),
+        match *self {
+            Expressions::Identifier(ref x) => x.token.literal.clone(),
+            Expressions::IntegerLiteral(ref x) => x.token.literal.clone(),
+            Expressions::FloatLiteral(ref x) => x.token.literal.clone(),
+            Expressions::StringLiteral(ref x) => x.token.literal.clone(),
+            Expressions::ArrayLiteral(ref x) => x.token.literal.clone(),
+            Expressions::HashLiteral(ref x) => x.token.literal.clone(),
+            Expressions::PrefixExpression(ref x) => x.token.literal.clone(),
+            Expressions::InfixExpression(ref x) => x.token.literal.clone(),
+            Expressions::Boolean(ref x) => x.token.literal.clone(),
+            Expressions::IfExpression(ref x) => x.token.literal.clone(),
+            Expressions::WhileExpression(ref x) => x.token.literal.clone(),
+            Expressions::FunctionLiteral(ref x) => x.token.literal.clone(),
+            Expressions::CallExpression(ref x) => x.token.literal.clone(),
+            Expressions::IndexExpression(ref x) => x.token.literal.clone(),
+            Expressions::SliceExpression(ref x) => x.token.literal.clone(),
         }
     }
 
     fn string(&self) -> String {
-        match self {
-            &Expressions::Identifier(ref x) => x.value.clone(),
-            &Expressions::IntegerLiteral(ref x) => format!("{}", x.value),
-            &Expressions::StringLiteral(ref x) => x.value.clone(),
-            &Expressions::ArrayLiteral(ref x) => {
+        match *self {
+            Expressions::Identifier(ref x) => x.value.clone(),
+            Expressions::IntegerLiteral(ref x) => format!("{}", x.value),
+            Expressions::FloatLiteral(ref x) => x.token.literal.clone(),
+            Expressions::StringLiteral(ref x) => x.value.clone(),
+            Expressions::ArrayLiteral(ref x) => {
                 let elements = &(x.elements)
                                     .iter()
                                     .map(|p| p.string())
@@ -205,21 +380,21 @@ impl Node for Expressions {
                                     .join(", ");
                 format!("[{}]", elements)
             }
-            &Expressions::HashLiteral(ref x) => {
+            Expressions::HashLiteral(ref x) => {
                 let mut pairs: Vec<String> = Vec::new();
                 for (key, value) in &x.pairs {
                     pairs.push(format!("{}: {}", key.string(), value.string()));
                 }
                 format!("{{{}}}", pairs.join(","))
             }
-            &Expressions::PrefixExpression(ref x) => {
+            Expressions::PrefixExpression(ref x) => {
                 format!("({}{})", x.operator, x.right.string())
             }
-            &Expressions::InfixExpression(ref x) => {
+            Expressions::InfixExpression(ref x) => {
                 format!("({} {} {})", x.left.string(), x.operator, x.right.string())
             }
-            &Expressions::Boolean(ref x) => format!("{}", x.value),
-            &Expressions::IfExpression(ref x) => {
+            Expressions::Boolean(ref x) => format!("{}", x.value),
+            Expressions::IfExpression(ref x) => {
                 match x.alternative {
                     Some(ref a) => {
                         format!("if {} {} else {}",
@@ -234,7 +409,10 @@ impl Node for Expressions {
                     }
                 }
             }
-            &Expressions::FunctionLiteral(ref x) => {
+            Expressions::WhileExpression(ref x) => {
+                format!("while {} {}", x.condition.string(), x.body.to_enum().string())
+            }
+            Expressions::FunctionLiteral(ref x) => {
                 let parameters_string = (&x.parameters)
                     .iter()
                     .map(|p| p.to_enum().string())
@@ -246,7 +424,7 @@ impl Node for Expressions {
                         parameters_string,
                         x.body.to_enum().string())
             }
-            &Expressions::CallExpression(ref x) => {
+            Expressions::CallExpression(ref x) => {
                 let arguments_string = (&x.arguments)
                     .iter()
                     .map(|p| p.string())
@@ -254,26 +432,35 @@ impl Node for Expressions {
                     .join(", ");
                 format!("{}({})", x.function.string(), arguments_string)
             }
-            &Expressions::IndexExpression(ref x) => {
+            Expressions::IndexExpression(ref x) => {
                 format!("({}[{}])", x.left.string(), x.index.string())
             }
+            Expressions::SliceExpression(ref x) => {
+                let bound = |b: &Option<Box<Expressions>>| {
+                    b.as_ref().map_or("".to_string(), |e| e.string())
+                };
+                format!("({}[{}:{}])", x.left.string(), bound(&x.start), bound(&x.end))
+            }
         }
     }
 
     fn to_ast(&self) -> AST {
-        match self {
-            &Expressions::Identifier(ref x) => AST::Identifier(x.clone()),
-            &Expressions::IntegerLiteral(ref x) => AST::IntegerLiteral(x.clone()),
-            &Expressions::StringLiteral(ref x) => AST::StringLiteral(x.clone()),
-            &Expressions::ArrayLiteral(ref x) => AST::ArrayLiteral(x.clone()),
-            &Expressions::HashLiteral(ref x) => AST::HashLiteral(x.clone()),
-            &Expressions::PrefixExpression(ref x) => AST::PrefixExpression(x.clone()),
-            &Expressions::InfixExpression(ref x) => AST::InfixExpression(x.clone()),
-            &Expressions::Boolean(ref x) => AST::Boolean(x.clone()),
-            &Expressions::IfExpression(ref x) => AST::IfExpression(x.clone()),
-            &Expressions::FunctionLiteral(ref x) => AST::FunctionLiteral(x.clone()),
-            &Expressions::CallExpression(ref x) => AST::CallExpression(x.clone()),
-            &Expressions::IndexExpression(ref x) => AST::IndexExpression(x.clone()),
+        match *self {
+            Expressions::Identifier(ref x) => AST::Identifier(x.clone()),
+            Expressions::IntegerLiteral(ref x) => AST::IntegerLiteral(x.clone()),
+            Expressions::FloatLiteral(ref x) => AST::FloatLiteral(x.clone()),
+            Expressions::StringLiteral(ref x) => AST::StringLiteral(x.clone()),
+            Expressions::ArrayLiteral(ref x) => AST::ArrayLiteral(x.clone()),
+            Expressions::HashLiteral(ref x) => AST::HashLiteral(x.clone()),
+            Expressions::PrefixExpression(ref x) => AST::PrefixExpression(x.clone()),
+            Expressions::InfixExpression(ref x) => AST::InfixExpression(x.clone()),
+            Expressions::Boolean(ref x) => AST::Boolean(x.clone()),
+            Expressions::IfExpression(ref x) => AST::IfExpression(x.clone()),
+            Expressions::WhileExpression(ref x) => AST::WhileExpression(x.clone()),
+            Expressions::FunctionLiteral(ref x) => AST::FunctionLiteral(x.clone()),
+            Expressions::CallExpression(ref x) => AST::CallExpression(x.clone()),
+            Expressions::IndexExpression(ref x) => AST::IndexExpression(x.clone()),
+            Expressions::SliceExpression(ref x) => AST::SliceExpression(x.clone()),
         }
     }
 }
@@ -301,6 +488,18 @@ impl BlockStatement {
     pub fn to_enum(&self) -> Statements {
         Statements::BlockStatement(self.clone())
     }
+
+    pub fn to_source(&self) -> String {
+        if self.statements.is_empty() {
+            return "{}".to_string();
+        }
+        let statements = self.statements
+            .iter()
+            .map(|s| s.to_source())
+            .collect::<Vec<_>>()
+            .join(" ");
+        format!("{{ {} }}", statements)
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -308,24 +507,74 @@ pub struct LetStatement {
     pub token: Token,
     pub name: Identifier,
     pub value: Expressions,
+    pub doc: Vec<String>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct ReturnStatement {
     pub token: Token,
-    pub return_value: Expressions,
+    pub return_value: Option<Expressions>,
+    pub doc: Vec<String>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct ExpressionStatement {
     pub token: Token,
     pub expression: Expressions,
+    pub doc: Vec<String>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TestStatement {
+    pub token: Token,
+    pub name: String,
+    pub body: BlockStatement,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct AssignStatement {
+    pub token: Token,
+    pub target: Expressions,
+    pub value: Expressions,
+}
+
+// Every value is a function literal; the parser rejects anything else.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct LetRecStatement {
+    pub token: Token,
+    pub bindings: Vec<LetStatement>,
+}
+
+#[derive(Debug, Clone)]
 pub struct Identifier {
     pub token: Token,
     pub value: String,
+    // Filled in by the resolver; None means the name is looked up by walking the enviroments.
+    pub resolved: Option<Resolved>,
+}
+
+// Identifiers are the same whether or not they have been resolved.
+impl PartialEq for Identifier {
+    fn eq(&self, other: &Identifier) -> bool {
+        self.token == other.token && self.value == other.value
+    }
+}
+
+impl Eq for Identifier {}
+
+impl Hash for Identifier {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.token.hash(state);
+        self.value.hash(state);
+    }
+}
+
+// Where a local binding lives: `depth` function scopes out from the use, at `slot` of that
+// scope.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Resolved {
+    pub depth: usize,
+    pub slot: usize,
 }
 
 impl Identifier {
@@ -337,7 +586,24 @@ impl Identifier {
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct IntegerLiteral {
     pub token: Token,
-    pub value: i32,
+    pub value: i64,
+}
+
+// The parser only builds literals from digits, so the value is never NaN and equality on
+// it is a full equivalence.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FloatLiteral {
+    pub token: Token,
+    pub value: f64,
+}
+
+impl Eq for FloatLiteral {}
+
+impl Hash for FloatLiteral {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.token.hash(state);
+        self.value.to_bits().hash(state);
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -378,6 +644,24 @@ impl HashLiteral {
         self.values.push(value.clone());
         self.pairs.insert(Box::new(key), Box::new(value));
     }
+
+    pub fn entries(&self) -> Vec<(&Expressions, &Expressions)> {
+        self.keys.iter().zip(self.values.iter()).collect()
+    }
+
+    // Rewrites every key and value in place, then rebuilds `pairs` from them.
+    pub fn update_entries<F>(&mut self, mut f: F)
+        where F: FnMut(&mut Expressions)
+    {
+        for x in self.keys.iter_mut().chain(self.values.iter_mut()) {
+            f(x);
+        }
+        self.pairs = self.keys
+            .iter()
+            .zip(self.values.iter())
+            .map(|(k, v)| (Box::new(k.clone()), Box::new(v.clone())))
+            .collect();
+    }
 }
 
 impl Hash for HashLiteral {
@@ -409,6 +693,15 @@ pub struct IndexExpression {
     pub index: Box<Expressions>,
 }
 
+// `left[start:end]`, where either bound may be left out.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SliceExpression {
+    pub token: Token,
+    pub left: Box<Expressions>,
+    pub start: Option<Box<Expressions>>,
+    pub end: Option<Box<Expressions>>,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Boolean {
     pub token: Token,
@@ -423,6 +716,13 @@ pub struct IfExpression {
     pub alternative: Option<BlockStatement>,
 }
 
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct WhileExpression {
+    pub token: Token,
+    pub condition: Box<Expressions>,
+    pub body: BlockStatement,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct FunctionLiteral {
     pub token: Token,
@@ -443,6 +743,15 @@ pub struct CallExpression {
     pub arguments: Vec<Box<Expressions>>,
 }
 
+pub fn function_source(parameters: &[Identifier], body: &BlockStatement) -> String {
+    let parameters = parameters
+        .iter()
+        .map(|p| p.value.clone())
+        .collect::<Vec<_>>()
+        .join(", ");
+    format!("fn({}) {}", parameters, body.to_source())
+}
+
 fn fold_statements(x: &Vec<Statements>) -> String {
     x.iter().fold("".to_string(), |acc, s| acc + &s.string())
 }
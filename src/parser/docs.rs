@@ -0,0 +1,74 @@
+use lexer::lexer::Lexer;
+use parser::parser::Parser;
+use parser::ast::{Statements, Expressions};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SymbolDoc {
+    pub name: String,
+    pub doc: String,
+    pub parameters: Option<Vec<String>>,
+}
+
+pub fn extract_docs(input: &str) -> Vec<SymbolDoc> {
+    let mut p = Parser::new(Lexer::new(input.to_string()));
+    let program = p.parse_program();
+
+    let mut docs = vec![];
+    for statement in program.statements.iter() {
+        if let Statements::LetStatement(ref x) = *statement {
+            let parameters = match x.value {
+                Expressions::FunctionLiteral(ref f) => {
+                    Some(f.parameters.iter().map(|p| p.value.clone()).collect())
+                }
+                _ => None,
+            };
+            docs.push(SymbolDoc {
+                          name: x.name.value.clone(),
+                          doc: x.doc.join("\n"),
+                          parameters,
+                      });
+        }
+    }
+    docs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_should_extract_docs() {
+        let input = "
+            // Adds two numbers.
+            // Both must be integers.
+            let add = fn(a, b) { a + b };
+
+            let double = fn(x) {
+                // not a top-level doc
+                let y = x * 2;
+                y
+            };
+
+            // The answer.
+            let answer = 42;
+            add(answer, 1);
+        ";
+
+        let expect = vec![SymbolDoc {
+                              name: "add".to_string(),
+                              doc: "Adds two numbers.\nBoth must be integers.".to_string(),
+                              parameters: Some(vec!["a".to_string(), "b".to_string()]),
+                          },
+                          SymbolDoc {
+                              name: "double".to_string(),
+                              doc: "".to_string(),
+                              parameters: Some(vec!["x".to_string()]),
+                          },
+                          SymbolDoc {
+                              name: "answer".to_string(),
+                              doc: "The answer.".to_string(),
+                              parameters: None,
+                          }];
+        assert_eq!(extract_docs(input), expect);
+    }
+}
@@ -1,2 +1,5 @@
 pub mod parser;
 pub mod ast;
+pub mod printer;
+pub mod docs;
+pub mod resolver;
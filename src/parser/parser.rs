@@ -1,16 +1,67 @@
+use std::collections::VecDeque;
 use std::str::FromStr;
 use lexer::token::{Token, TokenType};
 use lexer::lexer::Lexer;
-use parser::ast::{Program, LetStatement, ReturnStatement, ExpressionStatement, Identifier,
+use parser::ast::{Node, Program, LetStatement, ReturnStatement, ExpressionStatement, Identifier,
                   PrefixExpression, InfixExpression, IntegerLiteral, Boolean, IfExpression,
-                  BlockStatement, FunctionLiteral, CallExpression, Statements, Expressions,
-                  StringLiteral, ArrayLiteral, IndexExpression, HashLiteral};
+                  WhileExpression, BlockStatement, FunctionLiteral, CallExpression, Statements,
+                  Expressions, FloatLiteral,
+                  StringLiteral, ArrayLiteral, IndexExpression, SliceExpression, HashLiteral,
+                  TestStatement, AssignStatement, LetRecStatement};
+use parser::resolver;
+
+pub const DEFAULT_MAX_DEPTH: usize = 64;
+
+pub fn parse_statements_iter(source: &str) -> StatementIter {
+    StatementIter {
+        parser: Parser::new(Lexer::new(source.to_string())),
+        pending: VecDeque::new(),
+        done: false,
+    }
+}
+
+// Yields top-level statements as they are parsed. The first statement with parse errors
+// yields those errors instead and ends the iteration.
+pub struct StatementIter {
+    parser: Parser,
+    pending: VecDeque<Statements>,
+    done: bool,
+}
+
+impl Iterator for StatementIter {
+    type Item = Result<Statements, Vec<String>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(statement) = self.pending.pop_front() {
+                return Some(Ok(statement));
+            }
+            if self.done || self.parser.current_token_is(TokenType::EOF) {
+                return None;
+            }
 
+            let seen = self.parser.errors.len();
+            let statements = self.parser.parse_statement();
+            self.parser.next_token();
+            if self.parser.errors.len() > seen {
+                self.done = true;
+                return Some(Err(self.parser.errors[seen..].to_vec()));
+            }
+            self.pending.extend(statements);
+        }
+    }
+}
+
+// Named in capitals like the TokenType variants they are derived from.
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Debug, PartialOrd, PartialEq, Ord, Eq)]
 enum Precedence {
     LOWEST,
+    LOGICALOR,
+    LOGICALAND,
     EQUALS,
     LESSGREATER,
+    RANGE,
     SUM,
     PRODUCT,
     PREFIX,
@@ -23,13 +74,19 @@ fn precendences(token: TokenType) -> Precedence {
     use self::TokenType::*;
 
     match token {
+        OR => LOGICALOR,
+        AND => LOGICALAND,
         EQ => EQUALS,
         NOTEQ => EQUALS,
         LT => LESSGREATER,
         GT => LESSGREATER,
+        IN => LESSGREATER,
+        DOTDOT => RANGE,
+        DOTDOTEQ => RANGE,
         PLUS => SUM,
         MINUS => SUM,
         DIVIDE => PRODUCT,
+        MODULO => PRODUCT,
         MULTIPLY => PRODUCT,
         LPAREN => CALL,
         LBRACKET => INDEX,
@@ -39,10 +96,9 @@ fn precendences(token: TokenType) -> Precedence {
 
 fn is_infix_operator(t: TokenType) -> bool {
     use self::TokenType::*;
-    match t {
-        PLUS | MINUS | DIVIDE | MULTIPLY | EQ | NOTEQ | LT | GT | LPAREN | LBRACKET => true,
-        _ => false,
-    }
+    matches!(t,
+             PLUS | MINUS | DIVIDE | MODULO | MULTIPLY | AND | OR | EQ | NOTEQ | LT | GT | IN |
+             DOTDOT | DOTDOTEQ | LPAREN | LBRACKET)
 }
 
 #[derive(Debug, Clone)]
@@ -51,10 +107,17 @@ pub struct Parser {
     current_token: Token,
     peek_token: Token,
     pub errors: Vec<String>,
+    depth: usize,
+    max_depth: usize,
+    too_deep: bool,
 }
 
 impl Parser {
-    pub fn new(mut lexer: Lexer) -> Self {
+    pub fn new(lexer: Lexer) -> Self {
+        Parser::with_max_depth(lexer, DEFAULT_MAX_DEPTH)
+    }
+
+    pub fn with_max_depth(mut lexer: Lexer, max_depth: usize) -> Self {
         let first = lexer.next_token();
         let second = lexer.next_token();
         Parser {
@@ -62,10 +125,16 @@ impl Parser {
             current_token: first,
             peek_token: second,
             errors: vec![],
+            depth: 0,
+            max_depth,
+            too_deep: false,
         }
     }
 
     fn next_token(&mut self) {
+        if self.too_deep {
+            return;
+        }
         self.current_token = self.peek_token.clone();
         self.peek_token = self.lexer.next_token();
     }
@@ -83,18 +152,66 @@ impl Parser {
 
         while self.current_token.token_type != TokenType::EOF {
             let statement = self.parse_statement();
-            statements.push(statement);
+            statements.extend(statement);
             self.next_token();
         }
 
-        Program { statements: statements }
+        let mut program = Program { statements: statements };
+        resolver::resolve(&mut program);
+        program
     }
 
-    fn parse_statement(&mut self) -> Statements {
+    fn parse_statement(&mut self) -> Vec<Statements> {
         match self.current_token.token_type {
-            TokenType::LET => Statements::new_let_statement(self.parse_let_statement()),
-            TokenType::RETURN => Statements::new_return_statement(self.parse_return_statement()),
-            _ => Statements::new_expression_statement(self.parse_expression_statement()),
+            TokenType::LET if self.peek_is_rec() => {
+                vec![Statements::new_let_rec_statement(self.parse_let_rec_statement())]
+            }
+            TokenType::LET => {
+                self.parse_let_statements()
+                    .into_iter()
+                    .map(Statements::new_let_statement)
+                    .collect()
+            }
+            TokenType::RETURN => {
+                vec![Statements::new_return_statement(self.parse_return_statement())]
+            }
+            TokenType::TEST => vec![Statements::new_test_statement(self.parse_test_statement())],
+            _ => {
+                let statement = self.parse_expression_statement();
+                if self.peek_token_is(TokenType::ASSIGN) {
+                    let target = statement.expression;
+                    vec![Statements::new_assign_statement(self.parse_assign_statement(target))]
+                } else {
+                    vec![Statements::new_expression_statement(statement)]
+                }
+            }
+        }
+    }
+
+    fn parse_assign_statement(&mut self, target: Expressions) -> AssignStatement {
+        self.next_token();
+        let token = self.current_token.clone();
+        match target {
+            Expressions::IndexExpression(_) |
+            Expressions::Identifier(_) => {}
+            _ => {
+                self.errors.push(format!("invalid assignment target {}, at line: {}, column: {}",
+                                         target.string(),
+                                         token.line_num,
+                                         token.column_num))
+            }
+        }
+        self.next_token();
+        let value = self.parse_expression(Precedence::LOWEST);
+
+        if self.peek_token_is(TokenType::SEMICOLON) {
+            self.next_token();
+        }
+
+        AssignStatement {
+            token,
+            target,
+            value,
         }
     }
 
@@ -107,14 +224,54 @@ impl Parser {
         }
 
         ExpressionStatement {
+            doc: current_token.comments.clone(),
             token: current_token,
             expression: expression,
         }
     }
 
     fn parse_expression(&mut self, precedence: Precedence) -> Expressions {
+        if self.depth >= self.max_depth {
+            return self.abort_too_deep();
+        }
+        self.depth += 1;
+        let expression = self.parse_nested_expression(precedence);
+        self.depth -= 1;
+        expression
+    }
+
+    // Stops the parser where it is: both tokens become EOF and next_token no longer reads
+    // from the lexer, so every enclosing loop unwinds without touching the rest of the input.
+    fn abort_too_deep(&mut self) -> Expressions {
+        self.errors.push(format!("expression too deeply nested, at line: {}, column: {}",
+                                 self.current_token.line_num,
+                                 self.current_token.column_num));
+        let eof = Token::new(String::new(),
+                             false,
+                             self.current_token.line_num,
+                             self.current_token.column_num);
+        self.current_token = eof.clone();
+        self.peek_token = eof;
+        self.too_deep = true;
+        self.invalid_expression()
+    }
+
+    // Stands in for an expression that failed to parse; the recorded error keeps the
+    // program from being evaluated.
+    fn invalid_expression(&self) -> Expressions {
+        Expressions::new_identifier(Identifier {
+                                        token: self.current_token.clone(),
+                                        value: self.current_token.literal.clone(),
+                                        resolved: None,
+                                    })
+    }
+
+    fn parse_nested_expression(&mut self, precedence: Precedence) -> Expressions {
         let token_type = self.current_token.token_type.clone();
-        let mut left = self.parse_prefix(token_type).unwrap();
+        let mut left = match self.parse_prefix(token_type) {
+            Some(x) => x,
+            None => return self.invalid_expression(),
+        };
 
         while !self.peek_token_is(TokenType::SEMICOLON) && precedence < self.peek_precedence() {
             let token_type = self.peek_token.token_type.clone();
@@ -134,6 +291,7 @@ impl Parser {
         match t {
             IDENT(_) => Some(self.parse_identifier()),
             INT(_) => self.parse_integer_literal(),
+            FLOAT(_) => self.parse_float_literal(),
             STRING(_) => Some(self.parse_string_literal()),
             BANG => Some(self.parse_prefix_expression()),
             MINUS => Some(self.parse_prefix_expression()),
@@ -143,8 +301,18 @@ impl Parser {
             LBRACKET => Some(self.parse_array_literal()),
             LBRACE => Some(self.parse_hash_literal()),
             IF => Some(self.parse_if_expression()),
+            WHILE => Some(self.parse_while_expression()),
             FUNCTION => Some(self.parse_function_literal()),
-            _ => None,
+            _ => {
+                if !self.too_deep {
+                    self.errors.push(format!("no prefix parse function for {:?} found, at line: \
+                                              {}, column: {}",
+                                             self.current_token.token_type,
+                                             self.current_token.line_num,
+                                             self.current_token.column_num));
+                }
+                None
+            }
         }
     }
 
@@ -161,20 +329,40 @@ impl Parser {
         let token = self.current_token.clone();
         let mut hash_map = HashLiteral::new(token);
 
-        while !self.peek_token_is(TokenType::RBRACE) {
+        while !self.peek_token_is(TokenType::RBRACE) && !self.peek_token_is(TokenType::EOF) {
             self.next_token();
-            let key = self.parse_expression(Precedence::LOWEST);
+            let key = self.parse_hash_key();
             self.expect_peek_token(TokenType::COLON);
             self.next_token();
 
             let value = self.parse_expression(Precedence::LOWEST);
             hash_map.set_pairs(key, value);
-            self.expect_peek_token(TokenType::COMMA);
+            if !self.peek_token_is(TokenType::RBRACE) && !self.expect_peek_token(TokenType::COMMA) {
+                break;
+            }
         }
         self.expect_peek_token(TokenType::RBRACE);
         Expressions::HashLiteral(hash_map)
     }
 
+    // A bare identifier directly followed by `:` is a string key, so `{name: 1}` means
+    // `{"name": 1}`. Any other key, including `name + "x"`, is an ordinary expression.
+    fn parse_hash_key(&mut self) -> Expressions {
+        if let TokenType::IDENT(ref name) = self.current_token.token_type {
+            if self.peek_token_is(TokenType::COLON) {
+                let token = Token::new(name.clone(),
+                                       true,
+                                       self.current_token.line_num,
+                                       self.current_token.column_num);
+                return Expressions::new_string_literal(StringLiteral {
+                                                           token,
+                                                           value: name.clone(),
+                                                       });
+            }
+        }
+        self.parse_expression(Precedence::LOWEST)
+    }
+
     fn parse_function_literal(&mut self) -> Expressions {
         let token = self.current_token.clone();
         self.expect_peek_token(TokenType::LPAREN);
@@ -201,6 +389,7 @@ impl Parser {
         identifiers.push(Identifier {
                              token: self.current_token.clone(),
                              value: self.current_token.literal.clone(),
+                             resolved: None,
                          });
         while self.peek_token_is(TokenType::COMMA) {
             self.next_token();
@@ -208,6 +397,7 @@ impl Parser {
             identifiers.push(Identifier {
                                  token: self.current_token.clone(),
                                  value: self.current_token.literal.clone(),
+                                 resolved: None,
                              });
         }
         self.expect_peek_token(TokenType::RPAREN);
@@ -247,14 +437,34 @@ impl Parser {
                                        })
     }
 
+    fn parse_while_expression(&mut self) -> Expressions {
+        let token = self.current_token.clone();
+        self.expect_peek_token(TokenType::LPAREN);
+        self.next_token();
+        let condition = self.parse_expression(Precedence::LOWEST);
+        self.expect_peek_token(TokenType::RPAREN);
+        self.expect_peek_token(TokenType::LBRACE);
+        let body = self.parse_block_statement();
+
+        Expressions::new_while_expression(WhileExpression {
+                                              token,
+                                              condition: Box::new(condition),
+                                              body,
+                                          })
+    }
+
     fn parse_block_statement(&mut self) -> BlockStatement {
         let token = self.current_token.clone();
         let mut statements: Vec<Statements> = vec![];
         self.next_token();
 
         while !self.current_token_is(TokenType::RBRACE) {
+            if self.current_token_is(TokenType::EOF) {
+                self.peek_error(TokenType::RBRACE);
+                break;
+            }
             let statement = self.parse_statement();
-            statements.push(statement);
+            statements.extend(statement);
             self.next_token();
         }
 
@@ -289,11 +499,17 @@ impl Parser {
             PLUS => self.parse_infix_expression(left),
             MINUS => self.parse_infix_expression(left),
             DIVIDE => self.parse_infix_expression(left),
+            MODULO => self.parse_infix_expression(left),
             MULTIPLY => self.parse_infix_expression(left),
             EQ => self.parse_infix_expression(left),
             NOTEQ => self.parse_infix_expression(left),
+            AND => self.parse_infix_expression(left),
+            OR => self.parse_infix_expression(left),
             LT => self.parse_infix_expression(left),
             GT => self.parse_infix_expression(left),
+            IN => self.parse_infix_expression(left),
+            DOTDOT => self.parse_infix_expression(left),
+            DOTDOTEQ => self.parse_infix_expression(left),
             LPAREN => self.parse_call_expression(left),
             LBRACKET => self.parse_index_expression(left),
             _ => left,
@@ -332,7 +548,14 @@ impl Parser {
     fn parse_index_expression(&mut self, left: Expressions) -> Expressions {
         let token = self.current_token.clone();
         self.next_token();
+        if self.current_token_is(TokenType::COLON) {
+            return self.parse_slice_expression(token, left, None);
+        }
         let index = self.parse_expression(Precedence::LOWEST);
+        if self.peek_token_is(TokenType::COLON) {
+            self.next_token();
+            return self.parse_slice_expression(token, left, Some(index));
+        }
         self.expect_peek_token(TokenType::RBRACKET);
         Expressions::IndexExpression(IndexExpression {
                                          token: token,
@@ -341,6 +564,27 @@ impl Parser {
                                      })
     }
 
+    // Called with the colon as the current token.
+    fn parse_slice_expression(&mut self,
+                              token: Token,
+                              left: Expressions,
+                              start: Option<Expressions>)
+                              -> Expressions {
+        let end = if self.peek_token_is(TokenType::RBRACKET) {
+            None
+        } else {
+            self.next_token();
+            Some(Box::new(self.parse_expression(Precedence::LOWEST)))
+        };
+        self.expect_peek_token(TokenType::RBRACKET);
+        Expressions::SliceExpression(SliceExpression {
+                                         token,
+                                         left: Box::new(left),
+                                         start: start.map(Box::new),
+                                         end,
+                                     })
+    }
+
     fn parse_call_expression(&mut self, function: Expressions) -> Expressions {
         let token = self.current_token.clone();
         let arguments = self.parse_expression_list(TokenType::RPAREN);
@@ -355,12 +599,13 @@ impl Parser {
         Expressions::new_identifier(Identifier {
                                         token: self.current_token.clone(),
                                         value: self.current_token.literal.clone(),
+                                        resolved: None,
                                     })
     }
 
     fn parse_integer_literal(&mut self) -> Option<Expressions> {
         let current_token = self.current_token.clone();
-        let value = i32::from_str(self.current_token
+        let value = i64::from_str(self.current_token
                                       .literal
                                       .as_str()
                                       .clone());
@@ -372,8 +617,28 @@ impl Parser {
                                                           value: s,
                                                       }))
             }
+            // The lexer only produces digits for INT, so overflow is the only way to fail.
             Err(_) => {
-                self.errors.push(format!("could not parse {:?} as integer", current_token));
+                self.errors.push(format!("integer literal too large: {}, at line: {}, column: {}",
+                                         current_token.literal,
+                                         current_token.line_num,
+                                         current_token.column_num));
+                None
+            }
+        }
+    }
+
+    fn parse_float_literal(&mut self) -> Option<Expressions> {
+        let current_token = self.current_token.clone();
+        match f64::from_str(current_token.literal.as_str()) {
+            Ok(value) => {
+                Some(Expressions::new_float_literal(FloatLiteral {
+                                                        token: current_token,
+                                                        value,
+                                                    }))
+            }
+            Err(_) => {
+                self.errors.push(format!("could not parse {:?} as float", current_token));
                 None
             }
         }
@@ -388,19 +653,44 @@ impl Parser {
 
     fn parse_return_statement(&mut self) -> ReturnStatement {
         let current_token = self.current_token.clone();
-        self.next_token();
-        let value = self.parse_expression(Precedence::LOWEST);
+        let value = if self.peek_token_is(TokenType::SEMICOLON) ||
+                       self.peek_token_is(TokenType::RBRACE) ||
+                       self.peek_token_is(TokenType::EOF) {
+            None
+        } else {
+            self.next_token();
+            Some(self.parse_expression(Precedence::LOWEST))
+        };
 
         if self.peek_token_is(TokenType::SEMICOLON) {
             self.next_token();
         }
 
         ReturnStatement {
+            doc: current_token.comments.clone(),
             token: current_token,
             return_value: value,
         }
     }
 
+    fn parse_let_statements(&mut self) -> Vec<LetStatement> {
+        let let_token = self.current_token.clone();
+        let mut statements = vec![self.parse_let_statement()];
+
+        while self.peek_token_is(TokenType::COMMA) {
+            self.next_token();
+            let mut statement = self.parse_let_statement();
+            statement.token = let_token.clone();
+            statement.doc = vec![];
+            statements.push(statement);
+        }
+
+        if self.peek_token_is(TokenType::SEMICOLON) {
+            self.next_token();
+        }
+        statements
+    }
+
     fn parse_let_statement(&mut self) -> LetStatement {
         let current_token = self.current_token.clone();
         let ident = self.peek_token.literal.clone();
@@ -410,6 +700,7 @@ impl Parser {
         let name = Identifier {
             token: self.current_token.clone(),
             value: self.current_token.literal.clone(),
+            resolved: None,
         };
 
         self.expect_peek_token(TokenType::ASSIGN);
@@ -417,14 +708,65 @@ impl Parser {
         self.next_token();
         let value = self.parse_expression(Precedence::LOWEST);
 
+        LetStatement {
+            name: name,
+            doc: current_token.comments.clone(),
+            token: current_token,
+            value: value,
+        }
+    }
+
+    // `and` is not a keyword: it is only read as a separator after a binding, where an
+    // identifier could not otherwise appear.
+    fn parse_let_rec_statement(&mut self) -> LetRecStatement {
+        let let_token = self.current_token.clone();
+        self.next_token();
+        let mut bindings = vec![];
+
+        loop {
+            let mut binding = self.parse_let_statement();
+            binding.doc = match bindings.is_empty() {
+                true => let_token.comments.clone(),
+                false => vec![],
+            };
+            binding.token = let_token.clone();
+            let is_function = matches!(binding.value, Expressions::FunctionLiteral(_));
+            if !is_function && !self.too_deep {
+                self.errors.push(format!("let rec binding {} is not a function literal, at \
+                                          line: {}, column: {}",
+                                         binding.name.value,
+                                         binding.name.token.line_num,
+                                         binding.name.token.column_num));
+            }
+            bindings.push(binding);
+
+            if !self.peek_token_is(TokenType::IDENT("and".to_string())) {
+                break;
+            }
+            self.next_token();
+        }
+
         if self.peek_token_is(TokenType::SEMICOLON) {
             self.next_token();
         }
 
-        LetStatement {
-            name: name,
+        LetRecStatement {
+            token: let_token,
+            bindings,
+        }
+    }
+
+    fn parse_test_statement(&mut self) -> TestStatement {
+        let current_token = self.current_token.clone();
+        let name = self.peek_token.literal.clone();
+
+        self.expect_peek_token(TokenType::STRING(name.clone()));
+        self.expect_peek_token(TokenType::LBRACE);
+
+        TestStatement {
             token: current_token,
-            value: value,
+            name,
+            body: self.parse_block_statement(),
         }
     }
 
@@ -441,6 +783,13 @@ impl Parser {
         self.current_token.token_type == t
     }
 
+    // Like `and`, `rec` is not a keyword. It starts a `let rec` only when another identifier
+    // follows it, so `let rec = 1;` still binds a variable named rec.
+    fn peek_is_rec(&self) -> bool {
+        self.peek_token_is(TokenType::IDENT("rec".to_string())) &&
+        matches!(self.lexer.clone().next_token().token_type, TokenType::IDENT(_))
+    }
+
     fn peek_token_is(&self, t: TokenType) -> bool {
         self.peek_token.token_type == t
     }
@@ -460,6 +809,9 @@ impl Parser {
     }
 
     fn peek_error(&mut self, t: TokenType) {
+        if self.too_deep {
+            return;
+        }
         self.errors.push(format!("expected next token to be {:?}, got {:?} instead, at line: {}, column: {}",
                                  t,
                                  self.peek_token.token_type,
@@ -553,6 +905,58 @@ mod tests {
         }
     }
 
+    #[test]
+    fn it_should_parse_multiple_let_bindings() {
+        let (statements, statements_count) = create_parsed_statement("
+            let a = 1, b = add(a, 2), c = [a, b];
+            let d = 4;
+        ");
+        assert_eq!(statements_count, 4);
+
+        let expects = [("a", "1"), ("b", "add(a, 2)"), ("c", "[a, b]"), ("d", "4")];
+        for i in 0..statements_count {
+            if let Statements::LetStatement(ls) = statements[i].clone() {
+                assert_eq!(ls.token.token_type, TokenType::LET);
+                assert_eq!(ls.name.value, expects[i].0);
+                assert_eq!(ls.value.string(), expects[i].1);
+            } else {
+                assert!(false);
+            }
+        }
+    }
+
+    #[test]
+    fn it_should_attach_leading_comments_as_doc() {
+        let (statements, statements_count) = create_parsed_statement("
+            // adds two numbers
+            let add = fn(a, b) { a + b }; // not a doc
+            add(1, 2);
+        ");
+        assert_eq!(statements_count, 2);
+
+        if let Statements::LetStatement(ls) = statements[0].clone() {
+            assert_eq!(ls.name.value, "add");
+            assert_eq!(ls.doc, vec!["adds two numbers".to_string()]);
+        } else {
+            assert!(false);
+        }
+        if let Statements::ExpressionStatement(es) = statements[1].clone() {
+            assert_eq!(es.doc, Vec::<String>::new());
+        } else {
+            assert!(false);
+        }
+    }
+
+    #[test]
+    fn it_should_stop_unterminated_block_at_eof() {
+        let l = lexer::Lexer::new("if (x) { 1; 2".to_string());
+        let mut parser = Parser::new(l);
+        parser.parse_program();
+        assert_eq!(parser.errors,
+                   vec!["expected next token to be RBRACE, got EOF instead, at line: 1, column: 15"
+                            .to_string()]);
+    }
+
     #[test]
     fn it_should_peek_error_syntax() {
         let (errors, errors_count) = create_parsed_error("
@@ -586,11 +990,96 @@ mod tests {
             let statement = &statements[0];
             assert_eq!(statement.token_literal(), "return");
             if let Statements::ReturnStatement(x) = statement.clone() {
-                assert_eq!(x.return_value.string(), expect.1);
+                assert_eq!(x.return_value.unwrap().string(), expect.1);
             }
         }
     }
 
+    #[test]
+    fn it_should_parse_assign_statement() {
+        let mut parser = Parser::new(lexer::Lexer::new("s[0] = \"b\"; s".to_string()));
+        let program = parser.parse_program();
+        assert_eq!(parser.errors.len(), 0);
+        assert_eq!(program.statements.len(), 2);
+        assert_eq!(program.statements[0].to_source(), "(s[0]) = \"b\";");
+
+        let mut parser = Parser::new(lexer::Lexer::new("x = x + 1".to_string()));
+        let program = parser.parse_program();
+        assert_eq!(parser.errors.len(), 0);
+        assert_eq!(program.statements[0].to_source(), "x = (x + 1);");
+
+        let mut parser = Parser::new(lexer::Lexer::new("1 + 2 = 3;".to_string()));
+        parser.parse_program();
+        assert_eq!(parser.errors,
+                   vec!["invalid assignment target (1 + 2), at line: 1, column: 7".to_string()]);
+    }
+
+    #[test]
+    fn it_should_iterate_top_level_statements() {
+        let statements = parse_statements_iter("let x = 1; let f = fn(a) { a; x }; f(2);")
+            .map(|s| s.unwrap().to_source())
+            .collect::<Vec<_>>();
+        assert_eq!(statements,
+                   vec!["let x = 1;".to_string(),
+                        "let f = fn(a) { a; x; };".to_string(),
+                        "f(2);".to_string()]);
+
+        let mut statements = parse_statements_iter("let x = 1; let = 2; let y = 3;");
+        assert!(statements.next().unwrap().is_ok());
+        assert!(statements.next().unwrap().is_err());
+        assert!(statements.next().is_none());
+    }
+
+    #[test]
+    fn it_should_reject_too_deeply_nested_input() {
+        let inputs = [format!("{}1{}", "(".repeat(100000), ")".repeat(100000)),
+                      format!("{}{}", "[".repeat(100000), "]".repeat(100000)),
+                      format!("{}1", "-".repeat(100000))];
+        for input in inputs.iter() {
+            let (parser, _) = create_program(input);
+            assert_eq!(parser.errors.len(), 1);
+            assert!(parser.errors[0].starts_with("expression too deeply nested"),
+                    "{}",
+                    parser.errors[0]);
+        }
+
+        let nested = format!("{}1{}",
+                             "(".repeat(DEFAULT_MAX_DEPTH - 1),
+                             ")".repeat(DEFAULT_MAX_DEPTH - 1));
+        let (parser, _) = create_program(&nested);
+        assert!(parser.errors.is_empty());
+
+        let mut parser = Parser::with_max_depth(lexer::Lexer::new("let x = [[[1]]];".to_string()),
+                                                3);
+        parser.parse_program();
+        assert_eq!(parser.errors,
+                   vec!["expression too deeply nested, at line: 1, column: 12".to_string()]);
+    }
+
+    #[test]
+    fn it_should_report_missing_prefix_parse_function() {
+        let (parser, _) = create_program("let x = ;");
+        assert_eq!(parser.errors,
+                   vec!["no prefix parse function for SEMICOLON found, at line: 1, column: 9"
+                            .to_string()]);
+    }
+
+    #[test]
+    fn it_should_parse_bare_return_statement() {
+        for input in ["return;", "return", "fn() { return }"].iter() {
+            let mut parser = Parser::new(lexer::Lexer::new(input.to_string()));
+            let program = parser.parse_program();
+            assert_eq!(parser.errors.len(), 0, "{}", input);
+            assert_eq!(program.statements.len(), 1);
+        }
+        let mut parser = Parser::new(lexer::Lexer::new("return;".to_string()));
+        if let Statements::ReturnStatement(x) = parser.parse_program().statements[0].clone() {
+            assert_eq!(x.return_value, None);
+        } else {
+            panic!("expected a return statement");
+        }
+    }
+
     #[test]
     fn it_should_parse_identifier_expression() {
         let (statements, statements_count) = create_parsed_statement("foobar");
@@ -622,6 +1111,33 @@ mod tests {
         assert!(false);
     }
 
+    #[test]
+    fn it_should_reject_too_large_integer_literals() {
+        let (statements, _) = create_parsed_statement("9223372036854775807;");
+        assert_eq!(statements[0].to_source(), "9223372036854775807;");
+
+        let (errors, _) = create_parsed_error("let x = 1;\n  9223372036854775808 + 1;");
+        assert_eq!(errors[0],
+                   "integer literal too large: 9223372036854775808, at line: 2, column: 3");
+        let (errors, _) = create_parsed_error("-99999999999999999999");
+        assert_eq!(errors[0],
+                   "integer literal too large: 99999999999999999999, at line: 1, column: 2");
+    }
+
+    #[test]
+    fn it_should_parse_float_literal_expression() {
+        let (statements, statements_count) = create_parsed_statement("3.25;");
+        assert_eq!(statements_count, 1);
+
+        if let Statements::ExpressionStatement(x) = statements[0].clone() {
+            assert_eq!(x.expression.token_literal(), "3.25");
+            if let Expressions::FloatLiteral(y) = x.expression {
+                return assert_eq!(y.value, 3.25);
+            }
+        }
+        assert!(false);
+    }
+
     #[test]
     fn it_should_parse_string_expression() {
         let (statements, statements_count) = create_parsed_statement(r#""hello world.";"#);
@@ -682,6 +1198,37 @@ mod tests {
         }
     }
 
+    #[test]
+    fn it_should_parse_while_expression() {
+        let (statements, statements_count) =
+            create_parsed_statement("while (i < 3) { let i = i + 1; }");
+        assert_eq!(statements_count, 1);
+        assert_eq!(statements[0].to_source(), "while ((i < 3)) { let i = (i + 1); };");
+
+        let (parser, _) = create_program("while i < 3 { i }");
+        assert_eq!(parser.errors[0],
+                   "expected next token to be LPAREN, got IDENT(\"i\") instead, at line: 1, \
+                    column: 7");
+    }
+
+    #[test]
+    fn it_should_parse_let_rec_statement() {
+        let (statements, statements_count) =
+            create_parsed_statement("let rec f = fn(n) { g(n) } and g = fn(n) { f(n) };");
+        assert_eq!(statements_count, 1);
+        assert_eq!(statements[0].to_source(),
+                   "let rec f = fn(n) { g(n); } and g = fn(n) { f(n); };");
+
+        let (parser, _) = create_program("let rec f = fn(n) { n } and g = 5;");
+        assert_eq!(parser.errors[0],
+                   "let rec binding g is not a function literal, at line: 1, column: 29");
+
+        let (parser, program) = create_program("let rec = 1; let f = fn(rec) { rec }; f(rec)");
+        assert!(parser.errors.is_empty(), "{:?}", parser.errors);
+        let sources = program.statements.iter().map(|x| x.to_source()).collect::<Vec<_>>();
+        assert_eq!(sources, vec!["let rec = 1;", "let f = fn(rec) { rec; };", "f(rec);"]);
+    }
+
     #[test]
     fn it_should_parse_if_else_expression() {
         let (statements, statements_count) = create_parsed_statement("if (x < y) {x} else {y}");
@@ -778,6 +1325,30 @@ mod tests {
         }
     }
 
+    #[test]
+    fn it_should_parse_slice_expression() {
+        let expects = [("a[1:3]", "(a[1:3]);"),
+                       ("a[:2]", "(a[:2]);"),
+                       ("a[1 + 1:]", "(a[(1 + 1):]);"),
+                       ("a[:]", "(a[:]);"),
+                       ("a[f(1):-1]", "(a[f(1):(-1)]);"),
+                       ("f()[1:][0]", "((f()[1:])[0]);")];
+        for expect in expects.iter() {
+            let (parser, program) = create_program(expect.0);
+            assert!(parser.errors.is_empty(), "{:?}", parser.errors);
+            assert_eq!(program.statements[0].to_source(), expect.1);
+        }
+
+        let (statements, _) = create_parsed_statement("a[1:];");
+        if let Statements::ExpressionStatement(x) = statements[0].clone() {
+            if let Expressions::SliceExpression(y) = x.expression {
+                assert_eq!(y.start.map(|s| s.string()), Some("1".to_string()));
+                return assert_eq!(y.end, None);
+            }
+        }
+        assert!(false);
+    }
+
     #[test]
     fn it_should_parse_call_expression() {
         let (statements, statements_count) = create_parsed_statement("add(1, 2 * 3, 4 + 5);");
@@ -890,7 +1461,10 @@ mod tests {
 
     #[test]
     fn it_should_parse_hash_literal_with_string() {
-        let (statements, statements_count) = create_parsed_statement(r#"{ "one": 1, "two": 2, "three": 3 }"#);
+        let (parser, program) = create_program(r#"{ "one": 1, "two": 2, "three": 3 }"#);
+        assert!(parser.errors.is_empty(), "{:?}", parser.errors);
+        let statements_count = program.statements.len();
+        let statements = program.statements;
         assert_eq!(statements_count, 1);
 
         if let Statements::ExpressionStatement(expression) = statements[0].clone() {
@@ -909,9 +1483,40 @@ mod tests {
         }
     }
 
+    #[test]
+    fn it_should_parse_identifier_hash_keys_as_strings() {
+        let (parser, program) = create_program(r#"{name: 1, "a" + "b": 2, k + "x": k}"#);
+        assert!(parser.errors.is_empty(), "{:?}", parser.errors);
+        let statements = program.statements;
+        if let Statements::ExpressionStatement(expression) = statements[0].clone() {
+            if let Expressions::HashLiteral(x) = expression.expression {
+                let entries = x.entries();
+                let keys = entries.iter().map(|e| e.0.to_source()).collect::<Vec<_>>();
+                assert_eq!(keys, vec!["\"name\"", "(\"a\" + \"b\")", "(k + \"x\")"]);
+                if let Expressions::StringLiteral(ref y) = *entries[0].0 {
+                    return assert_eq!(y.value, "name");
+                }
+            }
+        }
+        assert!(false);
+    }
+
+    #[test]
+    fn it_should_require_commas_between_hash_pairs() {
+        let (errors, _) = create_parsed_error(r#"{"a": 1 "b": 2}"#);
+        assert_eq!(errors[0],
+                   "expected next token to be COMMA, got STRING(\"b\") instead, at line: 1, \
+                    column: 9");
+        let (errors, count) = create_parsed_error(r#"{"a": 1, "b": 2,}"#);
+        assert_eq!(count, 0, "{:?}", errors);
+    }
+
     #[test]
     fn it_should_parse_empty_hash_literal() {
-        let (statements, statements_count) = create_parsed_statement(r#"{}"#);
+        let (parser, program) = create_program(r#"{}"#);
+        assert!(parser.errors.is_empty(), "{:?}", parser.errors);
+        let statements_count = program.statements.len();
+        let statements = program.statements;
         assert_eq!(statements_count, 1);
         if let Statements::ExpressionStatement(expression) = statements[0].clone() {
             if let Expressions::HashLiteral(x) = expression.expression {
@@ -922,7 +1527,10 @@ mod tests {
 
     #[test]
     fn it_should_parse_hash_expression() {
-        let (statements, statements_count) = create_parsed_statement(r#"{ "one": 0 + 1, "two": 10 - 8, "three": 15 / 5 }"#);
+        let (parser, program) = create_program(r#"{ "one": 0 + 1, "two": 10 - 8, "three": 15 / 5 }"#);
+        assert!(parser.errors.is_empty(), "{:?}", parser.errors);
+        let statements_count = program.statements.len();
+        let statements = program.statements;
         assert_eq!(statements_count, 1);
 
         if let Statements::ExpressionStatement(expression) = statements[0].clone() {
@@ -945,6 +1553,9 @@ mod tests {
     fn it_should_parse_operator_with_precedence() {
         let expects = [("-a * b", "((-a) * b)"),
                        ("!-a", "(!(-a))"),
+                       ("--a", "(-(-a))"),
+                       ("- -a", "(-(-a))"),
+                       ("a--b", "(a - (-b))"),
                        ("a + b + c", "((a + b) + c)"),
                        ("a + b - c", "((a + b) - c)"),
                        ("a * b * c", "((a * b) * c)"),
@@ -962,6 +1573,13 @@ mod tests {
                        ("1 + (2 + 3) + 4", "((1 + (2 + 3)) + 4)"),
                        ("(5 + 5) * 2", "((5 + 5) * 2)"),
                        ("2 / (5 + 5)", "(2 / (5 + 5))"),
+                       ("a + b % c * d", "(a + ((b % c) * d))"),
+                       ("a || b && c == d", "(a || (b && (c == d)))"),
+                       ("a && b || c", "((a && b) || c)"),
+                       ("a + 1 in b == c", "(((a + 1) in b) == c)"),
+                       ("!a in b && c", "(((!a) in b) && c)"),
+                       ("a..b + 1 < c", "((a .. (b + 1)) < c)"),
+                       ("0..=n * 2 in r", "((0 ..= (n * 2)) in r)"),
                        ("-(5 + 5)", "(-(5 + 5))"),
                        ("!(true == true)", "(!(true == true))"),
                        ("a + add(b * c) + d", "((a + add((b * c))) + d)"),
@@ -0,0 +1,177 @@
+use parser::ast::{Node, AST};
+
+fn label(node: &AST) -> String {
+    use self::AST::*;
+    match *node {
+        Program(_) => "Program".to_string(),
+        BlockStatement(_) => "BlockStatement".to_string(),
+        LetStatement(ref x) => format!("LetStatement {}", x.name.value),
+        ReturnStatement(_) => "ReturnStatement".to_string(),
+        ExpressionStatement(_) => "ExpressionStatement".to_string(),
+        TestStatement(ref x) => format!("TestStatement {:?}", x.name),
+        AssignStatement(_) => "AssignStatement".to_string(),
+        LetRecStatement(_) => "LetRecStatement".to_string(),
+        Identifier(ref x) => format!("Identifier {}", x.value),
+        IntegerLiteral(ref x) => format!("IntegerLiteral {}", x.value),
+        FloatLiteral(ref x) => format!("FloatLiteral {}", x.token.literal),
+        StringLiteral(ref x) => format!("StringLiteral {:?}", x.value),
+        ArrayLiteral(_) => "ArrayLiteral".to_string(),
+        HashLiteral(_) => "HashLiteral".to_string(),
+        PrefixExpression(ref x) => format!("PrefixExpression {}", x.operator),
+        InfixExpression(ref x) => format!("InfixExpression {}", x.operator),
+        IndexExpression(_) => "IndexExpression".to_string(),
+        SliceExpression(_) => "SliceExpression".to_string(),
+        Boolean(ref x) => format!("Boolean {}", x.value),
+        IfExpression(_) => "IfExpression".to_string(),
+        WhileExpression(_) => "WhileExpression".to_string(),
+        FunctionLiteral(ref x) => {
+            let parameters = x.parameters
+                .iter()
+                .map(|p| p.value.clone())
+                .collect::<Vec<_>>()
+                .join(", ");
+            format!("FunctionLiteral({})", parameters)
+        }
+        CallExpression(_) => "CallExpression".to_string(),
+    }
+}
+
+fn children(node: &AST) -> Vec<AST> {
+    use self::AST::*;
+    match *node {
+        Program(ref x) => x.statements.iter().map(|s| s.to_ast()).collect(),
+        BlockStatement(ref x) => x.statements.iter().map(|s| s.to_ast()).collect(),
+        LetStatement(ref x) => vec![x.value.to_ast()],
+        ReturnStatement(ref x) => x.return_value.iter().map(|v| v.to_ast()).collect(),
+        ExpressionStatement(ref x) => vec![x.expression.to_ast()],
+        TestStatement(ref x) => vec![x.body.to_enum().to_ast()],
+        AssignStatement(ref x) => vec![x.target.to_ast(), x.value.to_ast()],
+        LetRecStatement(ref x) => x.bindings.iter().map(|b| AST::LetStatement(b.clone())).collect(),
+        ArrayLiteral(ref x) => x.elements.iter().map(|e| e.to_ast()).collect(),
+        HashLiteral(ref x) => {
+            x.entries()
+                .iter()
+                .flat_map(|&(k, v)| vec![k.to_ast(), v.to_ast()])
+                .collect()
+        }
+        PrefixExpression(ref x) => vec![x.right.to_ast()],
+        InfixExpression(ref x) => vec![x.left.to_ast(), x.right.to_ast()],
+        IndexExpression(ref x) => vec![x.left.to_ast(), x.index.to_ast()],
+        SliceExpression(ref x) => {
+            let mut nodes = vec![x.left.to_ast()];
+            nodes.extend(x.start.iter().chain(x.end.iter()).map(|b| b.to_ast()));
+            nodes
+        }
+        IfExpression(ref x) => {
+            let mut nodes = vec![x.condition.to_ast(), x.consequence.to_enum().to_ast()];
+            if let Some(ref a) = x.alternative {
+                nodes.push(a.to_enum().to_ast());
+            }
+            nodes
+        }
+        WhileExpression(ref x) => vec![x.condition.to_ast(), x.body.to_enum().to_ast()],
+        FunctionLiteral(ref x) => vec![x.body.to_enum().to_ast()],
+        CallExpression(ref x) => {
+            let mut nodes = vec![x.function.to_ast()];
+            nodes.extend(x.arguments.iter().map(|a| a.to_ast()));
+            nodes
+        }
+        Identifier(_) | IntegerLiteral(_) | FloatLiteral(_) | StringLiteral(_) | Boolean(_) => {
+            vec![]
+        }
+    }
+}
+
+fn collect_lines(node: &AST, depth: usize, lines: &mut Vec<(usize, String)>) {
+    lines.push((depth, label(node)));
+    for child in children(node).iter() {
+        collect_lines(child, depth + 1, lines);
+    }
+}
+
+fn tree_lines(node: &AST) -> Vec<(usize, String)> {
+    let mut lines = vec![];
+    collect_lines(node, 0, &mut lines);
+    lines
+}
+
+pub fn print_tree(node: &AST) -> String {
+    tree_lines(node)
+        .iter()
+        .map(|&(depth, ref l)| format!("{}{}", "  ".repeat(depth), l))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+pub fn diff(expected: &AST, actual: &AST) -> Option<String> {
+    let expected_lines = tree_lines(expected);
+    let actual_lines = tree_lines(actual);
+    let len = expected_lines.len().max(actual_lines.len());
+
+    for i in 0..len {
+        let e = expected_lines.get(i);
+        let a = actual_lines.get(i);
+        if e == a {
+            continue;
+        }
+
+        let mut path: Vec<String> = vec![];
+        let mut depth = e.or(a).map(|&(d, _)| d).unwrap_or(0);
+        for &(d, ref l) in expected_lines[..i.min(expected_lines.len())].iter().rev() {
+            if d < depth {
+                path.insert(0, l.clone());
+                depth = d;
+            }
+        }
+
+        let describe = |x: Option<&(usize, String)>| match x {
+            Some((_, l)) => l.clone(),
+            None => "<missing>".to_string(),
+        };
+        return Some(format!("node {} differs under {}\n  expected: {}\n  actual:   {}",
+                            i + 1,
+                            path.join(" > "),
+                            describe(e),
+                            describe(a)));
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lexer::lexer::Lexer;
+    use parser::parser::Parser;
+
+    fn parse(input: &str) -> AST {
+        let mut p = Parser::new(Lexer::new(input.to_string()));
+        p.parse_program().to_enum().to_ast()
+    }
+
+    #[test]
+    fn it_should_print_tree() {
+        let expect = ["Program",
+                      "  LetStatement add",
+                      "    FunctionLiteral(x, y)",
+                      "      BlockStatement",
+                      "        ExpressionStatement",
+                      "          InfixExpression +",
+                      "            Identifier x",
+                      "            InfixExpression *",
+                      "              Identifier y",
+                      "              IntegerLiteral 2"];
+        assert_eq!(print_tree(&parse("let add = fn(x, y) { x + y * 2 };")),
+                   expect.join("\n"));
+    }
+
+    #[test]
+    fn it_should_diff_differing_node() {
+        assert_eq!(diff(&parse("1 + 2 * 3"), &parse("1 + 2 * 3")), None);
+        assert_eq!(diff(&parse("1 + 2 * 3"), &parse("1 + 2 * 4")).unwrap(),
+                   "node 7 differs under Program > ExpressionStatement > InfixExpression + > \
+                    InfixExpression *\n  expected: IntegerLiteral 3\n  actual:   IntegerLiteral 4");
+        assert_eq!(diff(&parse("f(1)"), &parse("f(1, 2)")).unwrap(),
+                   "node 6 differs under Program > ExpressionStatement > CallExpression\n  \
+                    expected: <missing>\n  actual:   IntegerLiteral 2");
+    }
+}
@@ -0,0 +1,341 @@
+use std::collections::HashMap;
+
+use parser::ast::{Program, Statements, Expressions, BlockStatement, FunctionLiteral, Identifier,
+                  Resolved};
+
+// Gives every identifier inside a function the scope and slot of the binding it refers to,
+// so the evaluator indexes into the enviroment instead of walking it by name. Only function
+// scopes have slots: top-level names stay looked up by name, since the REPL and test blocks
+// keep adding to the top-level enviroment between programs.
+//
+// Blocks don't open scopes and `let` may sit in a branch that never runs, so every name a
+// function body binds anywhere gets a slot up front. A slot that is still empty when read
+// falls back to the lookup by name, which finds the outer binding as before.
+pub fn resolve(program: &mut Program) {
+    let mut resolver = Resolver { scopes: vec![] };
+    resolver.statements(&mut program.statements);
+}
+
+struct Scope {
+    slots: HashMap<String, usize>,
+    len: usize,
+}
+
+impl Scope {
+    fn declare(&mut self, name: &str) {
+        if !self.slots.contains_key(name) {
+            self.slots.insert(name.to_string(), self.len);
+            self.len += 1;
+        }
+    }
+}
+
+struct Resolver {
+    scopes: Vec<Scope>,
+}
+
+impl Resolver {
+    fn statements(&mut self, xs: &mut [Statements]) {
+        for x in xs.iter_mut() {
+            self.statement(x);
+        }
+    }
+
+    fn block(&mut self, x: &mut BlockStatement) {
+        self.statements(&mut x.statements);
+    }
+
+    fn statement(&mut self, x: &mut Statements) {
+        match *x {
+            Statements::BlockStatement(ref mut x) => self.block(x),
+            Statements::LetStatement(ref mut x) => {
+                self.expression(&mut x.value);
+                self.identifier(&mut x.name);
+            }
+            Statements::ReturnStatement(ref mut x) => {
+                if let Some(ref mut v) = x.return_value {
+                    self.expression(v);
+                }
+            }
+            Statements::ExpressionStatement(ref mut x) => self.expression(&mut x.expression),
+            Statements::TestStatement(ref mut x) => self.block(&mut x.body),
+            Statements::AssignStatement(ref mut x) => {
+                self.expression(&mut x.target);
+                self.expression(&mut x.value);
+            }
+            Statements::LetRecStatement(ref mut x) => {
+                let group = x.bindings.iter().map(|b| b.name.value.clone()).collect::<Vec<_>>();
+                for binding in x.bindings.iter_mut() {
+                    self.identifier(&mut binding.name);
+                    match binding.value {
+                        Expressions::FunctionLiteral(ref mut f) => self.function(&group, f),
+                        ref mut v => self.expression(v),
+                    }
+                }
+            }
+        }
+    }
+
+    fn expression(&mut self, x: &mut Expressions) {
+        match *x {
+            Expressions::Identifier(ref mut x) => self.identifier(x),
+            Expressions::IntegerLiteral(_) |
+            Expressions::FloatLiteral(_) |
+            Expressions::StringLiteral(_) |
+            Expressions::Boolean(_) => {}
+            Expressions::ArrayLiteral(ref mut x) => {
+                for e in x.elements.iter_mut() {
+                    self.expression(e);
+                }
+            }
+            Expressions::HashLiteral(ref mut x) => x.update_entries(|e| self.expression(e)),
+            Expressions::PrefixExpression(ref mut x) => self.expression(&mut x.right),
+            Expressions::InfixExpression(ref mut x) => {
+                self.expression(&mut x.left);
+                self.expression(&mut x.right);
+            }
+            Expressions::IndexExpression(ref mut x) => {
+                self.expression(&mut x.left);
+                self.expression(&mut x.index);
+            }
+            Expressions::SliceExpression(ref mut x) => {
+                self.expression(&mut x.left);
+                for bound in x.start.iter_mut().chain(x.end.iter_mut()) {
+                    self.expression(bound);
+                }
+            }
+            Expressions::IfExpression(ref mut x) => {
+                self.expression(&mut x.condition);
+                self.block(&mut x.consequence);
+                if let Some(ref mut alternative) = x.alternative {
+                    self.block(alternative);
+                }
+            }
+            Expressions::WhileExpression(ref mut x) => {
+                self.expression(&mut x.condition);
+                self.block(&mut x.body);
+            }
+            Expressions::FunctionLiteral(ref mut x) => self.function(&[], x),
+            Expressions::CallExpression(ref mut x) => {
+                self.expression(&mut x.function);
+                for a in x.arguments.iter_mut() {
+                    self.expression(a);
+                }
+            }
+        }
+    }
+
+    // The names of a `let rec` group come first, in order: a call binds member i of its
+    // group to slot i. The parameters follow, then everything the body binds.
+    fn function(&mut self, group: &[String], x: &mut FunctionLiteral) {
+        let mut scope = Scope {
+            slots: group.iter().cloned().zip(0..).collect(),
+            len: group.len(),
+        };
+        for p in x.parameters.iter() {
+            scope.declare(&p.value);
+        }
+        declare_block(&x.body, &mut scope);
+
+        self.scopes.push(scope);
+        for p in x.parameters.iter_mut() {
+            self.identifier(p);
+        }
+        self.block(&mut x.body);
+        self.scopes.pop();
+    }
+
+    fn identifier(&self, x: &mut Identifier) {
+        x.resolved = self.scopes
+            .iter()
+            .rev()
+            .enumerate()
+            .filter_map(|(depth, s)| s.slots.get(&x.value).map(|&slot| Resolved { depth, slot }))
+            .next();
+    }
+}
+
+// Declares the names bound by `let` and `let rec` anywhere in the block, leaving out the
+// bodies of nested functions, which have scopes of their own.
+fn declare_block(x: &BlockStatement, scope: &mut Scope) {
+    for statement in x.statements.iter() {
+        match *statement {
+            Statements::BlockStatement(ref x) => declare_block(x, scope),
+            Statements::LetStatement(ref x) => {
+                declare_expression(&x.value, scope);
+                scope.declare(&x.name.value);
+            }
+            Statements::ReturnStatement(ref x) => {
+                if let Some(ref v) = x.return_value {
+                    declare_expression(v, scope);
+                }
+            }
+            Statements::ExpressionStatement(ref x) => declare_expression(&x.expression, scope),
+            Statements::TestStatement(ref x) => declare_block(&x.body, scope),
+            Statements::AssignStatement(ref x) => {
+                declare_expression(&x.target, scope);
+                declare_expression(&x.value, scope);
+            }
+            Statements::LetRecStatement(ref x) => {
+                for binding in x.bindings.iter() {
+                    scope.declare(&binding.name.value);
+                }
+            }
+        }
+    }
+}
+
+fn declare_expression(x: &Expressions, scope: &mut Scope) {
+    match *x {
+        Expressions::Identifier(_) |
+        Expressions::IntegerLiteral(_) |
+        Expressions::FloatLiteral(_) |
+        Expressions::StringLiteral(_) |
+        Expressions::Boolean(_) |
+        Expressions::FunctionLiteral(_) => {}
+        Expressions::ArrayLiteral(ref x) => {
+            for e in x.elements.iter() {
+                declare_expression(e, scope);
+            }
+        }
+        Expressions::HashLiteral(ref x) => {
+            for (k, v) in x.entries() {
+                declare_expression(k, scope);
+                declare_expression(v, scope);
+            }
+        }
+        Expressions::PrefixExpression(ref x) => declare_expression(&x.right, scope),
+        Expressions::InfixExpression(ref x) => {
+            declare_expression(&x.left, scope);
+            declare_expression(&x.right, scope);
+        }
+        Expressions::IndexExpression(ref x) => {
+            declare_expression(&x.left, scope);
+            declare_expression(&x.index, scope);
+        }
+        Expressions::SliceExpression(ref x) => {
+            declare_expression(&x.left, scope);
+            for bound in x.start.iter().chain(x.end.iter()) {
+                declare_expression(bound, scope);
+            }
+        }
+        Expressions::IfExpression(ref x) => {
+            declare_expression(&x.condition, scope);
+            declare_block(&x.consequence, scope);
+            if let Some(ref alternative) = x.alternative {
+                declare_block(alternative, scope);
+            }
+        }
+        Expressions::WhileExpression(ref x) => {
+            declare_expression(&x.condition, scope);
+            declare_block(&x.body, scope);
+        }
+        Expressions::CallExpression(ref x) => {
+            declare_expression(&x.function, scope);
+            for a in x.arguments.iter() {
+                declare_expression(a, scope);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lexer::lexer::Lexer;
+    use parser::ast::ExpressionStatement;
+    use parser::parser::Parser;
+
+    type Found = Vec<(String, Option<(usize, usize)>)>;
+
+    fn parse(input: &str) -> Program {
+        let mut p = Parser::new(Lexer::new(input.to_string()));
+        let program = p.parse_program();
+        assert!(p.errors.is_empty(), "{:?}", p.errors);
+        program
+    }
+
+    // Every identifier read in the statements, in source order, with its resolution.
+    fn collect(xs: &[Statements], out: &mut Found) {
+        for x in xs.iter() {
+            match *x {
+                Statements::LetStatement(ref x) => collect_expression(&x.value, out),
+                Statements::ExpressionStatement(ref x) => collect_expression(&x.expression, out),
+                _ => {}
+            }
+        }
+    }
+
+    fn collect_expression(x: &Expressions, out: &mut Found) {
+        match *x {
+            Expressions::Identifier(ref x) => {
+                out.push((x.value.clone(), x.resolved.map(|r| (r.depth, r.slot))))
+            }
+            Expressions::InfixExpression(ref x) => {
+                collect_expression(&x.left, out);
+                collect_expression(&x.right, out);
+            }
+            Expressions::IfExpression(ref x) => {
+                collect_expression(&x.condition, out);
+                collect(&x.consequence.statements, out);
+            }
+            Expressions::FunctionLiteral(ref x) => collect(&x.body.statements, out),
+            _ => {}
+        }
+    }
+
+    fn found(expects: &[(&str, Option<(usize, usize)>)]) -> Found {
+        expects.iter().map(|&(name, at)| (name.to_string(), at)).collect()
+    }
+
+    #[test]
+    fn it_should_resolve_locals_to_scopes_and_slots() {
+        let program = parse("let g = 1; g; fn(a, b) { let c = a; fn(d) { a + c + d + g } }");
+        let mut out = vec![];
+        collect(&program.statements, &mut out);
+        assert_eq!(out,
+                   found(&[("g", None),
+                           ("a", Some((0, 0))),
+                           ("a", Some((1, 0))),
+                           ("c", Some((1, 2))),
+                           ("d", Some((0, 0))),
+                           ("g", None)]));
+    }
+
+    #[test]
+    fn it_should_give_slots_to_lets_anywhere_in_the_body() {
+        let program = parse("fn(x) { x + y; if (x) { let y = 1; y }; fn() { let z = 2; z } }");
+        let mut out = vec![];
+        collect(&program.statements, &mut out);
+        assert_eq!(out,
+                   found(&[("x", Some((0, 0))),
+                           ("y", Some((0, 1))),
+                           ("x", Some((0, 0))),
+                           ("y", Some((0, 1))),
+                           ("z", Some((0, 0)))]));
+    }
+
+    #[test]
+    fn it_should_put_rec_group_names_first() {
+        let program = parse("fn() { let rec even = fn(n) { odd + n } and odd = fn(n) { even } }");
+        let body = match program.statements[0] {
+            Statements::ExpressionStatement(ExpressionStatement {
+                expression: Expressions::FunctionLiteral(ref f), ..
+            }) => f.body.statements.clone(),
+            _ => unreachable!(),
+        };
+        let bindings = match body[0] {
+            Statements::LetRecStatement(ref x) => x.bindings.clone(),
+            _ => unreachable!(),
+        };
+        assert_eq!(bindings.iter().map(|b| b.name.resolved.map(|r| r.slot)).collect::<Vec<_>>(),
+                   vec![Some(0), Some(1)]);
+
+        let mut out = vec![];
+        for b in bindings.iter() {
+            collect_expression(&b.value, &mut out);
+        }
+        assert_eq!(out,
+                   found(&[("odd", Some((0, 1))), ("n", Some((0, 2))), ("even", Some((0, 0)))]));
+    }
+}
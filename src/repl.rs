@@ -1,38 +1,410 @@
-use std::io::{self, Write};
+use std::env;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+use rustyline::DefaultEditor;
+use rustyline::error::ReadlineError;
 
 use lexer::lexer;
+use lexer::token::TokenType;
 use parser::parser;
 use parser::ast::Node;
+use parser::printer::print_tree;
 use evaluator::evaluator::eval;
-use evaluator::object::Enviroment;
+use evaluator::object::{Object, Enviroment};
+
+static HISTORY_FILE: &str = ".monkey_history";
+const MAX_HISTORY: usize = 1000;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReplResult {
+    pub object: Option<Object>,
+    pub errors: Vec<String>,
+    pub needs_more_input: bool,
+}
+
+pub fn is_incomplete(input: &str) -> bool {
+    if has_open_string(input) {
+        return true;
+    }
+
+    let mut l = lexer::Lexer::new(input.to_string());
+    let mut depth = 0;
+    loop {
+        match l.next_token().token_type {
+            TokenType::LPAREN | TokenType::LBRACE | TokenType::LBRACKET => depth += 1,
+            TokenType::RPAREN | TokenType::RBRACE | TokenType::RBRACKET => depth -= 1,
+            TokenType::EOF => break,
+            _ => {}
+        }
+    }
+    depth > 0
+}
+
+// Follows the lexer's rules: `\` escapes the next character inside a string, and a `"` in
+// a line comment does not open one.
+fn has_open_string(input: &str) -> bool {
+    let mut chars = input.chars().peekable();
+    let mut in_string = false;
+    while let Some(c) = chars.next() {
+        match (in_string, c) {
+            (true, '\\') => {
+                chars.next();
+            }
+            (_, '"') => in_string = !in_string,
+            (false, '/') if chars.peek() == Some(&'/') => {
+                while chars.peek().is_some_and(|&c| c != '\n') {
+                    chars.next();
+                }
+            }
+            _ => {}
+        }
+    }
+    in_string
+}
+
+pub fn eval_line(line: &str, env: &mut Enviroment) -> ReplResult {
+    if is_incomplete(line) {
+        return ReplResult {
+                   object: None,
+                   errors: vec![],
+                   needs_more_input: true,
+               };
+    }
+
+    let lex = lexer::Lexer::new(line.to_string());
+    let mut p = parser::Parser::new(lex);
+    let program = p.parse_program();
+
+    if !p.errors.is_empty() {
+        return ReplResult {
+                   object: None,
+                   errors: p.errors,
+                   needs_more_input: false,
+               };
+    }
+
+    ReplResult {
+        object: Some(eval(program.to_enum().to_ast(), env)),
+        errors: vec![],
+        needs_more_input: false,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    Print(String),
+    Quit,
+}
+
+// Lines starting with `:` are REPL commands rather than code. Returns what the command asks
+// the loop to do, or None when `line` is not a command.
+pub fn eval_command(line: &str, env: &mut Enviroment) -> Option<Command> {
+    let line = line.trim();
+    if !line.starts_with(':') {
+        return None;
+    }
+    let (name, rest) = match line.find(char::is_whitespace) {
+        Some(i) => (&line[..i], line[i..].trim()),
+        None => (line, ""),
+    };
+    Some(match name {
+             ":ast" => Command::Print(debug_ast(rest)),
+             ":env" => Command::Print(show_env(env)),
+             ":reset" => {
+                 *env = Enviroment::with_options(env.options().clone());
+                 Command::Print("environment reset".to_string())
+             }
+             ":quit" => Command::Quit,
+             _ => Command::Print(format!("unknown command: {}", name)),
+         })
+}
+
+// One `name = value` line per binding, with values shown as source where they have one.
+fn show_env(env: &Enviroment) -> String {
+    env.bindings()
+        .iter()
+        .map(|(name, value)| {
+                 let shown = value.to_source().unwrap_or_else(|| value.inspect_with(env.options()));
+                 format!("{} = {}", name, shown)
+             })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+// Prints the parse tree of `input` without evaluating it.
+fn debug_ast(input: &str) -> String {
+    let mut p = parser::Parser::new(lexer::Lexer::new(input.to_string()));
+    let program = p.parse_program();
+    if !p.errors.is_empty() {
+        return p.errors.join("\n");
+    }
+    print_tree(&program.to_enum().to_ast())
+}
+
+pub fn history_path() -> Option<PathBuf> {
+    env::var_os("HOME").map(|home| Path::new(&home).join(HISTORY_FILE))
+}
+
+pub fn load_history(path: &Path) -> Vec<String> {
+    match File::open(path) {
+        Ok(f) => {
+            BufReader::new(f)
+                .lines()
+                .map_while(Result::ok)
+                .filter(|line| !line.trim().is_empty())
+                .collect()
+        }
+        Err(_) => vec![],
+    }
+}
+
+// Appends a single line, so concurrent sessions interleave their history instead of
+// overwriting each other's.
+pub fn save_history(path: &Path, line: &str) -> io::Result<()> {
+    let mut f = OpenOptions::new().append(true).create(true).open(path)?;
+    writeln!(f, "{}", line)
+}
+
+// Drops all but the newest MAX_HISTORY lines and returns the ones kept. This runs once at
+// startup and replaces the file through a rename, so an interrupt never leaves it truncated.
+pub fn trim_history(path: &Path) -> io::Result<Vec<String>> {
+    let lines = load_history(path);
+    if lines.len() <= MAX_HISTORY {
+        return Ok(lines);
+    }
+    let kept = lines[lines.len() - MAX_HISTORY..].to_vec();
+    let tmp = path.with_extension("tmp");
+    {
+        let mut f = File::create(&tmp)?;
+        for l in kept.iter() {
+            writeln!(f, "{}", l)?;
+        }
+    }
+    fs::rename(&tmp, path)?;
+    Ok(kept)
+}
 
 pub fn run() {
     let prompt = ">>";
-    let mut scan = String::new();
+    let continuation_prompt = "..";
     let mut env = Enviroment::new();
+    let mut editor = DefaultEditor::new().expect("Failed to initialize line editor");
+    let history = history_path();
+    let mut scan = String::new();
 
-    print!("read print eval loop is started {}", prompt);
-    io::stdout().flush().unwrap();
+    if let Some(ref path) = history {
+        for line in trim_history(path).unwrap_or_else(|_| load_history(path)) {
+            let _ = editor.add_history_entry(line);
+        }
+    }
+
+    println!("read print eval loop is started");
 
     loop {
-        io::stdin().read_line(&mut scan).expect("Failed to read line");
+        let current_prompt = if scan.is_empty() {
+            prompt
+        } else {
+            continuation_prompt
+        };
+        let line = match editor.readline(current_prompt) {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) |
+            Err(ReadlineError::Eof) => break,
+            Err(e) => {
+                println!("{}", e);
+                break;
+            }
+        };
 
-        let lex = lexer::Lexer::new(scan.clone());
-        let mut p = parser::Parser::new(lex);
-        let program = p.parse_program();
+        if !line.trim().is_empty() {
+            let _ = editor.add_history_entry(line.as_str());
+            if let Some(ref path) = history {
+                let _ = save_history(path, &line);
+            }
+        }
 
-        if p.errors.len() > 0 {
-            for error in p.errors {
-                println!("{}", error);
+        if scan.is_empty() {
+            match eval_command(&line, &mut env) {
+                Some(Command::Print(output)) => {
+                    println!("{}", output);
+                    continue;
+                }
+                Some(Command::Quit) => break,
+                None => {}
             }
+        } else {
+            scan.push('\n');
+        }
+        scan.push_str(&line);
+
+        let result = eval_line(&scan, &mut env);
+        if result.needs_more_input {
             continue;
         }
+        scan = String::new();
 
-        let evaluated = eval(program.to_enum().to_ast(), &mut env);
-        println!("{:?}", evaluated.inspect());
-        scan = "".to_string();
-        print!("{}", prompt);
-        io::stdout().flush().unwrap();
+        for error in result.errors.iter() {
+            println!("{}", error);
+        }
+        if let Some(evaluated) = result.object {
+            println!("{:?}", evaluated.inspect_with(env.options()));
+        }
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process;
+    use evaluator::object::ErrorKind;
+
+    fn temp_history_path(name: &str) -> PathBuf {
+        env::temp_dir().join(format!("monkey_history_{}_{}", name, process::id()))
+    }
+
+    #[test]
+    fn it_should_round_trip_history_file() {
+        let path = temp_history_path("round_trip");
+        let _ = fs::remove_file(&path);
+
+        assert_eq!(load_history(&path), Vec::<String>::new());
+
+        save_history(&path, "let x = 5;").unwrap();
+        save_history(&path, "x + 1").unwrap();
+        assert_eq!(load_history(&path), vec!["let x = 5;".to_string(), "x + 1".to_string()]);
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn it_should_cap_history_file() {
+        let path = temp_history_path("cap");
+        let _ = fs::remove_file(&path);
+
+        for i in 0..MAX_HISTORY + 5 {
+            save_history(&path, &format!("{}", i)).unwrap();
+        }
+        assert_eq!(load_history(&path).len(), MAX_HISTORY + 5);
+
+        let history = trim_history(&path).unwrap();
+        assert_eq!(history, load_history(&path));
+        assert_eq!(history.len(), MAX_HISTORY);
+        assert_eq!(history.first(), Some(&"5".to_string()));
+        assert_eq!(history.last(), Some(&format!("{}", MAX_HISTORY + 4)));
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn it_should_eval_lines_with_shared_enviroment() {
+        let mut env = Enviroment::new();
+
+        let result = eval_line("let x = 5;", &mut env);
+        assert_eq!(result.object, Some(Object::new_int(5)));
+        assert!(result.errors.is_empty());
+
+        let partial = "let add = fn(a, b) {";
+        let result = eval_line(partial, &mut env);
+        assert!(result.needs_more_input);
+        assert_eq!(result.object, None);
+
+        let result = eval_line(&format!("{}\n  a + b\n}};", partial), &mut env);
+        assert!(!result.needs_more_input);
+
+        let result = eval_line("let = 1;", &mut env);
+        assert_eq!(result.object, None);
+        assert_eq!(result.errors.len(), 1);
+
+        let result = eval_line("add(x, 2)", &mut env);
+        assert_eq!(result.object, Some(Object::new_int(7)));
+    }
+
+    #[test]
+    fn it_should_keep_evaluating_after_runtime_error() {
+        let mut env = Enviroment::new();
+
+        let result = eval_line("let x = 10;", &mut env);
+        assert_eq!(result.object, Some(Object::new_int(10)));
+
+        let result = eval_line("x / 0", &mut env);
+        assert_eq!(result.object.unwrap().to_error_message(),
+                   Some("division by zero, at line: 1, column: 3".to_string()));
+
+        let result = eval_line("x % 0", &mut env);
+        assert_eq!(result.object.unwrap().to_error_message(),
+                   Some("division by zero, at line: 1, column: 3".to_string()));
+
+        let result = eval_line("x / 2", &mut env);
+        assert_eq!(result.object, Some(Object::new_int(5)));
+
+        let result = eval_line("let y = x + 1; y / 0; let z = 3;", &mut env);
+        assert!(result.object.unwrap().to_error_message().is_some());
+        let result = eval_line("[x, y, z]", &mut env);
+        assert_eq!(result.object.unwrap().to_error_kind(), Some(ErrorKind::NameError));
+        let result = eval_line("[x, y]", &mut env);
+        assert_eq!(result.object.and_then(|x| x.to_source()), Some("[10, 11]".to_string()));
+    }
+
+    #[test]
+    fn it_should_print_ast_for_command() {
+        let expect = ["Program",
+                      "  ExpressionStatement",
+                      "    InfixExpression +",
+                      "      IntegerLiteral 1",
+                      "      InfixExpression *",
+                      "        IntegerLiteral 2",
+                      "        IntegerLiteral 3"];
+        let mut env = Enviroment::new();
+        let print = |x: &str| Some(Command::Print(x.to_string()));
+        assert_eq!(eval_command(":ast 1 + 2 * 3", &mut env), print(&expect.join("\n")));
+        assert_eq!(eval_command("  :ast   let x = 1;", &mut env),
+                   print("Program\n  LetStatement x\n    IntegerLiteral 1"));
+        assert_eq!(eval_command(":ast", &mut env), print("Program"));
+        match eval_command(":ast let = 1;", &mut env) {
+            Some(Command::Print(x)) => assert_eq!(x.lines().count(), 1),
+            x => panic!("unexpected {:?}", x),
+        }
+        assert_eq!(eval_command(":nope 1", &mut env), print("unknown command: :nope"));
+        assert_eq!(eval_command("1 + 2", &mut env), None);
+        assert_eq!(env, Enviroment::new());
+    }
+
+    #[test]
+    fn it_should_handle_env_reset_and_quit_commands() {
+        let mut env = Enviroment::new();
+        let print = |x: &str| Some(Command::Print(x.to_string()));
+        assert_eq!(eval_command(":env", &mut env), print(""));
+
+        eval_line("let b = [1, \"x\"]; let a = 2;", &mut env);
+        assert_eq!(eval_command(":env", &mut env), print("a = 2\nb = [1, \"x\"]"));
+
+        assert_eq!(eval_command(" :reset ", &mut env), print("environment reset"));
+        assert_eq!(eval_command(":env", &mut env), print(""));
+        assert_eq!(eval_line("a", &mut env).object.unwrap().to_error_kind(),
+                   Some(ErrorKind::NameError));
+
+        assert_eq!(eval_command(":quit", &mut env), Some(Command::Quit));
+    }
+
+    #[test]
+    fn it_should_detect_incomplete_input() {
+        let expects = [("let x = 5;", false),
+                       ("fn(x) {", true),
+                       ("add(1, [2, 3]", true),
+                       ("\"unterminated", true),
+                       ("fn(x) { if (x) { x } }", false),
+                       ("fn(x) {\n  if (x) {\n    [x, {1: 2}]\n  }", true),
+                       ("fn(x) {\n  if (x) {\n    [x, {1: 2}]\n  }\n}", false),
+                       ("puts(\"{\")", false),
+                       ("puts(\"}\"); fn() {", true),
+                       ("puts(\"a \\\"quoted\\\" {\")", false),
+                       ("puts(\"a \\\"quoted", true),
+                       ("1 // a \" or { in a comment", false),
+                       ("fn() { // }\n", true)];
+        for expect in expects.iter() {
+            assert_eq!(is_incomplete(expect.0), expect.1, "{:?}", expect.0);
+        }
+    }
+}
@@ -0,0 +1,228 @@
+use lexer::lexer::Lexer;
+use parser::parser::{Parser, parse_statements_iter};
+use parser::ast::{Node, Statements};
+use evaluator::evaluator::{eval, NULL};
+use evaluator::object::{Enviroment, Object, ObjectType};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TestReport {
+    pub passed: Vec<String>,
+    pub failed: Vec<(String, String)>,
+}
+
+impl TestReport {
+    pub fn lines(&self) -> Vec<String> {
+        let mut lines = vec![];
+        for name in self.passed.iter() {
+            lines.push(format!("test {} ... ok", name));
+        }
+        for (name, message) in self.failed.iter() {
+            lines.push(format!("test {} ... FAILED: {}", name, message));
+        }
+        lines.push(format!("{} passed, {} failed", self.passed.len(), self.failed.len()));
+        lines
+    }
+}
+
+pub fn run_tests(input: &str) -> Result<TestReport, Vec<String>> {
+    let mut p = Parser::new(Lexer::new(input.to_string()));
+    let program = p.parse_program();
+    if !p.errors.is_empty() {
+        return Err(p.errors);
+    }
+
+    let mut env = Enviroment::new();
+    if let ObjectType::Error(_, message, _) = eval(program.to_enum().to_ast(), &mut env).object_type {
+        return Err(vec![message]);
+    }
+
+    let mut report = TestReport {
+        passed: vec![],
+        failed: vec![],
+    };
+    // Tests share no bindings with each other, so one that reassigns a top-level name
+    // leaves it unchanged for the next.
+    let snapshot = env.snapshot();
+    for statement in program.statements.iter() {
+        if let Statements::TestStatement(ref t) = *statement {
+            env.restore(snapshot.clone());
+            let mut test_env = Enviroment::new_enclosed_enviroment(env.clone());
+            match eval(t.body.to_enum().to_ast(), &mut test_env).object_type {
+                ObjectType::Error(_, message, _) => report.failed.push((t.name.clone(), message)),
+                _ => report.passed.push(t.name.clone()),
+            }
+        }
+    }
+    Ok(report)
+}
+
+// Evaluates each top-level statement as soon as it is parsed, so the program is never
+// held in memory as a whole. Statements before a parse error have already run.
+pub fn run_script(input: &str, env: &mut Enviroment) -> Result<Object, Vec<String>> {
+    let mut result = NULL;
+    for statement in parse_statements_iter(input) {
+        result = eval(statement?.to_ast(), env);
+        match result.object_type {
+            ObjectType::Return(x) => return Ok(*x),
+            ObjectType::Error(_, message, _) => return Err(vec![message]),
+            _ => {}
+        }
+    }
+    Ok(result)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StatementError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl StatementError {
+    pub fn line(&self) -> String {
+        format!("error in statement at line: {}, column: {}: {}",
+                self.line,
+                self.column,
+                self.message)
+    }
+}
+
+// Like run_script, but a top-level statement that fails is recorded and the run carries on
+// with the next one, so several problems can be reported at once. A parse error still ends
+// the run, as the rest of the input cannot be split into statements reliably.
+pub fn run_script_collecting(input: &str,
+                             env: &mut Enviroment)
+                             -> Result<(Object, Vec<StatementError>), Vec<String>> {
+    let mut result = NULL;
+    let mut errors = vec![];
+    for statement in parse_statements_iter(input) {
+        let statement = statement?;
+        result = eval(statement.to_ast(), env);
+        match result.object_type {
+            ObjectType::Return(x) => return Ok((*x, errors)),
+            ObjectType::Error(_, ref message, _) => {
+                let token = statement.token();
+                errors.push(StatementError {
+                                line: token.line_num,
+                                column: token.column_num,
+                                message: message.clone(),
+                            });
+            }
+            _ => {}
+        }
+    }
+    Ok((result, errors))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_should_run_test_blocks() {
+        let input = "
+            let add = fn(a, b) { a + b };
+            test \"adds\" {
+                assert_eq(add(1, 2), 3);
+            }
+            test \"subtracts\" {
+                let x = 10;
+                assert_eq(add(x, -2), 7);
+            }
+        ";
+        let report = run_tests(input).unwrap();
+        assert_eq!(report.passed, vec!["adds".to_string()]);
+        assert_eq!(report.failed,
+                   vec![("subtracts".to_string(),
+                         "assertion failed: got 8, expected 7".to_string())]);
+        assert_eq!(report.lines(),
+                   vec!["test adds ... ok".to_string(),
+                        "test subtracts ... FAILED: assertion failed: got 8, expected 7"
+                            .to_string(),
+                        "1 passed, 1 failed".to_string()]);
+    }
+
+    #[test]
+    fn it_should_isolate_reassignments_between_tests() {
+        let input = "
+            let x = 1;
+            test \"reassigns\" { x = 5; assert_eq(x, 5); }
+            test \"sees the original\" { assert_eq(x, 1); }
+        ";
+        let report = run_tests(input).unwrap();
+        assert_eq!(report.passed,
+                   vec!["reassigns".to_string(), "sees the original".to_string()]);
+        assert!(report.failed.is_empty());
+    }
+
+    #[test]
+    fn it_should_run_script_like_whole_program() {
+        let inputs = ["let x = 2; let double = fn(a) { a * 2 }; double(x) + 1",
+                      "let x = 1; return x + 1; x + 10",
+                      "let a = [1, 2]; a[0] = 5; a",
+                      ""];
+        for input in inputs.iter() {
+            let mut p = Parser::new(Lexer::new(input.to_string()));
+            let program = p.parse_program();
+            let whole = eval(program.to_enum().to_ast(), &mut Enviroment::new());
+            assert_eq!(run_script(input, &mut Enviroment::new()), Ok(whole), "{}", input);
+        }
+
+        let mut env = Enviroment::new();
+        assert!(run_script("let x = 1; let = 2;", &mut env).is_err());
+        assert_eq!(env.get(&"x".to_string()), Some(Object::new_int(1)));
+        assert_eq!(run_script("let y = 1; y + true; let z = 2;", &mut env),
+                   Err(vec!["type mismatch: Integer(1) + Boolean(true), at line: 1, column: 14"
+                                .to_string()]));
+        assert_eq!(env.get(&"z".to_string()), None);
+    }
+
+    #[test]
+    fn it_should_collect_errors_from_every_statement() {
+        let input = "let x = 1;
+let y = x + true;
+len(1);
+let z = x * 2;
+foo;
+z";
+        let mut env = Enviroment::new();
+        let (result, errors) = run_script_collecting(input, &mut env).unwrap();
+        assert_eq!(result, Object::new_int(2));
+        assert_eq!(errors,
+                   vec![StatementError {
+                            line: 2,
+                            column: 1,
+                            message: "type mismatch: Integer(1) + Boolean(true), at line: 2, \
+                                      column: 11"
+                                .to_string(),
+                        },
+                        StatementError {
+                            line: 3,
+                            column: 1,
+                            message: "argument to \"len\" not supported. got Integer(1)"
+                                .to_string(),
+                        },
+                        StatementError {
+                            line: 5,
+                            column: 1,
+                            message: "identifier not found: foo".to_string(),
+                        }]);
+        assert_eq!(errors[2].line(),
+                   "error in statement at line: 5, column: 1: identifier not found: foo");
+        assert_eq!(env.get(&"y".to_string()), None);
+
+        assert_eq!(run_script_collecting("1 / 0; return 3; foo", &mut Enviroment::new())
+                       .map(|(r, e)| (r, e.len())),
+                   Ok((Object::new_int(3), 1)));
+        assert!(run_script_collecting("foo; let = 1;", &mut Enviroment::new()).is_err());
+    }
+
+    #[test]
+    fn it_should_skip_test_blocks_outside_test_mode() {
+        let mut p = Parser::new(Lexer::new("let x = 1; test \"x\" { assert_eq(x, 2) }".to_string()));
+        let program = p.parse_program();
+        assert!(p.errors.is_empty());
+        assert_eq!(eval(program.to_enum().to_ast(), &mut Enviroment::new()),
+                   ::evaluator::evaluator::NULL);
+    }
+}
@@ -13,6 +13,43 @@ pub fn is_digit(s: &String) -> bool {
 
 pub static EMPTY_STR: &'static str = "";
 
+// Writes a string value back as a literal, using the escapes the lexer understands.
+pub fn quote_string(s: &str) -> String {
+    let mut quoted = "\"".to_string();
+    for c in s.chars() {
+        match c {
+            '\n' => quoted.push_str("\\n"),
+            '\t' => quoted.push_str("\\t"),
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+// Splits seconds since the Unix epoch into UTC (year, month, day, hour, minute, second),
+// using the proleptic Gregorian calendar.
+pub fn utc_date_time(seconds: i64) -> (i64, i64, i64, i64, i64, i64) {
+    let days = seconds.div_euclid(86400);
+    let time = seconds.rem_euclid(86400);
+
+    // Count from 0000-03-01 so the leap day falls at the end of each year.
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z - era * 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 -
+                       day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    (year, month, day, time / 3600, time % 3600 / 60, time % 60)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -36,5 +73,20 @@ mod tests {
         assert!(is_digit(&"0".to_string()));
         assert!(is_digit(&"9".to_string()));
     }
+
+    #[test]
+    fn it_should_split_utc_date_time() {
+        assert_eq!(utc_date_time(0), (1970, 1, 1, 0, 0, 0));
+        assert_eq!(utc_date_time(951782400), (2000, 2, 29, 0, 0, 0));
+        assert_eq!(utc_date_time(1717245296), (2024, 6, 1, 12, 34, 56));
+        assert_eq!(utc_date_time(-1), (1969, 12, 31, 23, 59, 59));
+    }
+
+    #[test]
+    fn it_should_quote_string() {
+        assert_eq!(quote_string("abc"), "\"abc\"");
+        assert_eq!(quote_string("say \"hi\"\n\tand \\ bye"),
+                   "\"say \\\"hi\\\"\\n\\tand \\\\ bye\"");
+    }
 }
 
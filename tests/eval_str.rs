@@ -0,0 +1,89 @@
+extern crate monkey;
+
+use monkey::eval_str;
+use monkey::evaluator::object::{ErrorKind, Object};
+
+#[test]
+fn it_should_evaluate_arithmetic() {
+    assert_eq!(eval_str("1 + 2 * 3"), Ok(Object::new_int(7)));
+    assert_eq!(eval_str("(10 - 4) / 2; 7 % 4"), Ok(Object::new_int(3)));
+}
+
+#[test]
+fn it_should_evaluate_function_definitions() {
+    let input = "let add = fn(x, y) { x + y }; \
+                 let twice = fn(f, x) { f(f(x, x), x) }; \
+                 twice(add, 5)";
+    assert_eq!(eval_str(input), Ok(Object::new_int(15)));
+    assert_eq!(eval_str("let s = \"mon\"; s + \"key\"").unwrap().to_string(),
+               Some("monkey".to_string()));
+}
+
+#[test]
+fn it_should_return_parse_errors() {
+    let errors = eval_str("let = 5;").unwrap_err();
+    assert!(!errors.is_empty());
+    assert!(eval_str("fn(x { x }").is_err());
+}
+
+#[test]
+fn it_should_reject_integer_literals_out_of_range() {
+    assert_eq!(eval_str("9223372036854775807"), Ok(Object::new_int(i64::MAX)));
+    assert_eq!(eval_str("9223372036854775808"),
+               Err(vec!["integer literal too large: 9223372036854775808, at line: 1, column: 1"
+                            .to_string()]));
+}
+
+#[test]
+fn it_should_evaluate_hash_literals() {
+    assert_eq!(eval_str("{name: 1}[\"name\"]"), Ok(Object::new_int(1)));
+    assert_eq!(eval_str("let h = {\"a\": 1, b: 2}; h[\"a\"] + h[\"b\"]"),
+               Ok(Object::new_int(3)));
+}
+
+#[test]
+fn it_should_return_runtime_errors_as_objects() {
+    let result = eval_str("1 + true").unwrap();
+    assert_eq!(result.to_error_kind(), Some(ErrorKind::TypeError));
+}
+
+// Not a timing assertion, which would be flaky under test load. Run with --ignored.
+#[test]
+#[ignore]
+fn it_should_bench_builder_against_concatenation() {
+    let n = 2000;
+    let concat = format!("bench(fn() {{ let s = \"\"; let i = 0; \
+                          while (i < {}) {{ let s = s + \"fragment\"; let i = i + 1; }}; s }})",
+                         n);
+    let builder = format!("bench(fn() {{ let b = builder(); let i = 0; \
+                           while (i < {}) {{ build_push(b, \"fragment\"); let i = i + 1; }}; \
+                           build_string(b) }})",
+                          n);
+    for &(name, ref input) in [("concat", concat), ("builder", builder)].iter() {
+        let result = eval_str(input).unwrap().to_serde_value();
+        assert_eq!(result["result"], "fragment".repeat(n), "{}", name);
+        assert!(result["ms"].is_number(), "{}", name);
+    }
+}
+
+// Times variable lookups in a tight loop, from the loop's own scope and through three
+// enclosing function scopes. Run with --ignored.
+#[test]
+#[ignore]
+fn it_should_bench_identifier_lookups() {
+    let n = 5000;
+    let flat = format!("bench(fn() {{ let step = 1; let total = 0; let i = 0; \
+                        while (i < {}) {{ let total = total + step; let i = i + 1; }}; total }})",
+                       n);
+    let nested = format!("let outer = fn(step) {{ fn() {{ fn() {{ bench(fn() {{ \
+                          let total = 0; let i = 0; \
+                          while (i < {}) {{ let total = total + step; let i = i + 1; }}; \
+                          total }}) }} }} }}; \
+                          outer(1)()()",
+                         n);
+    for &(name, ref input) in [("flat", flat), ("nested", nested)].iter() {
+        let result = eval_str(input).unwrap().to_serde_value();
+        assert_eq!(result["result"], n, "{}", name);
+        assert!(result["ms"].is_number(), "{}", name);
+    }
+}